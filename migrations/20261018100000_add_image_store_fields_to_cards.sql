@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE cards ADD COLUMN image_path TEXT;
+ALTER TABLE cards ADD COLUMN image_hash TEXT;
\ No newline at end of file
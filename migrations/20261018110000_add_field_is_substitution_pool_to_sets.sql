@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE sets ADD COLUMN is_substitution_pool INTEGER NOT NULL DEFAULT 0;
+UPDATE sets SET is_substitution_pool = 1 WHERE short_name = 'Зал Славы';
\ No newline at end of file
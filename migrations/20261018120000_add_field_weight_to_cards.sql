@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE cards ADD COLUMN weight REAL NOT NULL DEFAULT 1 CHECK (weight >= 0);
\ No newline at end of file
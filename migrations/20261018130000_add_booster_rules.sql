@@ -0,0 +1,30 @@
+-- Add migration script here
+CREATE TABLE booster_rules (
+  id INTEGER PRIMARY KEY,
+  set_id INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  version INTEGER NOT NULL,
+  effective_from TEXT NOT NULL,
+  hall_of_fame_chanse REAL NOT NULL,
+  hall_of_fame_policy TEXT NOT NULL DEFAULT 'fallback',
+  hero_silver REAL NOT NULL,
+  hero_gold REAL NOT NULL,
+  command_bronze_silver_bronze REAL NOT NULL,
+  command_bronze_silver_silver REAL NOT NULL,
+  command_random_bronze REAL NOT NULL,
+  command_random_silver REAL NOT NULL,
+  command_random_gold REAL NOT NULL,
+  basic_card_silver REAL NOT NULL,
+  basic_card_gold REAL NOT NULL,
+  UNIQUE (set_id, name, version),
+  FOREIGN KEY (set_id) REFERENCES sets(id)
+);
+
+INSERT INTO booster_rules (
+  set_id, name, version, effective_from, hall_of_fame_chanse, hall_of_fame_policy,
+  hero_silver, hero_gold,
+  command_bronze_silver_bronze, command_bronze_silver_silver,
+  command_random_bronze, command_random_silver, command_random_gold,
+  basic_card_silver, basic_card_gold
+) VALUES
+  ((SELECT id FROM sets WHERE short_name = 'КОВ'), 'official', 1, '2025-10-21', 0.02, 'fallback', 0.8, 0.2, 0.75, 0.25, 0.7, 0.2, 0.1, 0.7, 0.3);
\ No newline at end of file
@@ -0,0 +1,35 @@
+-- Add migration script here
+ALTER TABLE cards ADD COLUMN cost INTEGER;
+ALTER TABLE cards ADD COLUMN text TEXT;
+
+-- Карта может принадлежать нескольким фракциям, карты без фракций нейтральны
+CREATE TABLE card_factions (
+  card_id INTEGER NOT NULL,
+  faction TEXT NOT NULL,
+  PRIMARY KEY (card_id, faction),
+  FOREIGN KEY (card_id) REFERENCES cards(id) ON DELETE CASCADE
+);
+
+-- Полнотекстовый индекс по названиям и тексту правил, синхронизируется триггерами
+CREATE VIRTUAL TABLE cards_fts USING fts5(
+  name,
+  text,
+  content = 'cards',
+  content_rowid = 'id',
+  tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER cards_fts_insert AFTER INSERT ON cards BEGIN
+  INSERT INTO cards_fts (rowid, name, text) VALUES (new.id, new.name, new.text);
+END;
+
+CREATE TRIGGER cards_fts_delete AFTER DELETE ON cards BEGIN
+  INSERT INTO cards_fts (cards_fts, rowid, name, text) VALUES ('delete', old.id, old.name, old.text);
+END;
+
+CREATE TRIGGER cards_fts_update AFTER UPDATE OF name, text ON cards BEGIN
+  INSERT INTO cards_fts (cards_fts, rowid, name, text) VALUES ('delete', old.id, old.name, old.text);
+  INSERT INTO cards_fts (rowid, name, text) VALUES (new.id, new.name, new.text);
+END;
+
+INSERT INTO cards_fts (cards_fts) VALUES ('rebuild');
\ No newline at end of file
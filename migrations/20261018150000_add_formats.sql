@@ -0,0 +1,32 @@
+-- Add migration script here
+CREATE TABLE formats (
+  id INTEGER PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  -- Сеты, вышедшие больше стольких месяцев назад, выходят из формата. NULL — без ротации
+  rotation_months INTEGER
+);
+
+-- Если для формата заданы сеты, легальны только они
+CREATE TABLE format_sets (
+  format_id INTEGER NOT NULL,
+  set_id INTEGER NOT NULL,
+  PRIMARY KEY (format_id, set_id),
+  FOREIGN KEY (format_id) REFERENCES formats(id),
+  FOREIGN KEY (set_id) REFERENCES sets(id)
+);
+
+-- История бан-листа: статус карты действует с effective_from до следующей записи
+CREATE TABLE format_bans (
+  id INTEGER PRIMARY KEY,
+  format_id INTEGER NOT NULL,
+  card_id INTEGER NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('legal', 'banned', 'restricted')),
+  effective_from TEXT NOT NULL,
+  UNIQUE (format_id, card_id, effective_from),
+  FOREIGN KEY (format_id) REFERENCES formats(id),
+  FOREIGN KEY (card_id) REFERENCES cards(id)
+);
+
+INSERT INTO formats (name, rotation_months) VALUES
+  ('standard', 24),
+  ('eternal', NULL);
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Цены карт по сету и номеру в сете, у карты может быть несколько вариантов (обычная, фольга и т.д.)
+CREATE TABLE prices (
+  id INTEGER PRIMARY KEY,
+  set_id INTEGER NOT NULL,
+  id_in_set INTEGER NOT NULL,
+  variant TEXT NOT NULL,
+  price REAL NOT NULL CHECK (price >= 0),
+  UNIQUE (set_id, id_in_set, variant),
+  FOREIGN KEY (set_id) REFERENCES sets(id)
+);
\ No newline at end of file
@@ -1,216 +1,69 @@
 use reqwest::{Client, Response};
 use tokio::time::{sleep, Duration};
-use serde::Deserialize;
-use serde_with::{serde_as, DisplayFromStr};
 use sqlx::SqlitePool;
-
-#[serde_as]
-#[derive(Deserialize, Debug)]
-struct CardJson {
-    name: String,
-    images: serde_json::Value,
-    #[serde_as(as = "DisplayFromStr")]
-    id_in_set: u32,
-    rarity: RarityJson,
-    // fractions: Vec<FractionJson>,
-    types: Vec<CardTypeJson>,
-    card_set: SetJson,
-}
-
-#[derive(Deserialize, Debug)]
-struct RarityJson {
-    display_name: String,
-}
-
-// #[derive(Deserialize, Debug)]
-// struct FractionJson {
-//     display_name: String,
-// }
-
-#[derive(Deserialize, Debug)]
-struct CardTypeJson {
-    display_name: String,
-}
-
-#[derive(Deserialize, Debug)]
-struct SetJson {
-    display_name: String,
-}
-
-#[derive(Debug)]
-struct Card {
-    name: String,
-    id_in_set: u32,
-    rarity: CardRarity,
-    buster_slot: BusterSlot,
-    set: CardSet,
-    image_url: Option<String>,
-}
-
-
-impl Card {
-    fn from_json(card_json: CardJson) -> Result<Self, String> {
-        let rarity = match card_json.rarity.display_name.as_str() {
-            "Бронза" => CardRarity::Bronze,
-            "Серебро" => CardRarity::Silver,
-            "Золото" => CardRarity::Gold,
-            _ => return Err(format!("Unknown rarity name: {}", card_json.rarity.display_name)),
-        };
-
-        // let fractions = card_json.fractions.iter().map(|fraction_json| {
-        //     match fraction_json.display_name.as_str() {
-        //         "Жизнь" => Ok(Fraction::Life),
-        //         "Порядок" => Ok(Fraction::Justice),
-        //         "Природа" => Ok(Fraction::Nature),
-        //         "Смерть" => Ok(Fraction::Death),
-        //         "Технологии" => Ok(Fraction::Technology),
-        //         "Хаос" => Ok(Fraction::Chaos),
-        //         _ => Err(format!("Unknown fraction name: {}", fraction_json.display_name)),
-        //     }
-        // }).collect::<Result<Vec<_>, String>>()?;
-
-        let buster_slot = if card_json.types.iter().any(|t| t.display_name.as_str() == "Герой") {
-            BusterSlot::Hero
-        } else if card_json.types.iter().any(|t| t.display_name.as_str() == "Приказ") {
-            BusterSlot::Command
-        } else {
-            BusterSlot::BasicCard
-        };
-
-        let set = match card_json.card_set.display_name.as_str() {
-            "БАЗ" => CardSet::BAZ,
-            "КОВ" => CardSet::KOV,
-            "Зал Славы" => CardSet::HallOfFame,
-            _ => return Err(format!("Unknown set name: {}", card_json.card_set.display_name).into()),
-        };
-
-        let image_url = card_json.images.get("card_path")
-            .and_then(|path| path.as_str().map(|s| RESPONSE_WORLD_URL.to_string() + s));
-
-        Ok(Card {
-            name: card_json.name,
-            id_in_set: card_json.id_in_set,
-            rarity,
-            // fractions,
-            buster_slot,
-            set,
-            image_url,
-        })
-    }
-}
-
-#[derive(Debug)]
-enum CardRarity {
-    Bronze,
-    Silver,
-    Gold,
-}
-
-impl CardRarity {
-    fn as_str(&self) -> &str {
-        match self {
-            CardRarity::Bronze => "Бронза",
-            CardRarity::Silver => "Серебро",
-            CardRarity::Gold => "Золото",
-        }
-    }
-}
-
-// #[derive(Debug)]
-// enum Fraction {
-//     Life,
-//     Justice,
-//     Nature,
-//     Death,
-//     Technology,
-//     Chaos,
-// }
-
-// impl Fraction {
-//     fn as_str(&self) -> &str {
-//         match self {
-//             Fraction::Life => "Жизнь",
-//             Fraction::Justice => "Порядок",
-//             Fraction::Nature => "Природа",
-//             Fraction::Death => "Смерть",
-//             Fraction::Technology => "Технологии",
-//             Fraction::Chaos => "Хаос",
-//         }
-//     }
-// }
-
-#[derive(Debug)]
-enum BusterSlot {
-    Hero,
-    Command,
-    BasicCard,
-}
-
-impl BusterSlot {
-    fn as_str(&self) -> &str {
-        match self {
-            BusterSlot::Hero => "Герой",
-            BusterSlot::Command => "Приказ",
-            BusterSlot::BasicCard => "Основная карта",
-        }
-    }
-}
-
-#[derive(Debug)]
-enum CardSet {
-    BAZ,
-    KOV,
-    HallOfFame,
-}
-
-impl CardSet {
-    fn as_str(&self) -> &str {
-        match self {
-            CardSet::BAZ => "БАЗ",
-            CardSet::KOV => "КОВ",
-            CardSet::HallOfFame => "Зал Славы",
-        }
-    }
-}
+use response_sim::{Card, SetRegistry, db};
+use response_sim::images::{self, ImageStore};
+use response_sim::import::{self, CardJson, ImportedCard, RESPONSE_WORLD_URL};
+use response_sim::scrape;
+use thirtyfour::{DesiredCapabilities, WebDriver};
 
 const LAST_CARD_ID: u32 = 415;
-const RESPONSE_WORLD_URL: &str = "https://response-world.ru/";
 const REQUEST_DELAY_MS: u64 = 100;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let pool = SqlitePool::connect(&std::env::var("DATABASE_URL")?).await?;
+    let pool = db::open_from_env().await?;
+    let image_store = ImageStore::new(images::IMAGE_STORE_DIR);
+    // Если задан WEBDRIVER_URL, HTML страницы рендерятся в браузере, иначе скачиваются как есть
+    let driver = match std::env::var("WEBDRIVER_URL") {
+        Ok(url) => Some(WebDriver::new(url, DesiredCapabilities::chrome()).await?),
+        Err(_) => None,
+    };
     let mut have_problem = false;
-    let base_url = RESPONSE_WORLD_URL.to_string() + "api/cards/";
+
+    match import_sets(&pool, driver.as_ref()).await {
+        Ok(count) => println!("SETS DONE: {}", count),
+        Err(e) => {
+            println!("SETS FAILED\nERROR: {}", e);
+            have_problem = true;
+        }
+    }
+
+    let sets = SetRegistry::load(&pool).await?;
     for card_id in 1..=LAST_CARD_ID {
-        let url = format!("{}{}", base_url, card_id);
-        let card_json_value: serde_json::Value = match get_with_retry(&url).await {
-            Ok(response) => {
+        let imported = match fetch_card_from_api(card_id, &sets).await {
+            Ok(imported) => {
                 println!("CARD #{} DONE", card_id);
-                response.json().await?
+                imported
             }
             Err(e) => {
-                println!("CARD #{} FAILED\nERROR: {}", card_id, e);
-                have_problem = true;
-                continue;
-            }
-        };
-        let card_json: CardJson = serde_json::from_value(card_json_value["card"].clone())?;
-        let card = match Card::from_json(card_json) {
-            Ok(card) => {
-                println!("Parsed card: {:?}", card);
-                card
-            }
-            Err(e) => {
-                println!("Failed to parse card #{}: {}", card_id, e);
-                have_problem = true;
-                continue;
+                println!("CARD #{} FAILED IN API, TRYING HTML\nERROR: {}", card_id, e);
+                match fetch_card_from_html(card_id, &sets, driver.as_ref()).await {
+                    Ok(imported) => {
+                        println!("CARD #{} DONE FROM HTML", card_id);
+                        imported
+                    }
+                    Err(e) => {
+                        println!("CARD #{} FAILED\nERROR: {}", card_id, e);
+                        have_problem = true;
+                        continue;
+                    }
+                }
             }
         };
-        insert_card_into_db(&pool, &card).await?;
+        println!("Parsed card: {:?}", imported);
+        insert_card_into_db(&pool, &imported).await?;
+        if let Err(e) = mirror_card_image(&pool, &image_store, &imported.card).await {
+            println!("Failed to mirror image of card #{}: {}", card_id, e);
+            have_problem = true;
+        }
         sleep(Duration::from_millis(REQUEST_DELAY_MS)).await;
     }
 
+    if let Some(driver) = driver {
+        driver.quit().await?;
+    }
+
     if have_problem {
         println!("Some cards failed to process.");
     } else {
@@ -219,13 +72,60 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn insert_card_into_db(pool: &SqlitePool, card: &Card) -> Result<(), Box<dyn std::error::Error>> {
-    let rarity = card.rarity.as_str();
-    let buster_slot = card.buster_slot.as_str();
-    let set = card.set.as_str();
+async fn fetch_card_from_api(card_id: u32, sets: &SetRegistry) -> Result<ImportedCard, Box<dyn std::error::Error>> {
+    let url = format!("{}api/cards/{}", RESPONSE_WORLD_URL, card_id);
+    let card_json_value: serde_json::Value = get_with_retry(&url).await?.json().await?;
+    let card_json: CardJson = serde_json::from_value(card_json_value["card"].clone())?;
+    import::card_from_json(card_json, sets)
+}
+
+async fn fetch_card_from_html(card_id: u32, sets: &SetRegistry, driver: Option<&WebDriver>) -> Result<ImportedCard, Box<dyn std::error::Error>> {
+    let url = format!("{}cards/{}", RESPONSE_WORLD_URL, card_id);
+    let html = fetch_html(&url, driver).await?;
+    import::card_from_scraped(scrape::parse_card_page(&html, RESPONSE_WORLD_URL)?, sets)
+}
+
+/// Загружает названия, даты выхода и количество карт сетов со страницы списка сетов
+async fn import_sets(pool: &SqlitePool, driver: Option<&WebDriver>) -> Result<usize, Box<dyn std::error::Error>> {
+    let url = format!("{}sets", RESPONSE_WORLD_URL);
+    let sets = scrape::parse_set_listing(&fetch_html(&url, driver).await?)?;
+    for set in sets.iter() {
+        sqlx::query!(
+            "INSERT INTO sets (name, short_name, release_date, card_count)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(short_name) DO UPDATE SET
+                    name = excluded.name,
+                    release_date = excluded.release_date,
+                    card_count = excluded.card_count",
+            set.name,
+            set.short_name,
+            set.release_date,
+            set.card_count,
+        ).execute(pool).await?;
+    }
+    Ok(sets.len())
+}
+
+async fn fetch_html(url: &str, driver: Option<&WebDriver>) -> Result<String, Box<dyn std::error::Error>> {
+    match driver {
+        Some(driver) => {
+            driver.goto(url).await?;
+            Ok(driver.source().await?)
+        }
+        None => Ok(get_with_retry(url).await?.text().await?),
+    }
+}
+
+/// Добавляет новую карту. Фракции, стоимость и текст правил дописываются и в уже
+/// загруженные карты, если раньше их не было
+async fn insert_card_into_db(pool: &SqlitePool, imported: &ImportedCard) -> Result<(), Box<dyn std::error::Error>> {
+    let card = &imported.card;
+    let rarity = card.rarity.db_name();
+    let buster_slot = card.buster_slot.db_name();
+    let set = &card.set.short_name;
     sqlx::query!(
         "INSERT 
-            INTO cards (id, name, rarity_id, type_id, set_id, id_in_set, image_url) 
+            INTO cards (id, name, rarity_id, type_id, set_id, id_in_set, image_url, cost, text) 
             SELECT 
                 (SELECT COALESCE(MAX(id), 0) + 1 FROM cards),
                 ?,
@@ -233,6 +133,8 @@ async fn insert_card_into_db(pool: &SqlitePool, card: &Card) -> Result<(), Box<d
                 (SELECT id FROM types WHERE name = ?),
                 (SELECT id FROM sets WHERE short_name = ?),
                 ?,
+                ?,
+                ?,
                 ?
             WHERE NOT EXISTS (
                 SELECT 1 FROM cards WHERE name = ? AND set_id = (SELECT id FROM sets WHERE short_name = ?)
@@ -243,6 +145,56 @@ async fn insert_card_into_db(pool: &SqlitePool, card: &Card) -> Result<(), Box<d
         set,
         card.id_in_set,
         card.image_url,
+        imported.cost,
+        imported.text,
+        card.name,
+        set,
+    ).execute(pool).await?;
+    sqlx::query!(
+        "UPDATE cards SET
+                cost = COALESCE(cost, ?),
+                text = COALESCE(text, ?)
+            WHERE name = ? AND set_id = (SELECT id FROM sets WHERE short_name = ?)",
+        imported.cost,
+        imported.text,
+        card.name,
+        set,
+    ).execute(pool).await?;
+    for faction in imported.factions.iter() {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO card_factions (card_id, faction)
+                SELECT id, ? FROM cards WHERE name = ? AND set_id = (SELECT id FROM sets WHERE short_name = ?)",
+            faction,
+            card.name,
+            set,
+        ).execute(pool).await?;
+    }
+    Ok(())
+}
+
+/// Скачивает изображение карты в локальное хранилище и записывает путь и хэш в БД.
+/// Уже скачанные изображения повторно не загружаются
+async fn mirror_card_image(pool: &SqlitePool, store: &ImageStore, card: &Card) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(image_url) = &card.image_url else {
+        return Ok(());
+    };
+    let set = &card.set.short_name;
+    let existing = sqlx::query!(
+        "SELECT image_path FROM cards WHERE name = ? AND set_id = (SELECT id FROM sets WHERE short_name = ?)",
+        card.name,
+        set,
+    ).fetch_one(pool).await?;
+    if existing.image_path.is_some_and(|path| store.contains(&path)) {
+        return Ok(());
+    }
+
+    let bytes = get_with_retry(image_url).await?.bytes().await?;
+    let stored = store.store(&bytes, images::extension_from_url(image_url))?;
+    sqlx::query!(
+        "UPDATE cards SET image_path = ?, image_hash = ?
+            WHERE name = ? AND set_id = (SELECT id FROM sets WHERE short_name = ?)",
+        stored.path,
+        stored.hash,
         card.name,
         set,
     ).execute(pool).await?;
@@ -0,0 +1,59 @@
+use response_sim::{CardBusterSlot, CardRarity, Locale, Localize, SetCode, cli, db, query::CardQuery};
+
+const USAGE: &str = "Usage: cards search [--name WORDS] [--text PHRASE] [--set SHORT_NAME] [--rarity bronze|silver|gold]
+                    [--slot hero|command|basic_card] [--faction NAME] [--cost-min N] [--cost-max N] [--limit N] [--json]";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.split_first() {
+        Some((command, options)) if command == "search" => search(options).await,
+        _ => Err(USAGE.into()),
+    }
+}
+
+async fn search(options: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db_pool = db::open_from_env().await?;
+    let mut query = CardQuery::default();
+    let mut json = false;
+    cli::parse_options(options.iter().cloned(), &["--json"], USAGE, |option, value| {
+        match (option, value) {
+            ("--json", _) => json = true,
+            ("--name", Some(value)) => query.name = Some(value),
+            ("--text", Some(value)) => query.text = Some(value),
+            ("--set", Some(value)) => query.set = Some(SetCode(value)),
+            ("--rarity", Some(value)) => query.rarity = Some(parse_key::<CardRarity>(&value)?),
+            ("--slot", Some(value)) => query.slot = Some(parse_key::<CardBusterSlot>(&value)?),
+            ("--faction", Some(value)) => query.faction = Some(value),
+            ("--cost-min", Some(value)) => query.cost_min = Some(value.parse()?),
+            ("--cost-max", Some(value)) => query.cost_max = Some(value.parse()?),
+            ("--limit", Some(value)) => query.limit = Some(value.parse()?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })?;
+
+    let cards = query.execute(&db_pool).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&cards)?);
+        return Ok(());
+    }
+    let locale = Locale::from_env();
+    for found in cards.iter() {
+        let cost = found.cost.map(|c| c.to_string()).unwrap_or_default();
+        println!(
+            "{:5} {} {:2} {:15}",
+            found.id,
+            found.card.localized(locale),
+            cost,
+            found.factions.join(", "),
+        );
+    }
+    println!("Found: {}", cards.len());
+    Ok(())
+}
+
+/// Значение перечисления по идентификатору из JSON, например `gold` или `basic_card`
+fn parse_key<T: serde::de::DeserializeOwned>(key: &str) -> Result<T, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_value(serde_json::Value::String(key.to_string()))?)
+}
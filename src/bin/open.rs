@@ -0,0 +1,165 @@
+use std::{io::{BufWriter, IsTerminal, Write}, sync::Arc};
+
+use serde::{Serialize, ser::{SerializeSeq, Serializer as _}};
+
+use response_sim::{Card, CardBase, CardPool, CardRarity, Locale, SetId, Localize, cli, db, locale::Translation, presets::PresetSpec};
+
+const USAGE: &str = "Usage: open [--set SHORT_NAME] [--preset NAME[:VERSION]] [--seed N] [--count N] [--json | --csv] [--no-color]";
+
+/// Формат вывода
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Карта открытого бустера. Номер слота и бустера начинаются с 1
+#[derive(Serialize)]
+struct OpenedCard<'a> {
+    pack: usize,
+    slot: usize,
+    hall_of_fame: bool,
+    #[serde(flatten)]
+    card: &'a Card,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut set, mut preset, mut seed, mut count) = ("КОВ".to_string(), PresetSpec::default(), None, 1);
+    let (mut output, mut color) = (Output::Table, std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none());
+    let mut help = false;
+    let flags = ["--json", "--csv", "--no-color", "--help", "-h"];
+    cli::parse_options(std::env::args().skip(1), &flags, USAGE, |option, value| {
+        match (option, value) {
+            ("--json", _) => output = Output::Json,
+            ("--csv", _) => output = Output::Csv,
+            ("--no-color", _) => color = false,
+            ("--help" | "-h", _) => help = true,
+            ("--set", Some(value)) => set = value,
+            ("--preset", Some(value)) => preset = value.parse()?,
+            ("--seed", Some(value)) => seed = Some(value.parse()?),
+            ("--count", Some(value)) => count = value.parse()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })?;
+    if help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let db_pool = db::open_from_env().await?;
+    let card_pool = Arc::new(CardPool::load(&db_pool).await?);
+    let set = card_pool.sets().by_short_name(&set).ok_or_else(|| format!("Error. Not found set: {}", set))?.clone();
+    let preset = preset.load(&db_pool, &set.code()).await?;
+    // Без `--seed` зерно случайное, но печатается, чтобы бустеры можно было повторить
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut card_base = CardBase::from_pool_with_seed(card_pool, seed);
+    // Бустеры генерируются и печатаются по одному: `--count` не ограничен и может быть очень большим
+    let mut next_pack = || card_base.generate_buster(&preset.rules)
+        .ok_or_else(|| format!("Error. Cannot generate booster: {}", set.short_name));
+    let set_id = set.id;
+
+    match output {
+        Output::Json => {
+            let mut serializer = serde_json::Serializer::pretty(BufWriter::new(std::io::stdout().lock()));
+            let mut seq = serializer.serialize_seq(None)?;
+            for pack in 1..=count {
+                for opened in opened_cards(pack, &next_pack()?, set_id) {
+                    seq.serialize_element(&opened)?;
+                }
+            }
+            seq.end()?;
+            let mut writer = serializer.into_inner();
+            writeln!(writer)?;
+            writer.flush()?;
+        }
+        Output::Csv => {
+            println!("pack,slot,set,id_in_set,name,rarity,buster_slot,hall_of_fame");
+            for pack in 1..=count {
+                print_csv(&opened_cards(pack, &next_pack()?, set_id))?;
+            }
+        }
+        Output::Table => {
+            let locale = Locale::from_env();
+            let text = |ru, en| Translation { ru, en }.get(locale);
+            println!("{} {} {}, {}: {}", text("Правила", "Rules"), set.localized(locale), preset.localized(locale), text("зерно", "seed"), seed);
+            let (mut gold, mut hall_of_fame) = (0, 0);
+            for pack in 1..=count {
+                let buster = next_pack()?;
+                let cards = opened_cards(pack, &buster, set_id);
+                print_table(&cards, locale, color);
+                gold += cards.iter().filter(|c| c.card.rarity == CardRarity::Gold).count();
+                hall_of_fame += cards.iter().filter(|c| c.hall_of_fame).count();
+            }
+            println!("\n{}: {}, {}: {}", text("Золото", "Gold"), gold, text("Зал Славы", "Hall of Fame"), hall_of_fame);
+        }
+    }
+    Ok(())
+}
+
+/// Карты бустера с номером `pack`, карты не из сета `set_id` — из Зала Славы
+fn opened_cards(pack: usize, cards: &[Card], set_id: SetId) -> Vec<OpenedCard<'_>> {
+    cards.iter().enumerate()
+        .map(|(slot, card)| OpenedCard { pack, slot: slot + 1, hall_of_fame: card.set.id != set_id, card })
+        .collect()
+}
+
+/// ANSI-цвет редкости
+fn rarity_color(rarity: CardRarity) -> &'static str {
+    match rarity {
+        CardRarity::Bronze => "\x1b[38;5;173m",
+        CardRarity::Silver => "\x1b[38;5;250m",
+        CardRarity::Gold => "\x1b[1;38;5;220m",
+    }
+}
+
+/// Строки карт бустера в формате `Display for Card`, карты Зала Славы отмечены звездой
+fn print_table(cards: &[OpenedCard], locale: Locale, color: bool) {
+    let text = |ru, en| Translation { ru, en }.get(locale);
+    for opened in cards.iter() {
+        if opened.slot == 1 {
+            println!("\n{} {}", text("Бустер", "Booster"), opened.pack);
+        }
+        let mark = if opened.hall_of_fame { "★" } else { " " };
+        let line = format!("{:2} {} {}", opened.slot, mark, opened.card.localized(locale));
+        if color {
+            println!("{}{}\x1b[0m", rarity_color(opened.card.rarity), line.trim_end());
+        } else {
+            println!("{}", line.trim_end());
+        }
+    }
+}
+
+/// Строки CSV карт бустера. Редкость и слот — идентификаторы из JSON, например `gold` и `basic_card`
+fn print_csv(cards: &[OpenedCard]) -> Result<(), Box<dyn std::error::Error>> {
+    for opened in cards.iter() {
+        println!(
+            "{},{},{},{},{},{},{},{}",
+            opened.pack,
+            opened.slot,
+            csv_field(&opened.card.set.short_name),
+            opened.card.id_in_set,
+            csv_field(&opened.card.name),
+            key(&opened.card.rarity)?,
+            key(&opened.card.buster_slot)?,
+            opened.hall_of_fame,
+        );
+    }
+    Ok(())
+}
+
+/// Поле CSV в кавычках, если в нем есть запятая, кавычка или перевод строки
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Идентификатор значения перечисления в JSON
+fn key<T: Serialize>(value: &T) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_json::to_value(value)?.as_str().ok_or("Error. Not a string key")?.to_string())
+}
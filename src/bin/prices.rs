@@ -0,0 +1,68 @@
+use response_sim::prices::{self, DEFAULT_VARIANT, PriceTable};
+use response_sim::{CardPool, Locale, Localize, cli, db, locale::Translation, presets::PresetSpec, simulation};
+
+const TRIALS: usize = 1_000_000;
+const SEED: u64 = 20260116;
+
+const USAGE: &str = "Usage: prices import FILE.csv
+       prices value RETAIL_PRICE [--set SHORT_NAME] [--preset NAME[:VERSION]] [--variant VARIANT] [--json]";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.split_first() {
+        Some((command, options)) if command == "import" && options.len() == 1 => import(&options[0]).await,
+        Some((command, options)) if command == "value" && !options.is_empty() => value(options).await,
+        _ => Err(USAGE.into()),
+    }
+}
+
+async fn import(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let db_pool = db::open_from_env().await?;
+    let records = prices::parse_price_csv(&std::fs::read_to_string(path)?)?;
+    let imported = prices::import_prices(&db_pool, &records).await?;
+    println!("Imported prices: {}", imported);
+    Ok(())
+}
+
+async fn value(options: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let retail_price: f64 = options[0].parse()?;
+    let (mut set, mut preset, mut variant, mut json) = ("КОВ".to_string(), PresetSpec::default(), DEFAULT_VARIANT.to_string(), false);
+    cli::parse_options(options[1..].iter().cloned(), &["--json"], USAGE, |option, value| {
+        match (option, value) {
+            ("--json", _) => json = true,
+            ("--set", Some(value)) => set = value,
+            ("--preset", Some(value)) => preset = value.parse()?,
+            ("--variant", Some(value)) => variant = value,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })?;
+
+    let db_pool = db::open_from_env().await?;
+    let card_pool = CardPool::load(&db_pool).await?;
+    let set = card_pool.sets().by_short_name(&set).ok_or_else(|| format!("Error. Not found set: {}", set))?.clone();
+    let preset = preset.load(&db_pool, &set.code()).await?;
+    let prices = PriceTable::load(&db_pool, &variant).await?;
+    let report = prices::pack_value(&card_pool, &preset.rules, &prices, retail_price, TRIALS, SEED, simulation::default_threads())?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let locale = Locale::from_env();
+    let text = |ru, en| Translation { ru, en }.get(locale);
+    println!("{} {} {}, {}: {}", text("Правила", "Rules"), set.localized(locale), preset.localized(locale), text("вариант", "variant"), variant);
+    println!("{}: {:.2}", text("Ожидаемая стоимость", "Expected value"), report.expected_value);
+    println!(
+        "{}: {:.2} / {:.2} / {:.2} / {:.2} / {:.2} (min / p10 / {} / p90 / max)",
+        text("Стоимость бустера", "Booster value"),
+        report.values.min, report.values.p10, report.values.median, report.values.p90, report.values.max,
+        text("медиана", "median"),
+    );
+    println!("{} {:.2}: {:.4}", text("Дороже цены", "Above retail price"), report.retail_price, report.above_retail);
+    if !report.unpriced_cards.is_empty() {
+        println!("{}: {}", text("Карты без цены", "Cards without price"), report.unpriced_cards.len());
+    }
+    Ok(())
+}
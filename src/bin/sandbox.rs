@@ -1,41 +1,114 @@
+use std::time::Instant;
+
 use response_sim::*;
-use sqlx::SqlitePool;
+use response_sim::locale::Translation;
+use response_sim::compare::{self, ComparisonSettings, Estimate};
+use response_sim::economy::EconomyRules;
+use response_sim::simulation::{self, CardCounts};
+
+const TRIALS: usize = 1_000_000;
+const SEED: u64 = 20260116;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let db_pool = SqlitePool::connect(&std::env::var("DATABASE_URL")?).await?;
-    let mut card_base = CardBase::new(db_pool).await;
-    let hall_of_fame_chanse = 0.02;
-    let hero_chanse = Distribution::new([0.8, 0.2]).unwrap();
-    let command_chanse_bronze_silver = Distribution::new([0.75, 0.25]).unwrap();
-    let command_chanse_random = Distribution::new([0.7, 0.2, 0.1]).unwrap();
-    let basic_card_chanse = Distribution::new([0.7, 0.3]).unwrap();
-    let buster_rules = BusterRules::new(
-        CardSet::KOV,
-        hall_of_fame_chanse,
-        hero_chanse,
-        command_chanse_bronze_silver,
-        command_chanse_random,
-        basic_card_chanse,
-    ).unwrap();
-    let mut nums = Vec::new();
-    for _ in 0..1000 {
-        let mut again = true;
-        let mut i = 0;
-        while again {
-            i += 1;
-            // println!("BUSTER #{i}");
-            // println!("---------------------------------------");
-            card_base.generate_buster(&buster_rules).await.unwrap().iter().for_each(|c| {
-                // println!("{}", c);
-                if c.set == CardSet::HallOfFame {
-                    again = false;
-                }
-            });
+    let locale = Locale::from_env();
+    let text = |ru, en| Translation { ru, en }.get(locale);
+    let db_pool = db::open_from_env().await?;
+    let card_pool = CardPool::load(&db_pool).await?;
+    let set = card_pool.sets().by_short_name("КОВ").ok_or("Error. Not found set: КОВ")?.clone();
+    // `sandbox compare official:1 official:2` сравнивает версии наборов правил
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((command, presets)) = args.split_first() && command == "compare" {
+        let mut rules = Vec::new();
+        for spec in presets {
+            let preset = spec.parse::<presets::PresetSpec>()?.load(&db_pool, &set.code()).await?;
+            println!("{}. {}", rules.len() + 1, preset.localized(locale));
+            rules.push(preset.rules);
         }
-        nums.push(i);
+        let settings = ComparisonSettings { trials: TRIALS, seed: SEED, economy: Some(EconomyRules::default()), ..Default::default() };
+        let comparison = compare::compare(&card_pool, &rules, &settings)?;
+        print_comparison(&comparison, locale);
+        return Ok(());
+    }
+    let preset = presets::load_preset(&db_pool, &set.code(), "official", None).await?;
+    println!("{} {} {}", text("Правила", "Rules"), set.localized(locale), preset.localized(locale));
+    let buster_rules = preset.rules;
+
+    let pool = card_pool.snapshot(&buster_rules)?;
+    let threads = simulation::default_threads();
+    let start = Instant::now();
+    let counts: CardCounts = simulation::simulate(&pool, TRIALS, SEED, threads);
+    println!(
+        "{} {} {:.2?}, {}: {}",
+        counts.busters,
+        text("бустеров за", "boosters in"),
+        start.elapsed(),
+        text("потоков", "threads"),
+        threads,
+    );
+
+    // Фактический шанс Зала Славы по слотам и замены основным сетом
+    println!("{:3} {:15} {:>12} {:>12}", "№", text("Слот", "Slot"), text("Зал Славы", "Hall of Fame"), text("Замена", "Fallback"));
+    for (i, rate) in card_pool.hall_of_fame_rates(&buster_rules).iter().enumerate() {
+        println!("{:3} {:15} {:12.5} {:12.5}", i + 1, rate.slot.localized(locale), rate.chanse, rate.fallback);
+    }
+    println!(
+        "{}: {} ({:.5} {}, {})",
+        text("Замен Зала Славы", "Hall of Fame fallbacks"),
+        counts.hall_of_fame_fallbacks,
+        counts.hall_of_fame_fallbacks as f64 / counts.busters as f64,
+        text("на бустер", "per booster"),
+        buster_rules.hall_of_fame_policy().localized(locale),
+    );
+
+    // Ожидаемые и наблюдаемые частоты карт с учетом весов
+    let mut pull_rates = card_pool.pull_rates(&buster_rules)?;
+    pull_rates.sort_by(|a, b| a.per_buster.total_cmp(&b.per_buster));
+    println!(
+        "{:30} {:3} {:12} {:>10} {:>10} {:>10}",
+        text("Карта", "Card"), "№", text("Сет", "Set"), text("Ожидание", "Expected"), text("Факт", "Observed"), text("Хоть одна", "Any"),
+    );
+    for rate in pull_rates.iter() {
+        let observed = pool.cards().iter()
+            .position(|c| c.set.id == rate.card.set.id && c.id_in_set == rate.card.id_in_set)
+            .map_or(0.0, |i| counts.per_buster(i));
+        println!(
+            "{:30} {:3} {:12} {:10.5} {:10.5} {:10.5}",
+            rate.card.name, rate.card.id_in_set, rate.card.set.localized(locale), rate.per_buster, observed, rate.at_least_one,
+        );
     }
-    println!("{:#?}", nums);
-    println!("{}", nums.iter().sum::<usize>() / nums.len());
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_comparison(comparison: &compare::Comparison, locale: Locale) {
+    let text = |ru, en| Translation { ru, en }.get(locale);
+    let estimate = |e: &Estimate| format!("{:.5} ± {:.5}", e.mean, e.half_width);
+    let cost = |e: Option<&Estimate>| e.map_or_else(String::new, |e| format!("{:.0} ± {:.0}", e.mean, e.half_width));
+    println!("{} {}", comparison.trials, text("бустеров на набор правил, разница с первым набором", "boosters per rule set, difference from the first rule set"));
+    println!(
+        "{:3} {:>20} {:>20} {:>20} {:>20} {:>16} {:>16}",
+        "№", text("Золото", "Gold"), text("Δ золото", "Δ gold"), text("Зал Славы", "Hall of Fame"), text("Δ Зал Славы", "Δ Hall of Fame"),
+        text("Сбор сета", "Set cost"), text("Δ сбор сета", "Δ set cost"),
+    );
+    for (i, variant) in comparison.variants.iter().enumerate() {
+        println!(
+            "{:3} {:>20} {:>20} {:>20} {:>20} {:>16} {:>16}",
+            i + 1,
+            estimate(&variant.gold_rate),
+            estimate(&variant.gold_rate_diff),
+            estimate(&variant.hall_of_fame_rate),
+            estimate(&variant.hall_of_fame_rate_diff),
+            cost(variant.completion_cost.as_ref()),
+            cost(variant.completion_cost_diff.as_ref()),
+        );
+    }
+
+    // Только карты, частота которых значимо изменилась хотя бы в одном наборе
+    println!("{:30} {:3} {:12} {}", text("Карта", "Card"), "№", text("Сет", "Set"), text("Частота, Δ", "Rate, Δ"));
+    for card in comparison.cards.iter().filter(|c| c.diffs.iter().any(Estimate::is_significant)) {
+        let rates: Vec<String> = card.rates.iter().zip(card.diffs.iter())
+            .map(|(rate, diff)| format!("{:.5} ({:+.5} ± {:.5})", rate.mean, diff.mean, diff.half_width))
+            .collect();
+        println!("{:30} {:3} {:12} {}", card.card.name, card.card.id_in_set, card.card.set.localized(locale), rates.join("  "));
+    }
+}
@@ -1,13 +1,44 @@
-use response_sim::*;
+use response_sim::{Locale, dashboard, db};
+use response_sim::images::{self, ImageStore};
+use response_sim::jobs::{SimulationJobs, SimulationRequest};
+use response_sim::query::CardQuery;
 use sqlx::SqlitePool;
-use axum::{response::Html, routing::get, Router};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Json, Redirect, Response},
+    routing::{get, post},
+    Router,
+};
+
+#[derive(Clone)]
+struct AppState {
+    db_pool: SqlitePool,
+    image_store: ImageStore,
+    jobs: SimulationJobs,
+    locale: Locale,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let db_pool = SqlitePool::connect(&std::env::var("DATABASE_URL")?).await?;
-    let mut card_base = CardBase::new(db_pool).await;
+    let db_pool = db::open_from_env().await?;
+    let state = AppState {
+        db_pool,
+        image_store: ImageStore::new(images::IMAGE_STORE_DIR),
+        jobs: SimulationJobs::new(),
+        locale: Locale::from_env(),
+    };
 
-    let app = Router::new().route("/", get(handler)).route("/bye", get(handler2));
+    let app = Router::new()
+        .route("/", get(handler))
+        .route("/bye", get(handler2))
+        .route("/images/cards/{id}", get(card_image))
+        .route("/images/{*path}", get(stored_image))
+        .route("/api/cards", get(search_cards))
+        .route("/api/simulations", post(start_simulation))
+        .route("/api/simulations/{id}", get(simulation_status))
+        .route("/simulations/{id}", get(simulation_page))
+        .with_state(state);
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
         .await
         .unwrap();
@@ -24,4 +55,89 @@ async fn handler() -> Html<&'static str> {
 
 async fn handler2() -> Html<&'static str> {
     Html("<h1>Bye, World!</h1>")
-}
\ No newline at end of file
+}
+
+/// Поиск карт, параметры запроса как поля `CardQuery`:
+/// `/api/cards?name=лорд&set=КОВ&rarity=gold&slot=hero&cost_max=3`
+async fn search_cards(State(state): State<AppState>, Query(query): Query<CardQuery>) -> Response {
+    match query.execute(&state.db_pool).await {
+        Ok(cards) => Json(cards).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+/// Запускает симуляцию в фоне, тело запроса — `SimulationRequest` в JSON:
+/// `{"set": "КОВ", "preset": "official", "trials": 1000000, "seed": 1}`.
+/// Отвечает номером задачи, ход и результат доступны по `/api/simulations/{id}`
+async fn start_simulation(State(state): State<AppState>, Json(request): Json<SimulationRequest>) -> Response {
+    match state.jobs.start(&state.db_pool, request).await {
+        Ok(id) => (
+            StatusCode::ACCEPTED,
+            [(header::LOCATION, format!("/api/simulations/{}", id))],
+            Json(serde_json::json!({ "id": id })),
+        ).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+async fn simulation_status(State(state): State<AppState>, Path(id): Path<u64>) -> Response {
+    match state.jobs.status(id) {
+        Some(status) => Json(status).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Страница задачи с графиками
+async fn simulation_page(State(state): State<AppState>, Path(id): Path<u64>) -> Response {
+    match state.jobs.status(id) {
+        Some(status) => Html(dashboard::job_page(&status, state.locale)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Перенаправляет на изображение карты в локальном хранилище, а если оно еще не скачано,
+/// то на оригинальный URL. Адрес зависит от номера карты, а не от содержимого, и изображение
+/// может быть скачано заново, поэтому сам ответ не кэшируется
+async fn card_image(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    let record = match sqlx::query!("SELECT image_url, image_path FROM cards WHERE id = ?", id)
+        .fetch_optional(&state.db_pool)
+        .await
+    {
+        Ok(Some(record)) => record,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let location = match (record.image_path, record.image_url) {
+        (Some(path), _) if state.image_store.contains(&path) => format!("/images/{}", path),
+        (_, Some(url)) => url,
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    ([(header::CACHE_CONTROL, "no-cache")], Redirect::temporary(&location)).into_response()
+}
+
+/// Отдает изображение из хранилища. Путь содержит хэш содержимого,
+/// поэтому ответ можно кэшировать навсегда
+async fn stored_image(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap) -> Response {
+    let Some(hash) = images::hash_from_path(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let etag = format!("\"{}\"", hash);
+    if headers.get(header::IF_NONE_MATCH).is_some_and(|v| v.as_bytes() == etag.as_bytes()) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    let Some(full_path) = state.image_store.resolve(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match tokio::fs::read(&full_path).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, images::content_type(&path).to_string()),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable".to_string()),
+                (header::ETAG, etag),
+            ],
+            bytes,
+        ).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
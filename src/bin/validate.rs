@@ -0,0 +1,18 @@
+use response_sim::{Locale, Localize, db, validate::validate_sets};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let db_pool = db::open_from_env().await?;
+    let reports = validate_sets(&db_pool).await?;
+    let locale = Locale::from_env();
+    for report in reports.iter() {
+        println!("{}", report.localized(locale));
+    }
+
+    if reports.iter().all(|r| r.is_ok()) {
+        println!("All sets are consistent.");
+        Ok(())
+    } else {
+        Err("Some sets have problems.".into())
+    }
+}
@@ -0,0 +1,305 @@
+//! Коробки бустеров и кейсы из нескольких коробок.
+//! Гарантии коробки (минимум карт Зала Славы, максимум золотых героев) выполняются
+//! выборкой с отклонением: коробка генерируется целиком заново, пока не выполнит все условия.
+//! Поэтому бустеры коробки распределены как обычные бустеры при условии выполнения гарантий
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BusterRules, CardBusterSlot, CardPool, CardRarity,
+    economy::CostDistribution,
+    simulation::{Accumulator, CardCounts, OpenedBuster, PoolSnapshot, block_seed, run_blocks},
+};
+
+fn default_max_attempts() -> u32 {
+    10_000
+}
+
+/// Правила коробки поверх правил бустера
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoxRules {
+    pub buster_rules: BusterRules,
+    pub busters_per_box: usize,
+    /// Минимум карт Зала Славы в коробке
+    #[serde(default)]
+    pub min_hall_of_fame: u32,
+    /// Максимум золотых героев в коробке, `None` — без ограничения
+    #[serde(default)]
+    pub max_gold_heroes: Option<u32>,
+    /// Сколько раз можно перегенерировать коробку, прежде чем считать гарантии невыполнимыми
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+impl BoxRules {
+    pub fn new(buster_rules: BusterRules, busters_per_box: usize) -> Self {
+        BoxRules { buster_rules, busters_per_box, min_hall_of_fame: 0, max_gold_heroes: None, max_attempts: default_max_attempts() }
+    }
+
+    pub fn with_min_hall_of_fame(mut self, min_hall_of_fame: u32) -> Self {
+        self.min_hall_of_fame = min_hall_of_fame;
+        self
+    }
+
+    pub fn with_max_gold_heroes(mut self, max_gold_heroes: u32) -> Self {
+        self.max_gold_heroes = Some(max_gold_heroes);
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Снимок пулов для правил бустера и проверка правил коробки
+    pub fn snapshot(&self, pool: &CardPool) -> Result<BoxSnapshot, Box<dyn std::error::Error>> {
+        if self.busters_per_box == 0 {
+            return Err("Error. Box must contain at least one booster".into());
+        }
+        if self.max_attempts == 0 {
+            return Err("Error. Box max attempts must be positive".into());
+        }
+        Ok(BoxSnapshot { pool: pool.snapshot(&self.buster_rules)?, rules: self.clone() })
+    }
+}
+
+/// Открытая коробка
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpenedBox {
+    pub busters: Vec<OpenedBuster>,
+    pub hall_of_fame: u32,
+    pub gold_heroes: u32,
+    /// Сколько раз коробка генерировалась, включая принятую
+    pub attempts: u32,
+}
+
+/// Снимок пулов и правила коробки
+#[derive(Debug, Clone)]
+pub struct BoxSnapshot {
+    pool: PoolSnapshot,
+    rules: BoxRules,
+}
+
+impl BoxSnapshot {
+    pub fn pool(&self) -> &PoolSnapshot {
+        &self.pool
+    }
+
+    pub fn rules(&self) -> &BoxRules {
+        &self.rules
+    }
+
+    /// Генерирует коробку, выполняющую гарантии.
+    /// Ошибка, если за `max_attempts` попыток гарантии не выполнились
+    pub fn generate_into<R: Rng + ?Sized>(&self, rng: &mut R, opened: &mut OpenedBox) -> Result<(), Box<dyn std::error::Error>> {
+        opened.busters.resize_with(self.rules.busters_per_box, OpenedBuster::default);
+        for attempt in 1..=self.rules.max_attempts {
+            let (mut hall_of_fame, mut gold_heroes) = (0, 0);
+            for buster in opened.busters.iter_mut() {
+                self.pool.generate_into(rng, buster);
+                for &index in buster.cards.iter() {
+                    let card = self.pool.card(index);
+                    hall_of_fame += u32::from(card.set.id != self.pool.set());
+                    gold_heroes += u32::from(card.buster_slot == CardBusterSlot::Hero && card.rarity == CardRarity::Gold);
+                }
+            }
+            if hall_of_fame >= self.rules.min_hall_of_fame && self.rules.max_gold_heroes.is_none_or(|max| gold_heroes <= max) {
+                opened.hall_of_fame = hall_of_fame;
+                opened.gold_heroes = gold_heroes;
+                opened.attempts = attempt;
+                return Ok(());
+            }
+        }
+        Err(format!("Error. Box guarantees are not met after {} attempts", self.rules.max_attempts).into())
+    }
+
+    pub fn generate<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<OpenedBox, Box<dyn std::error::Error>> {
+        let mut opened = OpenedBox::default();
+        self.generate_into(rng, &mut opened)?;
+        Ok(opened)
+    }
+}
+
+/// Итоги открытия кейсов
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseReport {
+    pub cases: usize,
+    pub boxes_per_case: usize,
+    /// Копии карт снимка по всем бустерам всех кейсов
+    pub counts: CardCounts,
+    pub hall_of_fame_per_box: f64,
+    pub gold_heroes_per_box: f64,
+    /// Среднее число генераций коробки. Чем больше, тем сильнее гарантии меняют распределение
+    pub attempts_per_box: f64,
+    /// Число разных карт основного сета в кейсе
+    pub distinct_per_case: CostDistribution,
+    /// Доля кейсов, в которых есть все карты основного сета снимка
+    pub complete_set_rate: f64,
+}
+
+/// Итоги одного кейса
+struct CaseStats {
+    counts: CardCounts,
+    hall_of_fame: u64,
+    gold_heroes: u64,
+    attempts: u64,
+    distinct: usize,
+}
+
+/// Открывает `cases` кейсов по `boxes_per_case` коробок.
+/// У каждого кейса свое зерно, поэтому результат не зависит от числа потоков
+pub fn simulate_cases(snapshot: &BoxSnapshot, cases: usize, boxes_per_case: usize, seed: u64, threads: usize) -> Result<CaseReport, Box<dyn std::error::Error>> {
+    if cases == 0 || boxes_per_case == 0 {
+        return Err("Error. Nothing to open: no cases or boxes".into());
+    }
+    let pool = snapshot.pool();
+    let set_cards = pool.cards().iter().filter(|card| card.set.id == pool.set()).count();
+    let results = run_blocks(cases, threads, |case| -> Result<CaseStats, String> {
+        let mut rng = StdRng::seed_from_u64(block_seed(seed, case));
+        let mut opened = OpenedBox::default();
+        let mut stats = CaseStats { counts: CardCounts::new(pool), hall_of_fame: 0, gold_heroes: 0, attempts: 0, distinct: 0 };
+        for _ in 0..boxes_per_case {
+            snapshot.generate_into(&mut rng, &mut opened).map_err(|e| e.to_string())?;
+            opened.busters.iter().for_each(|buster| stats.counts.record(pool, buster));
+            stats.hall_of_fame += u64::from(opened.hall_of_fame);
+            stats.gold_heroes += u64::from(opened.gold_heroes);
+            stats.attempts += u64::from(opened.attempts);
+        }
+        stats.distinct = pool.cards().iter().zip(stats.counts.counts.iter())
+            .filter(|(card, count)| card.set.id == pool.set() && **count > 0)
+            .count();
+        Ok(stats)
+    }).into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let boxes = (cases * boxes_per_case) as f64;
+    let mut counts = CardCounts::new(pool);
+    let (mut hall_of_fame, mut gold_heroes, mut attempts) = (0, 0, 0);
+    let mut distinct = Vec::with_capacity(cases);
+    for stats in results {
+        counts.merge(stats.counts);
+        hall_of_fame += stats.hall_of_fame;
+        gold_heroes += stats.gold_heroes;
+        attempts += stats.attempts;
+        distinct.push(stats.distinct as f64);
+    }
+    let complete_set_rate = distinct.iter().filter(|&&d| d as usize == set_cards).count() as f64 / cases as f64;
+    Ok(CaseReport {
+        cases,
+        boxes_per_case,
+        counts,
+        hall_of_fame_per_box: hall_of_fame as f64 / boxes,
+        gold_heroes_per_box: gold_heroes as f64 / boxes,
+        attempts_per_box: attempts as f64 / boxes,
+        distinct_per_case: CostDistribution::new(distinct).ok_or("Error. No cases opened")?,
+        complete_set_rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::{BUSTER_SIZE, fixture};
+
+    async fn pool() -> CardPool {
+        CardPool::load(&fixture::standard().await).await.unwrap()
+    }
+
+    #[test]
+    async fn generate_box_meets_guarantees() {
+        // GIVEN
+        let rules = BoxRules::new(fixture::rules(0.01), 12).with_min_hall_of_fame(1).with_max_gold_heroes(2);
+        let snapshot = rules.snapshot(&pool().await).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        // WHEN
+        let boxes: Vec<OpenedBox> = (0..100).map(|_| snapshot.generate(&mut rng).unwrap()).collect();
+        // THEN
+        for opened in boxes.iter() {
+            assert_eq!(opened.busters.len(), 12);
+            assert!(opened.hall_of_fame >= 1);
+            assert!(opened.gold_heroes <= 2);
+            let gold_heroes = opened.busters.iter()
+                .filter(|b| snapshot.pool().card(b.cards[0]).rarity == CardRarity::Gold)
+                .count();
+            assert_eq!(gold_heroes as u32, opened.gold_heroes);
+        }
+        // Без гарантии Зал Славы в коробке выпадает редко, поэтому нужны повторные генерации
+        assert!(boxes.iter().map(|b| b.attempts as f64).sum::<f64>() / 100.0 > 1.5);
+    }
+
+    #[test]
+    async fn generate_box_with_impossible_guarantee_then_error() {
+        // GIVEN
+        let rules = BoxRules::new(fixture::rules(0.0), 4).with_min_hall_of_fame(1).with_max_attempts(50);
+        let snapshot = rules.snapshot(&pool().await).unwrap();
+        // WHEN
+        let result = snapshot.generate(&mut StdRng::seed_from_u64(1));
+        // THEN
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn box_rules_with_empty_box_then_error() {
+        assert!(BoxRules::new(fixture::rules(0.0), 0).snapshot(&pool().await).is_err());
+    }
+
+    #[test]
+    async fn simulate_cases_with_same_seed_then_same_result_for_any_threads() {
+        // GIVEN
+        let rules = BoxRules::new(fixture::rules(0.02), 24).with_min_hall_of_fame(1);
+        let snapshot = rules.snapshot(&pool().await).unwrap();
+        // WHEN
+        let single = simulate_cases(&snapshot, 20, 6, 5, 1).unwrap();
+        let parallel = simulate_cases(&snapshot, 20, 6, 5, 4).unwrap();
+        // THEN
+        assert_eq!(single.counts, parallel.counts);
+        assert_eq!(single.counts.busters, 20 * 6 * 24);
+        assert_eq!(single.counts.counts.iter().sum::<u64>(), single.counts.busters * BUSTER_SIZE as u64);
+        assert!(single.hall_of_fame_per_box >= 1.0);
+        // 16 карт КОВ в стандартной фикстуре, за 144 коробки-бустера выпадут все
+        assert_eq!(single.distinct_per_case.min, 16.0);
+        assert_eq!(single.complete_set_rate, 1.0);
+    }
+
+    #[test]
+    async fn max_gold_heroes_lowers_gold_hero_rate() {
+        // GIVEN
+        let pool = pool().await;
+        let free = BoxRules::new(fixture::rules(0.0), 10).snapshot(&pool).unwrap();
+        let limited = BoxRules::new(fixture::rules(0.0), 10).with_max_gold_heroes(1).snapshot(&pool).unwrap();
+        // WHEN
+        let free = simulate_cases(&free, 50, 10, 1, 4).unwrap();
+        let limited = simulate_cases(&limited, 50, 10, 1, 4).unwrap();
+        // THEN
+        assert!((free.gold_heroes_per_box - 2.0).abs() < 0.2);
+        assert!(limited.gold_heroes_per_box <= 1.0);
+        assert_eq!(free.attempts_per_box, 1.0);
+    }
+
+    #[test]
+    async fn box_rules_from_toml() {
+        // GIVEN
+        let toml = r#"
+            busters_per_box = 24
+            min_hall_of_fame = 1
+
+            [buster_rules]
+            set = "КОВ"
+            hall_of_fame_chanse = 0.02
+            hero_chanse = [0.8, 0.2]
+            command_chanse_bronze_silver = [0.75, 0.25]
+            command_chanse_random = [0.7, 0.2, 0.1]
+            basic_card_chanse = [0.7, 0.3]
+        "#;
+        // WHEN
+        let rules: BoxRules = toml::from_str(toml).unwrap();
+        // THEN
+        assert_eq!(rules.busters_per_box, 24);
+        assert_eq!(rules.min_hall_of_fame, 1);
+        assert_eq!(rules.max_gold_heroes, None);
+        assert_eq!(rules.max_attempts, 10_000);
+    }
+}
@@ -0,0 +1,65 @@
+//! Разбор аргументов командной строки утилит из `src/bin`
+
+/// Разбирает аргументы вида `--flag` и `--option VALUE`.
+/// Опции из `flags` передаются в `apply` без значения, у остальных значение — следующий аргумент.
+/// `apply` возвращает `false` для неизвестной опции, тогда разбор завершается ошибкой с `usage`
+pub fn parse_options<F>(args: impl IntoIterator<Item = String>, flags: &[&str], usage: &str, mut apply: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, Option<String>) -> Result<bool, Box<dyn std::error::Error>>,
+{
+    let mut args = args.into_iter();
+    while let Some(option) = args.next() {
+        let value = if flags.contains(&option.as_str()) {
+            None
+        } else {
+            Some(args.next().ok_or_else(|| format!("Error. Missing value for {}\n{}", option, usage))?)
+        };
+        if !apply(&option, value)? {
+            return Err(format!("Error. Unknown option: {}\n{}", option, usage).into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    /// Опции со значениями в порядке разбора
+    type Parsed = Vec<(String, Option<String>)>;
+
+    fn parse(arguments: &[&str]) -> Result<Parsed, Box<dyn std::error::Error>> {
+        let mut options = Vec::new();
+        parse_options(args(arguments), &["--json"], "USAGE", |option, value| {
+            options.push((option.to_string(), value));
+            Ok(option != "--unknown")
+        })?;
+        Ok(options)
+    }
+
+    #[test]
+    async fn parse_options_with_flags_and_values() {
+        // WHEN
+        let options = parse(&["--set", "КОВ", "--json", "--count", "--json"]).unwrap();
+        // THEN
+        assert_eq!(options, vec![
+            ("--set".to_string(), Some("КОВ".to_string())),
+            ("--json".to_string(), None),
+            ("--count".to_string(), Some("--json".to_string())),
+        ]);
+    }
+
+    #[test]
+    async fn parse_options_with_missing_value_or_unknown_option_then_error() {
+        let missing = parse(&["--json", "--set"]).unwrap_err().to_string();
+        let unknown = parse(&["--unknown", "1"]).unwrap_err().to_string();
+        assert!(missing.starts_with("Error. Missing value for --set"));
+        assert!(unknown.starts_with("Error. Unknown option: --unknown"));
+        assert!(unknown.ends_with("USAGE"));
+    }
+}
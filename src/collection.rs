@@ -0,0 +1,71 @@
+//! Коллекция игрока: сколько копий каждой карты снимка пулов у него есть
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{OpenedBuster, PoolSnapshot};
+
+/// Число копий каждой карты, номера как в `PoolSnapshot::cards`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Collection {
+    counts: Vec<u32>,
+}
+
+impl Collection {
+    /// Пустая коллекция для карт снимка
+    pub fn new(pool: &PoolSnapshot) -> Self {
+        Collection { counts: vec![0; pool.cards().len()] }
+    }
+
+    pub fn count(&self, index: usize) -> u32 {
+        self.counts[index]
+    }
+
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    pub fn add(&mut self, index: usize, copies: u32) {
+        self.counts[index] += copies;
+    }
+
+    /// Убирает до `copies` копий и возвращает, сколько убрано
+    pub fn remove(&mut self, index: usize, copies: u32) -> u32 {
+        let removed = copies.min(self.counts[index]);
+        self.counts[index] -= removed;
+        removed
+    }
+
+    pub fn add_buster(&mut self, buster: &OpenedBuster) {
+        buster.cards.iter().for_each(|&index| self.add(index, 1));
+    }
+
+    /// Число разных карт, которых есть хотя бы `copies` копий
+    pub fn complete(&self, copies: u32) -> usize {
+        self.counts.iter().filter(|&&count| count >= copies).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture;
+
+    #[test]
+    async fn add_buster_and_remove_copies() {
+        // GIVEN
+        let pool = fixture::snapshot(0.0).await;
+        let mut collection = Collection::new(&pool);
+        let buster = OpenedBuster { cards: [0; crate::BUSTER_SIZE], hall_of_fame_fallbacks: 0 };
+        // WHEN
+        collection.add_buster(&buster);
+        let removed = collection.remove(0, 20);
+        collection.add(1, 2);
+        // THEN
+        assert_eq!(removed, 18);
+        assert_eq!(collection.count(0), 0);
+        assert_eq!(collection.complete(2), 1);
+        assert_eq!(collection.counts().len(), pool.cards().len());
+    }
+}
@@ -0,0 +1,319 @@
+//! Сравнение нескольких наборов правил бустера на общих случайных числах.
+//! Каждый набор открывает бустеры из тех же пакетов с теми же зернами, поэтому
+//! разности между наборами оцениваются по парам пакетов и имеют узкие доверительные интервалы
+
+use std::collections::HashMap;
+
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BusterRules, Card, CardPool, CardRarity, SetId,
+    economy::{EconomyRules, simulate_player},
+    simulation::{OpenedBuster, PoolSnapshot, block_seed, default_threads, run_blocks},
+};
+
+/// Число бустеров в пакете. Интервалы считаются по средним пакетов
+const BATCH_SIZE: usize = 1024;
+
+/// Квантиль нормального распределения для 95% интервала
+const Z_95: f64 = 1.959964;
+
+/// Оценка среднего с 95% доверительным интервалом `mean ± half_width`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    pub mean: f64,
+    pub half_width: f64,
+}
+
+impl Estimate {
+    /// Оценка по независимым одинаково распределенным наблюдениям.
+    /// Для одного наблюдения интервал бесконечен
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let half_width = if samples.len() > 1 { Z_95 * (variance / n).sqrt() } else { f64::INFINITY };
+        Estimate { mean, half_width }
+    }
+
+    pub fn low(&self) -> f64 {
+        self.mean - self.half_width
+    }
+
+    pub fn high(&self) -> f64 {
+        self.mean + self.half_width
+    }
+
+    /// Для разности: интервал не содержит нуля
+    pub fn is_significant(&self) -> bool {
+        self.mean.abs() > self.half_width
+    }
+}
+
+/// Параметры сравнения
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComparisonSettings {
+    /// Число бустеров для каждого набора, округляется вверх до целого числа пакетов
+    pub trials: usize,
+    pub seed: u64,
+    pub threads: usize,
+    /// Если задано, сравнивается и стоимость сбора сета в этой экономике
+    pub economy: Option<EconomyRules>,
+    /// Число игроков для стоимости сбора сета
+    pub players: usize,
+}
+
+impl Default for ComparisonSettings {
+    fn default() -> Self {
+        ComparisonSettings { trials: 100_000, seed: 0, threads: default_threads(), economy: None, players: 200 }
+    }
+}
+
+/// Показатели одного набора правил. Разности считаются относительно первого набора
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantComparison {
+    pub rules: BusterRules,
+    /// Золотых карт в бустере
+    pub gold_rate: Estimate,
+    /// Карт Зала Славы в бустере
+    pub hall_of_fame_rate: Estimate,
+    /// Валюта на сбор сета
+    pub completion_cost: Option<Estimate>,
+    pub gold_rate_diff: Estimate,
+    pub hall_of_fame_rate_diff: Estimate,
+    pub completion_cost_diff: Option<Estimate>,
+}
+
+/// Частота карты в бустере для каждого набора правил и разности с первым набором
+#[derive(Debug, Clone, Serialize)]
+pub struct CardRateComparison {
+    pub card: Card,
+    pub rates: Vec<Estimate>,
+    pub diffs: Vec<Estimate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    /// Бустеров на каждый набор правил
+    pub trials: usize,
+    pub variants: Vec<VariantComparison>,
+    /// Карты всех наборов по сетам, номерам в сете и названиям
+    pub cards: Vec<CardRateComparison>,
+}
+
+/// Средние одного пакета для одного набора правил
+struct BatchStats {
+    gold: f64,
+    hall_of_fame: f64,
+    /// По общему списку карт сравнения
+    cards: Vec<f64>,
+}
+
+/// Сравнивает наборы правил. Первый набор считается базовым
+pub fn compare(pool: &CardPool, rules: &[BusterRules], settings: &ComparisonSettings) -> Result<Comparison, Box<dyn std::error::Error>> {
+    if rules.is_empty() {
+        return Err("Error. No booster rules to compare".into());
+    }
+    let snapshots = rules.iter().map(|r| pool.snapshot(r)).collect::<Result<Vec<_>, _>>()?;
+
+    // Общий список карт и номер карты каждого снимка в нем. Карта определяется сетом и названием:
+    // номер в сете бывает у нескольких карт
+    let mut cards: Vec<Card> = Vec::new();
+    let mut positions: HashMap<(SetId, String), usize> = HashMap::new();
+    let card_positions: Vec<Vec<usize>> = snapshots.iter()
+        .map(|snapshot| snapshot.cards().iter()
+            .map(|card| *positions.entry((card.set.id, card.name.clone())).or_insert_with(|| {
+                cards.push(card.clone());
+                cards.len() - 1
+            }))
+            .collect())
+        .collect();
+
+    let batches = settings.trials.div_ceil(BATCH_SIZE).max(1);
+    let stats: Vec<Vec<BatchStats>> = run_blocks(batches, settings.threads, |batch| {
+        snapshots.iter().zip(card_positions.iter())
+            .map(|(snapshot, positions)| run_batch(snapshot, positions, cards.len(), settings.seed, batch))
+            .collect()
+    });
+
+    let completion: Option<Vec<Vec<f64>>> = settings.economy.as_ref().map(|economy| {
+        run_blocks(settings.players, settings.threads, |player| {
+            snapshots.iter()
+                .map(|snapshot| simulate_player(snapshot, economy, &mut StdRng::seed_from_u64(block_seed(settings.seed, player))).spent)
+                .collect()
+        })
+    });
+
+    let metric = |variant: usize, value: &dyn Fn(&BatchStats) -> f64| -> (Estimate, Estimate) {
+        let samples: Vec<f64> = stats.iter().map(|batch| value(&batch[variant])).collect();
+        let diffs: Vec<f64> = stats.iter().map(|batch| value(&batch[variant]) - value(&batch[0])).collect();
+        (Estimate::from_samples(&samples), Estimate::from_samples(&diffs))
+    };
+    let variants = rules.iter().enumerate().map(|(variant, rules)| {
+        let (gold_rate, gold_rate_diff) = metric(variant, &|s| s.gold);
+        let (hall_of_fame_rate, hall_of_fame_rate_diff) = metric(variant, &|s| s.hall_of_fame);
+        let (completion_cost, completion_cost_diff) = match completion.as_ref() {
+            Some(players) => {
+                let spent: Vec<f64> = players.iter().map(|p| p[variant]).collect();
+                let diffs: Vec<f64> = players.iter().map(|p| p[variant] - p[0]).collect();
+                (Some(Estimate::from_samples(&spent)), Some(Estimate::from_samples(&diffs)))
+            }
+            None => (None, None),
+        };
+        VariantComparison {
+            rules: rules.clone(),
+            gold_rate,
+            hall_of_fame_rate,
+            completion_cost,
+            gold_rate_diff,
+            hall_of_fame_rate_diff,
+            completion_cost_diff,
+        }
+    }).collect();
+
+    let mut cards: Vec<CardRateComparison> = cards.into_iter().enumerate().map(|(i, card)| {
+        let (rates, diffs) = (0..rules.len()).map(|variant| metric(variant, &|s| s.cards[i])).unzip();
+        CardRateComparison { card, rates, diffs }
+    }).collect();
+    cards.sort_by(|a, b| (a.card.set.id, a.card.id_in_set, &a.card.name).cmp(&(b.card.set.id, b.card.id_in_set, &b.card.name)));
+
+    Ok(Comparison { trials: batches * BATCH_SIZE, variants, cards })
+}
+
+fn run_batch(snapshot: &PoolSnapshot, positions: &[usize], cards: usize, seed: u64, batch: usize) -> BatchStats {
+    let mut rng = StdRng::seed_from_u64(block_seed(seed, batch));
+    let mut buster = OpenedBuster::default();
+    let mut counts = vec![0u32; cards];
+    let (mut gold, mut hall_of_fame) = (0u32, 0u32);
+    for _ in 0..BATCH_SIZE {
+        snapshot.generate_into(&mut rng, &mut buster);
+        for &index in buster.cards.iter() {
+            let card = snapshot.card(index);
+            counts[positions[index]] += 1;
+            gold += u32::from(card.rarity == CardRarity::Gold);
+            hall_of_fame += u32::from(card.set.id != snapshot.set());
+        }
+    }
+    let per_buster = |count: u32| f64::from(count) / BATCH_SIZE as f64;
+    BatchStats {
+        gold: per_buster(gold),
+        hall_of_fame: per_buster(hall_of_fame),
+        cards: counts.into_iter().map(per_buster).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::{Distribution, fixture};
+
+    /// Правила фикстуры с другим шансом золотого героя
+    fn with_hero_gold(rules: BusterRules, hero_gold: f64) -> BusterRules {
+        BusterRules { hero_chanse: Distribution::new([1.0 - hero_gold, hero_gold]).unwrap(), ..rules }
+    }
+
+    fn settings(trials: usize) -> ComparisonSettings {
+        ComparisonSettings { trials, seed: 11, threads: 4, ..Default::default() }
+    }
+
+    #[test]
+    async fn compare_same_rules_then_zero_difference() {
+        // GIVEN
+        let pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        let rules = [fixture::rules(0.1), fixture::rules(0.1)];
+        // WHEN
+        let comparison = compare(&pool, &rules, &settings(20_000)).unwrap();
+        // THEN
+        // Общие случайные числа: одинаковые правила дают одинаковые бустеры
+        let same = &comparison.variants[1];
+        assert_eq!(same.gold_rate, comparison.variants[0].gold_rate);
+        assert_eq!(same.gold_rate_diff, Estimate { mean: 0.0, half_width: 0.0 });
+        assert!(comparison.cards.iter().all(|c| c.diffs[1].mean == 0.0));
+    }
+
+    #[test]
+    async fn compare_detects_gold_rate_change() {
+        // GIVEN
+        let pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        let rules = [fixture::rules(0.0), with_hero_gold(fixture::rules(0.0), 0.5)];
+        // WHEN
+        let comparison = compare(&pool, &rules, &settings(50_000)).unwrap();
+        // THEN
+        let base = &comparison.variants[0];
+        let changed = &comparison.variants[1];
+        // Ожидаемое число золотых карт: 0.2 героя + 0.1 приказа + 1 + 0.3 основных карт
+        assert!((base.gold_rate.low()..=base.gold_rate.high()).contains(&1.6));
+        assert!((changed.gold_rate_diff.low()..=changed.gold_rate_diff.high()).contains(&0.3));
+        assert!(changed.gold_rate_diff.is_significant());
+        assert!(!changed.hall_of_fame_rate_diff.is_significant());
+        assert_eq!(changed.hall_of_fame_rate.mean, 0.0);
+    }
+
+    #[test]
+    async fn common_random_numbers_narrow_difference_interval() {
+        // GIVEN
+        let pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        let rules = [fixture::rules(0.02), fixture::rules(0.05)];
+        // WHEN
+        let comparison = compare(&pool, &rules, &settings(50_000)).unwrap();
+        // THEN
+        let changed = &comparison.variants[1];
+        let independent = (comparison.variants[0].gold_rate.half_width.powi(2) + changed.gold_rate.half_width.powi(2)).sqrt();
+        assert!(changed.gold_rate_diff.half_width < independent);
+        assert!(changed.hall_of_fame_rate_diff.is_significant());
+        assert!(changed.hall_of_fame_rate_diff.mean > 0.0);
+    }
+
+    #[test]
+    async fn compare_completion_cost() {
+        // GIVEN
+        let pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        let rules = [fixture::rules(0.02), fixture::rules(0.02)];
+        let settings = ComparisonSettings { economy: Some(EconomyRules::default()), players: 30, ..settings(1024) };
+        // WHEN
+        let comparison = compare(&pool, &rules, &settings).unwrap();
+        // THEN
+        let cost = comparison.variants[0].completion_cost.unwrap();
+        assert!(cost.mean > 0.0);
+        assert_eq!(comparison.variants[1].completion_cost_diff.unwrap().mean, 0.0);
+        assert_eq!(comparison.trials, 1024);
+    }
+
+    #[test]
+    async fn compare_keeps_cards_with_same_number_apart() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        sqlx::query("UPDATE cards SET id_in_set = 1 WHERE set_id = 2 AND id_in_set = 2").execute(&db_pull).await.unwrap();
+        let pool = CardPool::load(&db_pull).await.unwrap();
+        let rules = [fixture::rules(0.02), fixture::rules(0.05)];
+        // WHEN
+        let comparison = compare(&pool, &rules, &settings(1024)).unwrap();
+        // THEN
+        let same_number: Vec<&CardRateComparison> = comparison.cards.iter()
+            .filter(|c| c.card.set.id == SetId(2) && c.card.id_in_set == 1)
+            .collect();
+        assert_eq!(comparison.cards.len(), 16 + 3);
+        assert_eq!(same_number.len(), 2);
+        assert_ne!(same_number[0].card.name, same_number[1].card.name);
+    }
+
+    #[test]
+    async fn compare_without_rules_then_error() {
+        let pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        assert!(compare(&pool, &[], &settings(1024)).is_err());
+    }
+
+    #[test]
+    async fn estimate_from_samples() {
+        // WHEN
+        let estimate = Estimate::from_samples(&[1.0, 2.0, 3.0, 4.0]);
+        // THEN
+        assert_eq!(estimate.mean, 2.5);
+        assert!((estimate.half_width - Z_95 * (5.0f64 / 3.0 / 4.0).sqrt()).abs() < 1e-12);
+        assert!(Estimate::from_samples(&[1.0]).half_width.is_infinite());
+    }
+}
@@ -0,0 +1,252 @@
+//! HTML-страница задачи симуляции. Графики рисуются встроенным SVG,
+//! поэтому страница не требует JavaScript и внешних файлов
+
+use std::fmt::Write;
+
+use crate::{
+    Locale, Localize,
+    jobs::{Histogram, JobState, JobStatus, SimulationResult},
+    locale::Translation,
+};
+
+/// Ширина графиков в пикселях
+const CHART_WIDTH: f64 = 640.0;
+/// Высота области столбцов вертикальной диаграммы
+const COLUMNS_HEIGHT: f64 = 200.0;
+/// Высота строки горизонтальной диаграммы
+const BAR_HEIGHT: f64 = 22.0;
+/// Ширина подписей слева от горизонтальных столбцов
+const LABEL_WIDTH: f64 = 220.0;
+
+/// Экранирует текст для HTML и SVG
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Вертикальная столбчатая диаграмма, подписи под столбцами, значения над ними
+pub fn column_chart(title: &str, columns: &[(String, f64)]) -> String {
+    let max = columns.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+    let step = CHART_WIDTH / columns.len().max(1) as f64;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{CHART_WIDTH}" height="{}" role="img"><title>{}</title>"#,
+        COLUMNS_HEIGHT + 40.0,
+        escape(title),
+    );
+    for (i, (label, value)) in columns.iter().enumerate() {
+        let height = if max > 0.0 { value / max * COLUMNS_HEIGHT } else { 0.0 };
+        let (x, y) = (i as f64 * step, 20.0 + COLUMNS_HEIGHT - height);
+        let _ = write!(
+            svg,
+            r##"<rect x="{:.1}" y="{y:.1}" width="{:.1}" height="{height:.1}" fill="#4a7ebb"/><text x="{:.1}" y="{:.1}" font-size="11" text-anchor="middle">{value:.3}</text><text x="{:.1}" y="{:.1}" font-size="12" text-anchor="middle">{}</text>"##,
+            x + step * 0.1,
+            step * 0.8,
+            x + step / 2.0,
+            y - 4.0,
+            x + step / 2.0,
+            COLUMNS_HEIGHT + 36.0,
+            escape(label),
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Горизонтальная столбчатая диаграмма, подписи слева, значения справа от столбцов
+pub fn bar_chart(title: &str, bars: &[(String, f64)]) -> String {
+    let max = bars.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+    let area = CHART_WIDTH - LABEL_WIDTH - 60.0;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{CHART_WIDTH}" height="{:.0}" role="img"><title>{}</title>"#,
+        bars.len() as f64 * BAR_HEIGHT,
+        escape(title),
+    );
+    for (i, (label, value)) in bars.iter().enumerate() {
+        let width = if max > 0.0 { value / max * area } else { 0.0 };
+        let y = i as f64 * BAR_HEIGHT;
+        let _ = write!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" font-size="12" text-anchor="end">{}</text><rect x="{LABEL_WIDTH}" y="{:.1}" width="{width:.1}" height="{:.1}" fill="#4a7ebb"/><text x="{:.1}" y="{:.1}" font-size="11">{value:.4}</text>"##,
+            LABEL_WIDTH - 6.0,
+            y + 15.0,
+            escape(label),
+            y + 3.0,
+            BAR_HEIGHT - 6.0,
+            LABEL_WIDTH + width + 4.0,
+            y + 15.0,
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Доли бустеров по числу карт, без пустого хвоста гистограммы
+fn histogram_chart(title: &str, histogram: &Histogram) -> String {
+    let total: u64 = histogram.counts.iter().sum();
+    let last = histogram.counts.iter().rposition(|&count| count > 0).unwrap_or(0);
+    let columns: Vec<(String, f64)> = histogram.counts[..=last].iter().enumerate()
+        .map(|(k, &count)| (k.to_string(), count as f64 / total as f64))
+        .collect();
+    column_chart(title, &columns)
+}
+
+fn histogram_section(out: &mut String, title: &str, histogram: &Histogram, locale: Locale) {
+    let text = |ru, en| Translation { ru, en }.get(locale);
+    let _ = write!(
+        out,
+        "<h2>{}</h2>{}<p>{}: {:.3}, p10: {}, {}: {}, p90: {}, p99: {}</p>",
+        escape(title),
+        histogram_chart(title, histogram),
+        text("Среднее", "Mean"),
+        histogram.mean,
+        histogram.p10,
+        text("медиана", "median"),
+        histogram.median,
+        histogram.p90,
+        histogram.p99,
+    );
+}
+
+fn result_section(out: &mut String, result: &SimulationResult, locale: Locale) {
+    let text = |ru, en| Translation { ru, en }.get(locale);
+    histogram_section(out, text("Золотые карты в бустере", "Gold cards per booster"), &result.gold_cards, locale);
+    histogram_section(out, text("Карты Зала Славы в бустере", "Hall of Fame cards per booster"), &result.hall_of_fame_cards, locale);
+
+    // Копии в бустере по сету и редкости, в порядке первого появления в снимке
+    let mut groups: Vec<(String, f64)> = Vec::new();
+    for rate in result.cards.iter() {
+        let label = format!("{} {}", rate.card.set.localized(locale), rate.card.rarity.localized(locale));
+        match groups.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, value)) => *value += rate.per_buster,
+            None => groups.push((label, rate.per_buster)),
+        }
+    }
+    let title = text("Копий в бустере по сету и редкости", "Copies per booster by set and rarity");
+    let _ = write!(out, "<h2>{}</h2>{}", escape(title), bar_chart(title, &groups));
+
+    let mut cards: Vec<_> = result.cards.iter().collect();
+    cards.sort_by(|a, b| b.per_buster.total_cmp(&a.per_buster));
+    let _ = write!(
+        out,
+        "<h2>{}</h2><table><tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th></tr>",
+        text("Карты", "Cards"),
+        text("Название", "Name"),
+        text("Сет", "Set"),
+        text("Редкость", "Rarity"),
+        text("Слот", "Slot"),
+        text("Копий в бустере", "Copies per booster"),
+    );
+    for rate in cards {
+        let _ = write!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.5}</td></tr>",
+            escape(&rate.card.name),
+            escape(&rate.card.set.localized(locale).to_string()),
+            rate.card.rarity.localized(locale),
+            rate.card.buster_slot.localized(locale),
+            rate.per_buster,
+        );
+    }
+    out.push_str("</table>");
+}
+
+/// Страница задачи. Пока задача выполняется, страница обновляется каждые две секунды
+pub fn job_page(status: &JobStatus, locale: Locale) -> String {
+    let text = |ru, en| Translation { ru, en }.get(locale);
+    let title = format!("{} {}", text("Симуляция", "Simulation"), status.id);
+    let refresh = if status.state == JobState::Running { r#"<meta http-equiv="refresh" content="2">"# } else { "" };
+    let mut page = format!(
+        "<!DOCTYPE html><html lang=\"{}\"><head><meta charset=\"utf-8\">{}<title>{}</title>\
+        <style>body{{font-family:sans-serif;max-width:{}px;margin:auto}}td,th{{padding:2px 8px;text-align:left}}</style>\
+        </head><body><h1>{}</h1>",
+        locale.code(),
+        refresh,
+        escape(&title),
+        CHART_WIDTH + 40.0,
+        escape(&title),
+    );
+    let _ = write!(
+        page,
+        r##"<p>{}: {} / {}</p><svg xmlns="http://www.w3.org/2000/svg" width="{CHART_WIDTH}" height="16"><rect width="{CHART_WIDTH}" height="16" fill="#ddd"/><rect width="{:.1}" height="16" fill="#4a7ebb"/></svg>"##,
+        text("Открыто бустеров", "Boosters opened"),
+        status.done,
+        status.trials,
+        status.progress() * CHART_WIDTH,
+    );
+    match (&status.state, &status.result, &status.error) {
+        (JobState::Done, Some(result), _) => {
+            let _ = write!(page, "<p>{}: {}</p>", text("Зерно", "Seed"), result.seed);
+            result_section(&mut page, result, locale);
+        }
+        (JobState::Failed, _, Some(error)) => {
+            let _ = write!(page, "<p>{}: {}</p>", text("Ошибка", "Error"), escape(error));
+        }
+        _ => {}
+    }
+    page.push_str("</body></html>");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::{fixture, jobs::{SimulationJobs, SimulationRequest}};
+
+    #[test]
+    async fn escape_html() {
+        assert_eq!(escape(r#"<a href="x">'Лорд' & Ко</a>"#), "&lt;a href=&quot;x&quot;&gt;&#39;Лорд&#39; &amp; Ко&lt;/a&gt;");
+    }
+
+    #[test]
+    async fn charts_have_bar_for_each_value() {
+        // GIVEN
+        let values = vec![("a".to_string(), 1.0), ("<b>".to_string(), 0.5), ("c".to_string(), 0.0)];
+        // WHEN
+        let columns = column_chart("Заголовок", &values);
+        let bars = bar_chart("Заголовок", &values);
+        // THEN
+        for svg in [columns, bars] {
+            assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+            assert_eq!(svg.matches("<rect").count(), 3);
+            assert!(svg.contains("&lt;b&gt;") && !svg.contains("<b>"));
+        }
+    }
+
+    #[test]
+    async fn job_page_for_running_and_done_job() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        let jobs = SimulationJobs::new();
+        let request: SimulationRequest = serde_json::from_value(serde_json::json!({ "set": "КОВ", "trials": 1000 })).unwrap();
+        let id = jobs.start(&db_pull, request).await.unwrap();
+        let mut status = jobs.status(id).unwrap();
+        // WHEN
+        status.state = JobState::Running;
+        let running = job_page(&status, Locale::En);
+        while jobs.status(id).unwrap().state == JobState::Running {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let done = job_page(&jobs.status(id).unwrap(), Locale::En);
+        // THEN
+        assert!(running.contains(r#"http-equiv="refresh""#));
+        assert!(!done.contains(r#"http-equiv="refresh""#));
+        assert!(done.contains("Boosters opened: 1000 / 1000"));
+        assert!(done.contains("Gold cards per booster"));
+        // Прогресс и три графика
+        assert_eq!(done.matches("<svg").count(), 4);
+        // 16 карт КОВ и 3 карты Зала Славы
+        assert_eq!(done.matches("<tr>").count(), 1 + 19);
+    }
+}
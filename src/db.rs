@@ -0,0 +1,88 @@
+use std::{path::Path, str::FromStr};
+
+use sqlx::{
+    SqlitePool,
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
+
+/// БД по умолчанию, если не задан `DATABASE_URL`
+pub const DEFAULT_DATABASE_PATH: &str = "data/database.db";
+
+/// Миграции из каталога `migrations/`, встроенные в бинарник
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Открывает БД по пути к файлу. Если файла нет, он создается вместе с каталогом.
+/// Недостающие миграции применяются автоматически
+pub async fn open(path: impl AsRef<Path>) -> Result<SqlitePool, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    connect(SqliteConnectOptions::new().filename(path)).await
+}
+
+/// Открывает БД по URL вида `sqlite://data/database.db` или `sqlite::memory:`
+pub async fn open_url(url: &str) -> Result<SqlitePool, Box<dyn std::error::Error>> {
+    let options = SqliteConnectOptions::from_str(url)?;
+    if let Some(parent) = options.get_filename().parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    connect(options).await
+}
+
+/// Открывает БД из `DATABASE_URL`, а если переменная не задана, то `data/database.db`
+pub async fn open_from_env() -> Result<SqlitePool, Box<dyn std::error::Error>> {
+    match std::env::var("DATABASE_URL") {
+        Ok(url) => open_url(&url).await,
+        Err(_) => open(DEFAULT_DATABASE_PATH).await,
+    }
+}
+
+async fn connect(options: SqliteConnectOptions) -> Result<SqlitePool, Box<dyn std::error::Error>> {
+    let options = options.create_if_missing(true).foreign_keys(true);
+    let in_memory = options.get_filename() == Path::new(":memory:");
+    let pool_options = if in_memory {
+        // У каждого соединения своя БД в памяти, поэтому соединение должно быть одно и жить вечно
+        SqlitePoolOptions::new().max_connections(1).idle_timeout(None).max_lifetime(None)
+    } else {
+        SqlitePoolOptions::new()
+    };
+    let pool = pool_options.connect_with(options).await?;
+    MIGRATOR.run(&pool).await?;
+    Ok(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    #[test]
+    async fn open_missing_file_then_created_and_migrated() {
+        // GIVEN
+        let dir = std::env::temp_dir().join(format!("response-sim-db-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join("database.db");
+        // WHEN
+        let pool = open(&path).await.unwrap();
+        // THEN
+        let sets: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sets").fetch_one(&pool).await.unwrap();
+        assert!(path.exists());
+        assert_eq!(sets, 3);
+        pool.close().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    async fn open_in_memory_then_foreign_keys_enabled() {
+        // GIVEN
+        let pool = open_url("sqlite::memory:").await.unwrap();
+        // WHEN
+        let result = sqlx::query("INSERT INTO cards (id, name, rarity_id, type_id, set_id, id_in_set) VALUES (1, 'Карта', 1, 1, 100, 1)")
+            .execute(&pool)
+            .await;
+        // THEN
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,650 @@
+//! Колоды игроков: состав, проверка по правилам построения и коды для обмена колодами.
+//! Карты в колоде задаются идентификаторами из таблицы `cards`, а в коде колоды —
+//! кодом сета и номером в сете, чтобы код читался в любой БД
+
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, fmt::Display};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{CardBusterSlot, CardRarity, SetCode, query::{self, FoundCard}};
+
+/// Версия формата кода колоды, первый байт кода
+pub const DECK_CODE_VERSION: u8 = 3;
+
+/// Колода: герой и карты с количеством копий
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deck {
+    /// Идентификатор карты героя в БД
+    pub hero: Option<i64>,
+    /// Идентификатор карты в БД и число копий, без героя
+    pub cards: BTreeMap<i64, u32>,
+}
+
+impl Deck {
+    pub fn new(hero: i64) -> Self {
+        Deck { hero: Some(hero), cards: BTreeMap::new() }
+    }
+
+    /// Добавляет копии карты, повторные добавления суммируются
+    pub fn add(&mut self, card_id: i64, count: u32) {
+        if count > 0 {
+            *self.cards.entry(card_id).or_default() += count;
+        }
+    }
+
+    /// Число карт без героя
+    pub fn size(&self) -> u32 {
+        self.cards.values().sum()
+    }
+
+    /// Идентификаторы всех карт колоды вместе с героем
+    pub fn card_ids(&self) -> Vec<i64> {
+        self.hero.iter().chain(self.cards.keys()).copied().collect()
+    }
+
+    /// Код колоды: base64 (URL-safe, без `=`) от байтов
+    /// `версия, число сетов, коды сетов, герой, число разных карт, карты (сет, номер в сете, название, копии)`.
+    /// Числа записаны как LEB128, строки — длиной и байтами UTF-8, сет карты — индексом в списке сетов.
+    /// Название записывается, только если тот же номер в сете есть у нескольких карт, иначе это пустая строка.
+    /// Герой записан как индекс сета + 1, номер в сете и название, отсутствие героя кодируется нулем
+    pub async fn code(&self, db_pull: &SqlitePool) -> Result<String, Box<dyn std::error::Error>> {
+        let mut keys = HashMap::new();
+        for found in query::cards_by_id(db_pull, &self.card_ids()).await? {
+            let set = found.card.set.code();
+            let shared = cards_with_number(db_pull, &set, found.card.id_in_set).await? > 1;
+            keys.insert(found.id, CardKey { set, id_in_set: found.card.id_in_set, name: shared.then_some(found.card.name) });
+        }
+        let key = |card_id: i64| keys.get(&card_id).cloned().ok_or_else(|| format!("Error. Not found card: {}", card_id));
+        let hero = self.hero.map(key).transpose()?;
+        let mut cards = BTreeMap::new();
+        for (&card_id, &count) in self.cards.iter() {
+            cards.insert(key(card_id)?, count);
+        }
+        DeckKeys { hero, cards }.encode()
+    }
+
+    /// Колода из кода [`Deck::code`], карты ищутся в БД по сету, номеру в сете и названию, если оно есть.
+    /// Если без названия под ключ подходит несколько карт, это ошибка, а не первая из них
+    pub async fn from_code(db_pull: &SqlitePool, code: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let keys = DeckKeys::decode(code)?;
+        let hero = match keys.hero {
+            Some(hero) => Some(card_id_by_key(db_pull, &hero).await?),
+            None => None,
+        };
+        let mut cards = BTreeMap::new();
+        for (key, count) in keys.cards {
+            cards.insert(card_id_by_key(db_pull, &key).await?, count);
+        }
+        Ok(Deck { hero, cards })
+    }
+
+    /// Проверяет колоду по правилам, карты загружаются из БД
+    pub async fn validate(&self, db_pull: &SqlitePool, rules: &DeckRules) -> Result<Vec<DeckProblem>, Box<dyn std::error::Error>> {
+        let cards = query::cards_by_id(db_pull, &self.card_ids()).await?;
+        Ok(self.check(&cards, rules))
+    }
+
+    /// Проверяет колоду по правилам. В `cards` должны быть все карты колоды,
+    /// карты, которых там нет, считаются неизвестными
+    pub fn check(&self, cards: &[FoundCard], rules: &DeckRules) -> Vec<DeckProblem> {
+        let cards: HashMap<i64, &FoundCard> = cards.iter().map(|c| (c.id, c)).collect();
+        let mut problems = Vec::new();
+
+        let hero = match self.hero {
+            None => {
+                problems.push(DeckProblem::NoHero);
+                None
+            }
+            Some(hero) => match cards.get(&hero) {
+                None => {
+                    problems.push(DeckProblem::UnknownCard { card_id: hero });
+                    None
+                }
+                Some(card) if card.card.buster_slot != CardBusterSlot::Hero => {
+                    problems.push(DeckProblem::NotAHero { card_id: hero });
+                    None
+                }
+                Some(card) => Some(*card),
+            },
+        };
+        if self.size() != rules.deck_size {
+            problems.push(DeckProblem::WrongSize { expected: rules.deck_size, actual: self.size() });
+        }
+
+        for card in hero.into_iter().chain(self.cards.keys().filter_map(|id| cards.get(id).copied())) {
+            if rules.sets.as_ref().is_some_and(|sets| !sets.contains(&card.card.set.code())) {
+                problems.push(DeckProblem::IllegalSet { card_id: card.id, set: card.card.set.code() });
+            }
+        }
+        for (&card_id, &count) in self.cards.iter() {
+            let Some(card) = cards.get(&card_id) else {
+                problems.push(DeckProblem::UnknownCard { card_id });
+                continue;
+            };
+            if card.card.buster_slot == CardBusterSlot::Hero {
+                problems.push(DeckProblem::ExtraHero { card_id });
+                continue;
+            }
+            let limit = rules.copy_limit(card.card.rarity);
+            if count > limit {
+                problems.push(DeckProblem::TooManyCopies { card_id, rarity: card.card.rarity, count, limit });
+            }
+            let hero_factions = hero.map_or(&[][..], |h| h.factions.as_slice());
+            let allowed = card.factions.is_empty()
+                || hero_factions.is_empty()
+                || hero_factions.iter().any(|faction| card.factions.contains(faction));
+            if !allowed {
+                problems.push(DeckProblem::WrongFaction { card_id, faction: card.factions.join(", "), hero_faction: hero_factions.join(", ") });
+            }
+        }
+        problems
+    }
+}
+
+/// Карта в коде колоды: код сета, номер в сете и название, если номер в сете не уникален
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct CardKey {
+    set: SetCode,
+    id_in_set: u32,
+    name: Option<String>,
+}
+
+impl Display for CardKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} {} {}", self.set, self.id_in_set, name),
+            None => write!(f, "{} {}", self.set, self.id_in_set),
+        }
+    }
+}
+
+/// Содержимое кода колоды, не зависящее от идентификаторов в БД
+#[derive(Debug, Default, PartialEq)]
+struct DeckKeys {
+    hero: Option<CardKey>,
+    cards: BTreeMap<CardKey, u32>,
+}
+
+impl DeckKeys {
+    fn encode(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let sets: Vec<&SetCode> = self.hero.iter().chain(self.cards.keys())
+            .map(|key| &key.set)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let set_index = |set: &SetCode| -> Result<u64, Box<dyn std::error::Error>> {
+            let index = sets.binary_search(&set).map_err(|_| format!("Error. Not found set in deck code: {}", set))?;
+            Ok(u64::try_from(index)?)
+        };
+
+        let mut bytes = vec![DECK_CODE_VERSION];
+        write_varint(&mut bytes, u64::try_from(sets.len())?);
+        for set in sets.iter() {
+            write_string(&mut bytes, &set.0)?;
+        }
+        match &self.hero {
+            None => write_varint(&mut bytes, 0),
+            Some(hero) => {
+                write_varint(&mut bytes, set_index(&hero.set)? + 1);
+                write_varint(&mut bytes, u64::from(hero.id_in_set));
+                write_string(&mut bytes, hero.name.as_deref().unwrap_or_default())?;
+            }
+        }
+        write_varint(&mut bytes, u64::try_from(self.cards.len())?);
+        for (key, &count) in self.cards.iter() {
+            write_varint(&mut bytes, set_index(&key.set)?);
+            write_varint(&mut bytes, u64::from(key.id_in_set));
+            write_string(&mut bytes, key.name.as_deref().unwrap_or_default())?;
+            write_varint(&mut bytes, u64::from(count));
+        }
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn decode(code: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = URL_SAFE_NO_PAD.decode(code.trim())?;
+        let (&version, mut rest) = bytes.split_first().ok_or("Error. Empty deck code")?;
+        if version != DECK_CODE_VERSION {
+            return Err(format!("Error. Not supported deck code version: {}", version).into());
+        }
+        let mut sets = Vec::new();
+        for _ in 0..read_varint(&mut rest)? {
+            sets.push(SetCode(read_string(&mut rest)?));
+        }
+        let read_key = |set_index: u64, rest: &mut &[u8]| -> Result<CardKey, Box<dyn std::error::Error>> {
+            let set = usize::try_from(set_index).ok()
+                .and_then(|index| sets.get(index))
+                .cloned()
+                .ok_or_else(|| format!("Error. Invalid set index in deck code: {}", set_index))?;
+            let id_in_set = u32::try_from(read_varint(rest)?)?;
+            let name = Some(read_string(rest)?).filter(|name| !name.is_empty());
+            Ok(CardKey { set, id_in_set, name })
+        };
+
+        let hero = match read_varint(&mut rest)? {
+            0 => None,
+            index => Some(read_key(index - 1, &mut rest)?),
+        };
+        let entries = read_varint(&mut rest)?;
+        let mut keys = DeckKeys { hero, cards: BTreeMap::new() };
+        for _ in 0..entries {
+            let set_index = read_varint(&mut rest)?;
+            let key = read_key(set_index, &mut rest)?;
+            let count = u32::try_from(read_varint(&mut rest)?)?;
+            if count == 0 || keys.cards.contains_key(&key) {
+                return Err(format!("Error. Invalid deck code entry: {}", key).into());
+            }
+            keys.cards.insert(key, count);
+        }
+        if !rest.is_empty() {
+            return Err("Error. Unexpected bytes at the end of deck code".into());
+        }
+        Ok(keys)
+    }
+}
+
+/// Число карт сета `set` с номером `id_in_set`
+async fn cards_with_number(db_pull: &SqlitePool, set: &SetCode, id_in_set: u32) -> Result<i64, Box<dyn std::error::Error>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM cards
+        INNER JOIN sets ON cards.set_id = sets.id
+        WHERE sets.short_name = ? AND cards.id_in_set = ?",
+        set.0,
+        id_in_set,
+    ).fetch_one(db_pull).await?)
+}
+
+async fn card_id_by_key(db_pull: &SqlitePool, key: &CardKey) -> Result<i64, Box<dyn std::error::Error>> {
+    let ids = sqlx::query_scalar!(
+        "SELECT cards.id as \"id!\" FROM cards
+        INNER JOIN sets ON cards.set_id = sets.id
+        WHERE sets.short_name = ? AND cards.id_in_set = ? AND (? IS NULL OR cards.name = ?)",
+        key.set.0,
+        key.id_in_set,
+        key.name,
+        key.name,
+    ).fetch_all(db_pull).await?;
+    match ids.as_slice() {
+        [id] => Ok(*id),
+        [] => Err(format!("Error. Not found card: {}", key).into()),
+        _ => Err(format!("Error. Ambiguous card in deck code: {}", key).into()),
+    }
+}
+
+/// Правила построения колоды
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeckRules {
+    /// Число карт без героя
+    pub deck_size: u32,
+    pub bronze_copies: u32,
+    pub silver_copies: u32,
+    pub gold_copies: u32,
+    /// Сеты, карты которых разрешены. `None` — все сеты
+    pub sets: Option<Vec<SetCode>>,
+}
+
+impl Default for DeckRules {
+    fn default() -> Self {
+        DeckRules { deck_size: 40, bronze_copies: 3, silver_copies: 2, gold_copies: 1, sets: None }
+    }
+}
+
+impl DeckRules {
+    /// Максимальное число копий карты редкости `rarity`
+    pub fn copy_limit(&self, rarity: CardRarity) -> u32 {
+        match rarity {
+            CardRarity::Bronze => self.bronze_copies,
+            CardRarity::Silver => self.silver_copies,
+            CardRarity::Gold => self.gold_copies,
+        }
+    }
+}
+
+/// Нарушение правил построения колоды
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum DeckProblem {
+    NoHero,
+    /// Карта героя колоды не является героем
+    NotAHero { card_id: i64 },
+    /// Герой среди карт колоды
+    ExtraHero { card_id: i64 },
+    UnknownCard { card_id: i64 },
+    WrongSize { expected: u32, actual: u32 },
+    TooManyCopies { card_id: i64, rarity: CardRarity, count: u32, limit: u32 },
+    /// Карта чужой фракции: ни одна фракция героя не входит в фракции карты.
+    /// Карты без фракции нейтральны и разрешены с любым героем
+    WrongFaction { card_id: i64, faction: String, hero_faction: String },
+    IllegalSet { card_id: i64, set: SetCode },
+    /// Карта забанена в формате
+    Banned { card_id: i64 },
+    /// Несколько копий ограниченной в формате карты
+    Restricted { card_id: i64, count: u32 },
+}
+
+impl Display for DeckProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeckProblem::NoHero => write!(f, "deck has no hero"),
+            DeckProblem::NotAHero { card_id } => write!(f, "card {} is not a hero", card_id),
+            DeckProblem::ExtraHero { card_id } => write!(f, "hero {} among deck cards", card_id),
+            DeckProblem::UnknownCard { card_id } => write!(f, "unknown card {}", card_id),
+            DeckProblem::WrongSize { expected, actual } => write!(f, "deck has {} cards, expected {}", actual, expected),
+            DeckProblem::TooManyCopies { card_id, rarity, count, limit } =>
+                write!(f, "card {}: {} copies of {} card, limit {}", card_id, count, rarity, limit),
+            DeckProblem::WrongFaction { card_id, faction, hero_faction } =>
+                write!(f, "card {}: faction {} does not match hero faction {}", card_id, faction, hero_faction),
+            DeckProblem::IllegalSet { card_id, set } => write!(f, "card {}: set {} is not allowed", card_id, set),
+            DeckProblem::Banned { card_id } => write!(f, "card {} is banned", card_id),
+            DeckProblem::Restricted { card_id, count } => write!(f, "card {} is restricted, {} copies", card_id, count),
+        }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    write_varint(bytes, u64::try_from(value.len())?);
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn read_string(bytes: &mut &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let len = usize::try_from(read_varint(bytes)?)?;
+    if bytes.len() < len {
+        return Err("Error. Truncated deck code".into());
+    }
+    let (value, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(String::from_utf8(value.to_vec())?)
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or("Error. Truncated deck code")?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Error. Too long number in deck code".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture::{FixtureBuilder, HALL_OF_FAME, KOV};
+
+    fn rules(deck_size: u32) -> DeckRules {
+        DeckRules { deck_size, ..Default::default() }
+    }
+
+    /// Фикстура с фракциями, идентификаторы карт по порядку добавления:
+    /// 1 — герой Империи, 2 — бронзовый приказ Империи, 3 — серебряная карта Леса,
+    /// 4 — нейтральная золотая карта, 5 — герой Зала Славы
+    async fn deck_fixture() -> SqlitePool {
+        FixtureBuilder::new()
+            .described_card(KOV, CardBusterSlot::Hero, CardRarity::Gold, "Лорд Доминус", &["Империя"], 5, "")
+            .described_card(KOV, CardBusterSlot::Command, CardRarity::Bronze, "Приказ", &["Империя"], 1, "")
+            .described_card(KOV, CardBusterSlot::BasicCard, CardRarity::Silver, "Лучник", &["Лес"], 2, "")
+            .cards(KOV, CardBusterSlot::BasicCard, CardRarity::Gold, 1)
+            .cards(HALL_OF_FAME, CardBusterSlot::Hero, CardRarity::Gold, 1)
+            .build().await
+    }
+
+    /// Те же карты, что в [`deck_fixture`], с теми же номерами в сетах, но с другими
+    /// идентификаторами в БД: первыми добавлены карты БАЗ и Зала Славы
+    async fn shifted_deck_fixture() -> SqlitePool {
+        FixtureBuilder::new()
+            .cards("БАЗ", CardBusterSlot::BasicCard, CardRarity::Bronze, 3)
+            .cards(HALL_OF_FAME, CardBusterSlot::Hero, CardRarity::Gold, 1)
+            .described_card(KOV, CardBusterSlot::Hero, CardRarity::Gold, "Лорд Доминус", &["Империя"], 5, "")
+            .described_card(KOV, CardBusterSlot::Command, CardRarity::Bronze, "Приказ", &["Империя"], 1, "")
+            .described_card(KOV, CardBusterSlot::BasicCard, CardRarity::Silver, "Лучник", &["Лес"], 2, "")
+            .cards(KOV, CardBusterSlot::BasicCard, CardRarity::Gold, 1)
+            .build().await
+    }
+
+    fn key(set: &str, id_in_set: u32) -> CardKey {
+        CardKey { set: SetCode::from(set), id_in_set, name: None }
+    }
+
+    /// Дает карте `name` сета КОВ тот же номер в сете, что у карты `other`
+    async fn share_number(db_pull: &SqlitePool, name: &str, other: &str) {
+        sqlx::query("UPDATE cards SET id_in_set = (SELECT id_in_set FROM cards WHERE name = ?) WHERE name = ?")
+            .bind(other)
+            .bind(name)
+            .execute(db_pull).await.unwrap();
+    }
+
+    #[test]
+    async fn deck_code_round_trip_between_databases() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let shifted = shifted_deck_fixture().await;
+        let mut deck = Deck::new(1);
+        deck.add(2, 3);
+        deck.add(4, 1);
+        deck.add(2, 0);
+        // WHEN
+        let code = deck.code(&db_pull).await.unwrap();
+        let decoded = Deck::from_code(&shifted, &code).await.unwrap();
+        // THEN
+        let mut expected = Deck::new(5);
+        expected.add(6, 3);
+        expected.add(8, 1);
+        assert_eq!(decoded, expected);
+        assert_eq!(Deck::from_code(&db_pull, &decoded.code(&shifted).await.unwrap()).await.unwrap(), deck);
+        assert!(code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    async fn deck_code_with_cards_sharing_number_round_trip() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let shifted = shifted_deck_fixture().await;
+        share_number(&db_pull, "Лучник", "Приказ").await;
+        share_number(&shifted, "Лучник", "Приказ").await;
+        let mut deck = Deck::new(1);
+        deck.add(2, 1);
+        deck.add(3, 2);
+        let mut archer_only = Deck::new(1);
+        archer_only.add(3, 2);
+        // WHEN
+        let code = deck.code(&db_pull).await.unwrap();
+        let archer_code = archer_only.code(&db_pull).await.unwrap();
+        // THEN
+        let mut expected = Deck::new(5);
+        expected.add(6, 1);
+        expected.add(7, 2);
+        assert_eq!(Deck::from_code(&shifted, &code).await.unwrap(), expected);
+        let mut expected_archer = Deck::new(5);
+        expected_archer.add(7, 2);
+        assert_eq!(Deck::from_code(&shifted, &archer_code).await.unwrap(), expected_archer);
+    }
+
+    #[test]
+    async fn deck_code_without_name_for_shared_number_then_error() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let shared = deck_fixture().await;
+        share_number(&shared, "Лучник", "Приказ").await;
+        let mut deck = Deck::new(1);
+        deck.add(2, 1);
+        let code = deck.code(&db_pull).await.unwrap();
+        // WHEN
+        let result = Deck::from_code(&shared, &code).await;
+        // THEN
+        assert!(result.unwrap_err().to_string().contains("Ambiguous"));
+    }
+
+    #[test]
+    async fn deck_code_with_unknown_card_then_error() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let mut deck = Deck::new(1);
+        deck.add(99, 1);
+        let code = DeckKeys { hero: Some(key(KOV, 1)), cards: BTreeMap::from([(key(KOV, 99), 1)]) }.encode().unwrap();
+        // THEN
+        assert!(deck.code(&db_pull).await.is_err());
+        assert!(Deck::from_code(&db_pull, &code).await.is_err());
+    }
+
+    #[test]
+    async fn deck_keys_round_trip() {
+        // GIVEN
+        let keys = DeckKeys {
+            hero: Some(key(HALL_OF_FAME, 7)),
+            cards: BTreeMap::from([
+                (key(KOV, 7), 3),
+                (key(KOV, 1000), 1),
+                (key("БАЗ", 2), 2),
+                (CardKey { name: Some("Цикл жизни".into()), ..key(KOV, 150) }, 1),
+            ]),
+        };
+        let without_hero = DeckKeys { hero: None, cards: BTreeMap::from([(key(KOV, 1), 2)]) };
+        // THEN
+        assert_eq!(DeckKeys::decode(&keys.encode().unwrap()).unwrap(), keys);
+        assert_eq!(DeckKeys::decode(&without_hero.encode().unwrap()).unwrap(), without_hero);
+    }
+
+    #[test]
+    async fn deck_code_does_not_depend_on_add_order() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let mut first = Deck::new(1);
+        first.add(2, 1);
+        first.add(3, 2);
+        let mut second = Deck::new(1);
+        second.add(3, 2);
+        second.add(2, 1);
+        // THEN
+        assert_eq!(first.code(&db_pull).await.unwrap(), second.code(&db_pull).await.unwrap());
+    }
+
+    #[test]
+    async fn deck_code_with_unknown_version_then_error() {
+        // GIVEN
+        let code = DeckKeys { hero: Some(key(KOV, 1)), cards: BTreeMap::new() }.encode().unwrap();
+        let mut bytes = URL_SAFE_NO_PAD.decode(code).unwrap();
+        bytes[0] = 1;
+        // WHEN
+        let result = DeckKeys::decode(&URL_SAFE_NO_PAD.encode(bytes));
+        // THEN
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn deck_code_truncated_or_with_garbage_then_error() {
+        // GIVEN
+        let keys = DeckKeys { hero: Some(key(KOV, 300)), cards: BTreeMap::from([(key(KOV, 200), 2)]) };
+        let bytes = URL_SAFE_NO_PAD.decode(keys.encode().unwrap()).unwrap();
+        // WHEN
+        let truncated = DeckKeys::decode(&URL_SAFE_NO_PAD.encode(&bytes[..bytes.len() - 1]));
+        let extra = DeckKeys::decode(&URL_SAFE_NO_PAD.encode([bytes.as_slice(), &[0]].concat()));
+        let wrong_set = DeckKeys::decode(&URL_SAFE_NO_PAD.encode([DECK_CODE_VERSION, 0, 1, 1, 0]));
+        // THEN
+        assert!(truncated.is_err());
+        assert!(extra.is_err());
+        assert!(wrong_set.is_err());
+        assert!(DeckKeys::decode("not base64!").is_err());
+    }
+
+    #[test]
+    async fn validate_legal_deck() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let mut deck = Deck::new(1);
+        deck.add(2, 3);
+        deck.add(4, 1);
+        // WHEN
+        let problems = deck.validate(&db_pull, &rules(4)).await.unwrap();
+        // THEN
+        assert_eq!(problems, vec![]);
+    }
+
+    #[test]
+    async fn validate_size_copies_and_faction() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let mut deck = Deck::new(1);
+        deck.add(2, 4);
+        deck.add(3, 1);
+        // WHEN
+        let problems = deck.validate(&db_pull, &rules(4)).await.unwrap();
+        // THEN
+        assert_eq!(problems, vec![
+            DeckProblem::WrongSize { expected: 4, actual: 5 },
+            DeckProblem::TooManyCopies { card_id: 2, rarity: CardRarity::Bronze, count: 4, limit: 3 },
+            DeckProblem::WrongFaction { card_id: 3, faction: "Лес".to_string(), hero_faction: "Империя".to_string() },
+        ]);
+    }
+
+    #[test]
+    async fn validate_multi_faction_card() {
+        // GIVEN
+        let db_pull = FixtureBuilder::new()
+            .described_card(KOV, CardBusterSlot::Hero, CardRarity::Gold, "Лорд Доминус", &["Империя"], 5, "")
+            .described_card(KOV, CardBusterSlot::BasicCard, CardRarity::Silver, "Лесной страж", &["Лес", "Империя"], 2, "")
+            .described_card(KOV, CardBusterSlot::BasicCard, CardRarity::Silver, "Дракон", &["Лес", "Горы"], 3, "")
+            .build().await;
+        let mut deck = Deck::new(1);
+        deck.add(2, 1);
+        deck.add(3, 1);
+        // WHEN
+        let problems = deck.validate(&db_pull, &rules(2)).await.unwrap();
+        // THEN
+        assert_eq!(problems, vec![
+            DeckProblem::WrongFaction { card_id: 3, faction: "Лес, Горы".to_string(), hero_faction: "Империя".to_string() },
+        ]);
+    }
+
+    #[test]
+    async fn validate_heroes() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let mut without_hero = Deck::default();
+        without_hero.add(4, 1);
+        let mut two_heroes = Deck::new(1);
+        two_heroes.add(5, 1);
+        let mut not_a_hero = Deck::new(4);
+        not_a_hero.add(99, 1);
+        // WHEN
+        let without_hero = without_hero.validate(&db_pull, &rules(1)).await.unwrap();
+        let two_heroes = two_heroes.validate(&db_pull, &rules(1)).await.unwrap();
+        let not_a_hero = not_a_hero.validate(&db_pull, &rules(1)).await.unwrap();
+        // THEN
+        assert_eq!(without_hero, vec![DeckProblem::NoHero]);
+        assert_eq!(two_heroes, vec![DeckProblem::ExtraHero { card_id: 5 }]);
+        assert_eq!(not_a_hero, vec![DeckProblem::NotAHero { card_id: 4 }, DeckProblem::UnknownCard { card_id: 99 }]);
+    }
+
+    #[test]
+    async fn validate_set_legality() {
+        // GIVEN
+        let db_pull = deck_fixture().await;
+        let mut deck = Deck::new(5);
+        deck.add(4, 1);
+        let rules = DeckRules { deck_size: 1, sets: Some(vec![SetCode::from(KOV)]), ..Default::default() };
+        // WHEN
+        let problems = deck.validate(&db_pull, &rules).await.unwrap();
+        // THEN
+        assert_eq!(problems, vec![DeckProblem::IllegalSet { card_id: 5, set: SetCode::from(HALL_OF_FAME) }]);
+    }
+}
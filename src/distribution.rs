@@ -0,0 +1,422 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Допустимое отклонение суммы вероятностей от 1.0
+const SUM_TOLERANCE: f64 = 1e-9;
+
+/// Распределение на индексах `0..SIZE`. Генерация за O(1) методом псевдонимов Уолкера.
+/// Сериализуется массивом вероятностей и проверяется при десериализации
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<f64>", into = "Vec<f64>")]
+pub struct Distribution<const SIZE: usize> {
+    values: [f64; SIZE],
+    table: AliasTable,
+}
+
+impl<const SIZE: usize> Distribution<SIZE> {
+    /// Вероятности от 0.0 до 1.0, сумма которых равна 1.0 с точностью до погрешности округления
+    pub fn new(values: [f64; SIZE]) -> Result<Distribution<SIZE>, Box<dyn std::error::Error>> {
+        check_probabilities(&values)?;
+        Ok(Distribution {
+            table: AliasTable::new(&normalize(&values)?),
+            values,
+        })
+    }
+
+    /// Произвольные неотрицательные веса, вероятности пропорциональны весам
+    pub fn from_weights(weights: [f64; SIZE]) -> Result<Distribution<SIZE>, Box<dyn std::error::Error>> {
+        let normalized = normalize(&weights)?;
+        let mut values = [0.0; SIZE];
+        values.copy_from_slice(&normalized);
+        Ok(Distribution {
+            table: AliasTable::new(&normalized),
+            values,
+        })
+    }
+
+    /// Вероятности исходов
+    pub fn values(&self) -> &[f64; SIZE] {
+        &self.values
+    }
+
+    /// Возвращает usize от 0 до SIZE-1 в соответствии с распределением
+    pub fn generate<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.table.sample(rng)
+    }
+}
+
+/// Распределение, размер которого известен только во время выполнения,
+/// например веса карт в пуле
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<f64>", into = "Vec<f64>")]
+pub struct DynamicDistribution {
+    values: Vec<f64>,
+    table: AliasTable,
+}
+
+impl DynamicDistribution {
+    /// Вероятности от 0.0 до 1.0, сумма которых равна 1.0 с точностью до погрешности округления
+    pub fn new(values: Vec<f64>) -> Result<DynamicDistribution, Box<dyn std::error::Error>> {
+        check_probabilities(&values)?;
+        Ok(DynamicDistribution {
+            table: AliasTable::new(&normalize(&values)?),
+            values,
+        })
+    }
+
+    /// Произвольные неотрицательные веса, вероятности пропорциональны весам
+    pub fn from_weights(weights: &[f64]) -> Result<DynamicDistribution, Box<dyn std::error::Error>> {
+        let values = normalize(weights)?;
+        Ok(DynamicDistribution {
+            table: AliasTable::new(&values),
+            values,
+        })
+    }
+
+    /// Вероятности исходов
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Возвращает usize от 0 до len()-1 в соответствии с распределением
+    pub fn generate<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.table.sample(rng)
+    }
+}
+
+impl<const SIZE: usize> TryFrom<Vec<f64>> for Distribution<SIZE> {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(values: Vec<f64>) -> Result<Self, Self::Error> {
+        let values: [f64; SIZE] = values.try_into()
+            .map_err(|v: Vec<f64>| format!("Distribution must have {} values, got {}", SIZE, v.len()))?;
+        Distribution::new(values)
+    }
+}
+
+impl<const SIZE: usize> From<Distribution<SIZE>> for Vec<f64> {
+    fn from(distribution: Distribution<SIZE>) -> Self {
+        distribution.values.to_vec()
+    }
+}
+
+impl TryFrom<Vec<f64>> for DynamicDistribution {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(values: Vec<f64>) -> Result<Self, Self::Error> {
+        DynamicDistribution::new(values)
+    }
+}
+
+impl From<DynamicDistribution> for Vec<f64> {
+    fn from(distribution: DynamicDistribution) -> Self {
+        distribution.values
+    }
+}
+
+fn check_probabilities(values: &[f64]) -> Result<(), Box<dyn std::error::Error>> {
+    if values.iter().any(|v| !(0.0..=1.0).contains(v)) {
+        return Err("Distribution values must be between 0.0 and 1.0".into());
+    }
+    if (1.0 - values.iter().sum::<f64>()).abs() > SUM_TOLERANCE {
+        return Err("Sum of distribution values must be 1.0".into());
+    }
+    Ok(())
+}
+
+fn normalize(weights: &[f64]) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    if weights.is_empty() {
+        return Err("Distribution must have at least one value".into());
+    }
+    if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+        return Err("Distribution weights must be finite and non-negative".into());
+    }
+    let sum = weights.iter().sum::<f64>();
+    if sum <= 0.0 {
+        return Err("Sum of distribution weights must be positive".into());
+    }
+    Ok(weights.iter().map(|w| w / sum).collect())
+}
+
+/// Таблица псевдонимов: каждая ячейка хранит свой индекс с вероятностью `probability`
+/// и индекс-псевдоним на остаток
+#[derive(Debug, Clone)]
+struct AliasTable {
+    probability: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Алгоритм Воуза, `values` нормированы
+    fn new(values: &[f64]) -> Self {
+        let size = values.len();
+        let mut probability = vec![0.0; size];
+        let mut alias: Vec<usize> = (0..size).collect();
+        let mut scaled: Vec<f64> = values.iter().map(|v| v * size as f64).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..size).partition(|&i| scaled[i] < 1.0);
+
+        while let (Some(&less), Some(&more)) = (small.last(), large.last()) {
+            small.pop();
+            probability[less] = scaled[less];
+            alias[less] = more;
+            scaled[more] = scaled[more] + scaled[less] - 1.0;
+            if scaled[more] < 1.0 {
+                large.pop();
+                small.push(more);
+            }
+        }
+
+        // Остатки равны 1.0 с точностью до округления. Нулевые веса не должны выпадать никогда,
+        // поэтому их ячейки целиком отдаются самому вероятному исходу
+        let most_likely = (0..size).max_by(|&a, &b| values[a].total_cmp(&values[b])).unwrap_or(0);
+        for i in small.into_iter().chain(large) {
+            if values[i] > 0.0 {
+                probability[i] = 1.0;
+            } else {
+                probability[i] = 0.0;
+                alias[i] = most_likely;
+            }
+        }
+        AliasTable { probability, alias }
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = rng.random_range(0..self.probability.len());
+        if rng.random::<f64>() < self.probability[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    #[test]
+    async fn create_distribution_when_valid_values_then_ok1() {
+        // GIVEN
+        let my_distribution = [0.2, 0.5, 0.3];
+        // WHEN
+        let result = Distribution::<3>::new(my_distribution);
+        // THEN
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    async fn create_distribution_when_valid_values_then_ok2() {
+        // GIVEN
+        let my_distribution = [1.0, 0.0, 0.0, 0.0];
+        // WHEN
+        let result = Distribution::<4>::new(my_distribution);
+        // THEN
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    async fn create_distribution_when_valid_values_then_ok3() {
+        // GIVEN
+        let my_distribution = [0.001, 0.999];
+        // WHEN
+        let result = Distribution::<2>::new(my_distribution);
+        // THEN
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    async fn create_distribution_when_wrong_sum_then_err1() {
+        // GIVEN
+        let my_distribution = [0.5, 0.5, 0.5, 0.5];
+        // WHEN
+        let result = Distribution::<4>::new(my_distribution);
+        // THEN
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn create_distribution_when_wrong_sum_then_err2() {
+        // GIVEN
+        let my_distribution = [0.2, 0.1, 0.2, 0.1];
+        // WHEN
+        let result = Distribution::<4>::new(my_distribution);
+        // THEN
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn create_distribution_when_wrong_element_then_err1() {
+        // GIVEN
+        let my_distribution = [0.2, 20.0, 0.2, 0.1];
+        // WHEN
+        let result = Distribution::<4>::new(my_distribution);
+        // THEN
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn create_distribution_when_wrong_element_then_err2() {
+        // GIVEN
+        let my_distribution = [0.1, 0.2, 0.3, -0.4];
+        // WHEN
+        let result = Distribution::<4>::new(my_distribution);
+        // THEN
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn get_values_when_valid_values_then_ok() {
+        // GIVEN
+        let my_distribution = [0.1, 0.2, 0.3, 0.4];
+        // WHEN
+        let result = Distribution::<4>::new(my_distribution);
+        let d = result.unwrap();
+        // THEN
+        assert_eq!(d.values(), &[0.1, 0.2, 0.3, 0.4]);
+    }
+
+    #[test]
+    async fn generate_with_guaranteed_distribution1() {
+        // GIVEN
+        let my_distribution = [1.0, 0.0, 0.0, 0.0, 0.0];
+        // WHEN
+        let result = Distribution::<5>::new(my_distribution);
+        let d = result.unwrap();
+        let value = d.generate(&mut rand::rng());
+        // THEN
+        assert_eq!(value, 0);
+    }
+
+    #[test]
+    async fn generate_with_guaranteed_distribution2() {
+        // GIVEN
+        let my_distribution = [0.0, 0.0, 1.0, 0.0, 0.0];
+        // WHEN
+        let result = Distribution::<5>::new(my_distribution);
+        let d = result.unwrap();
+        let value = d.generate(&mut rand::rng());
+        // THEN
+        assert_eq!(value, 2);
+    }
+
+    #[test]
+    async fn generate_with_guaranteed_distribution3() {
+        // GIVEN
+        let my_distribution = [0.0, 0.0, 0.0, 0.0, 1.0];
+        // WHEN
+        let result = Distribution::<5>::new(my_distribution);
+        let d = result.unwrap();
+        let value = d.generate(&mut rand::rng());
+        // THEN
+        assert_eq!(value, 4);
+    }
+
+    #[test]
+    async fn generate_with_random_distribution() {
+        // GIVEN
+        let my_distribution = [0.2, 0.2, 0.2, 0.2, 0.2];
+        // WHEN
+        let result = Distribution::<5>::new(my_distribution);
+        let d = result.unwrap();
+        let value = d.generate(&mut rand::rng());
+        // THEN
+        assert!(value <= 4);
+    }
+
+
+    #[test]
+    async fn create_distribution_with_rounding_error_then_ok() {
+        // GIVEN
+        let my_distribution = [0.1; 10];
+        // WHEN
+        let result = Distribution::<10>::new(my_distribution);
+        // THEN
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    async fn create_distribution_from_weights_then_normalized() {
+        // GIVEN
+        let weights = [2.0, 1.0, 1.0, 0.0];
+        // WHEN
+        let d = Distribution::<4>::from_weights(weights).unwrap();
+        // THEN
+        assert_eq!(d.values(), &[0.5, 0.25, 0.25, 0.0]);
+    }
+
+    #[test]
+    async fn create_distribution_from_weights_when_negative_then_err() {
+        let result = Distribution::<3>::from_weights([1.0, -1.0, 1.0]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn create_distribution_from_weights_when_all_zero_then_err() {
+        let result = Distribution::<3>::from_weights([0.0, 0.0, 0.0]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn create_distribution_from_weights_when_not_finite_then_err() {
+        assert!(Distribution::<2>::from_weights([f64::NAN, 1.0]).is_err());
+        assert!(Distribution::<2>::from_weights([f64::INFINITY, 1.0]).is_err());
+    }
+
+    #[test]
+    async fn create_dynamic_distribution_from_weights() {
+        // GIVEN
+        let weights = vec![3.0, 1.0];
+        // WHEN
+        let d = DynamicDistribution::from_weights(&weights).unwrap();
+        // THEN
+        assert_eq!(d.len(), 2);
+        assert_eq!(d.values(), &[0.75, 0.25]);
+    }
+
+    #[test]
+    async fn create_dynamic_distribution_when_empty_then_err() {
+        assert!(DynamicDistribution::from_weights(&[]).is_err());
+        assert!(DynamicDistribution::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    async fn generate_dynamic_with_zero_weights_never_returns_them() {
+        // GIVEN
+        let weights: Vec<f64> = (0..100).map(|i| if i % 3 == 0 { 0.0 } else { i as f64 }).collect();
+        let d = DynamicDistribution::from_weights(&weights).unwrap();
+        let mut rng = rand::rng();
+        // WHEN
+        let values: Vec<usize> = (0..10000).map(|_| d.generate(&mut rng)).collect();
+        // THEN
+        assert!(values.iter().all(|&v| v % 3 != 0));
+    }
+
+    #[test]
+    async fn distribution_json_round_trip() {
+        // GIVEN
+        let distribution = Distribution::new([0.7, 0.2, 0.1]).unwrap();
+        // WHEN
+        let json = serde_json::to_string(&distribution).unwrap();
+        let restored: Distribution<3> = serde_json::from_str(&json).unwrap();
+        // THEN
+        assert_eq!(json, "[0.7,0.2,0.1]");
+        assert_eq!(restored.values(), distribution.values());
+    }
+
+    #[test]
+    async fn deserialize_distribution_when_invalid_then_err() {
+        assert!(serde_json::from_str::<Distribution<2>>("[0.7, 0.2]").is_err());
+        assert!(serde_json::from_str::<Distribution<2>>("[0.7, 0.2, 0.1]").is_err());
+        assert!(serde_json::from_str::<Distribution<2>>("[1.5, -0.5]").is_err());
+        assert!(serde_json::from_str::<DynamicDistribution>("[]").is_err());
+    }
+}
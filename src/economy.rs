@@ -0,0 +1,314 @@
+//! Модель цифровой экономики: бустеры покупаются за валюту,
+//! копии сверх плейсета распыляются в пыль, а недостающие карты создаются из пыли.
+//! Симуляция считает, сколько игрок потратит на сбор полного сета
+
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CardRarity,
+    collection::Collection,
+    simulation::{OpenedBuster, PoolSnapshot, block_seed, run_blocks},
+};
+
+/// Значение для каждой редкости
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RarityValues<T> {
+    pub bronze: T,
+    pub silver: T,
+    pub gold: T,
+}
+
+impl<T: Copy> RarityValues<T> {
+    pub fn get(&self, rarity: CardRarity) -> T {
+        match rarity {
+            CardRarity::Bronze => self.bronze,
+            CardRarity::Silver => self.silver,
+            CardRarity::Gold => self.gold,
+        }
+    }
+}
+
+/// Цены и курсы пыли
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EconomyRules {
+    /// Цена одного бустера в валюте
+    pub pack_price: f64,
+    /// Сколько копий карты нужно игроку, остальные распыляются
+    pub playset: RarityValues<u32>,
+    /// Пыль за распыление одной копии
+    pub disenchant: RarityValues<u64>,
+    /// Пыль на создание одной копии
+    pub craft: RarityValues<u64>,
+    /// Игрок перестает покупать бустеры после стольких бустеров, даже если сет не собран
+    pub max_packs: u64,
+}
+
+impl Default for EconomyRules {
+    fn default() -> Self {
+        EconomyRules {
+            pack_price: 100.0,
+            playset: RarityValues { bronze: 3, silver: 3, gold: 3 },
+            disenchant: RarityValues { bronze: 5, silver: 20, gold: 100 },
+            craft: RarityValues { bronze: 40, silver: 100, gold: 400 },
+            max_packs: 100_000,
+        }
+    }
+}
+
+/// Итог одного игрока
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerResult {
+    pub packs: u64,
+    /// Потрачено валюты на бустеры
+    pub spent: f64,
+    /// Получено пыли распылением
+    pub dust_earned: u64,
+    /// Создано копий из пыли
+    pub crafted: u32,
+    /// `false`, если игрок дошел до `max_packs`, не собрав сет
+    pub completed: bool,
+}
+
+/// Сводка распределения величины по игрокам
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CostDistribution {
+    pub mean: f64,
+    pub min: f64,
+    pub p10: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl CostDistribution {
+    /// `None` для пустой выборки
+    pub fn new(values: impl IntoIterator<Item = f64>) -> Option<Self> {
+        let mut values: Vec<f64> = values.into_iter().collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        // Процентиль по ближайшему рангу
+        let percentile = |p: f64| values[((p * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1];
+        Some(CostDistribution {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            min: values[0],
+            p10: percentile(0.1),
+            median: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+/// Результаты всех игроков в порядке номеров игроков
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EconomyReport {
+    pub players: Vec<PlayerResult>,
+}
+
+impl EconomyReport {
+    /// Распределение потраченной валюты
+    pub fn spent(&self) -> Option<CostDistribution> {
+        CostDistribution::new(self.players.iter().map(|p| p.spent))
+    }
+
+    pub fn packs(&self) -> Option<CostDistribution> {
+        CostDistribution::new(self.players.iter().map(|p| p.packs as f64))
+    }
+
+    pub fn crafted(&self) -> Option<CostDistribution> {
+        CostDistribution::new(self.players.iter().map(|p| f64::from(p.crafted)))
+    }
+
+    /// Доля игроков, собравших сет, `None` без игроков
+    pub fn completion_rate(&self) -> Option<f64> {
+        if self.players.is_empty() {
+            return None;
+        }
+        Some(self.players.iter().filter(|p| p.completed).count() as f64 / self.players.len() as f64)
+    }
+}
+
+/// Симулирует `players` игроков, собирающих основной сет снимка.
+/// Игрок открывает бустеры и распыляет копии сверх плейсета, пока пыли не хватит
+/// на все недостающие копии, затем создает их. Карты Зала Славы в сет не входят и
+/// тоже распыляются сверх плейсета. При одинаковом зерне результат не зависит от числа потоков
+pub fn simulate_economy(pool: &PoolSnapshot, rules: &EconomyRules, players: usize, seed: u64, threads: usize) -> EconomyReport {
+    let players = run_blocks(players, threads, |player| {
+        simulate_player(pool, rules, &mut StdRng::seed_from_u64(block_seed(seed, player)))
+    });
+    EconomyReport { players }
+}
+
+pub(crate) fn simulate_player(pool: &PoolSnapshot, rules: &EconomyRules, rng: &mut StdRng) -> PlayerResult {
+    let mut collection = Collection::new(pool);
+    let mut buster = OpenedBuster::default();
+    // Пыль на создание всех недостающих копий сета. Правила задает пользователь, поэтому
+    // суммы считаются в более широких типах, чтобы огромные значения не переполняли их
+    let mut missing_cost: u128 = pool.cards().iter()
+        .filter(|card| card.set.id == pool.set())
+        .map(|card| u128::from(rules.playset.get(card.rarity)) * u128::from(rules.craft.get(card.rarity)))
+        .sum();
+    let mut missing_copies: u64 = pool.cards().iter()
+        .filter(|card| card.set.id == pool.set())
+        .map(|card| u64::from(rules.playset.get(card.rarity)))
+        .sum();
+    let mut dust: u128 = 0;
+    let mut packs = 0;
+    while dust < missing_cost && packs < rules.max_packs {
+        pool.generate_into(rng, &mut buster);
+        packs += 1;
+        for &index in buster.cards.iter() {
+            let card = pool.card(index);
+            if collection.count(index) < rules.playset.get(card.rarity) {
+                collection.add(index, 1);
+                if card.set.id == pool.set() {
+                    missing_cost -= u128::from(rules.craft.get(card.rarity));
+                    missing_copies -= 1;
+                }
+            } else {
+                dust = dust.saturating_add(u128::from(rules.disenchant.get(card.rarity)));
+            }
+        }
+    }
+    let completed = dust >= missing_cost;
+    PlayerResult {
+        packs,
+        spent: packs as f64 * rules.pack_price,
+        dust_earned: u64::try_from(dust).unwrap_or(u64::MAX),
+        crafted: if completed { u32::try_from(missing_copies).unwrap_or(u32::MAX) } else { 0 },
+        completed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture;
+
+    fn without_crafting() -> EconomyRules {
+        let unreachable = RarityValues { bronze: u64::MAX / 1000, silver: u64::MAX / 1000, gold: u64::MAX / 1000 };
+        EconomyRules { craft: unreachable, ..Default::default() }
+    }
+
+    #[test]
+    async fn simulate_economy_with_same_seed_then_same_result_for_any_threads() {
+        // GIVEN
+        let pool = fixture::snapshot(0.02).await;
+        let rules = EconomyRules::default();
+        // WHEN
+        let single = simulate_economy(&pool, &rules, 50, 3, 1);
+        let parallel = simulate_economy(&pool, &rules, 50, 3, 4);
+        // THEN
+        assert_eq!(single, parallel);
+        assert_eq!(single.players.len(), 50);
+        assert_eq!(single.completion_rate(), Some(1.0));
+    }
+
+    #[test]
+    async fn without_crafting_then_collect_everything_from_packs() {
+        // GIVEN
+        let pool = fixture::snapshot(0.02).await;
+        // WHEN
+        let report = simulate_economy(&pool, &without_crafting(), 20, 1, 2);
+        // THEN
+        // В сете 16 карт по 3 копии, в бустере 18 карт
+        assert!(report.players.iter().all(|p| p.completed && p.crafted == 0 && p.packs >= 3));
+        assert!(report.players.iter().all(|p| p.spent == p.packs as f64 * 100.0));
+    }
+
+    #[test]
+    async fn free_crafting_then_no_packs() {
+        let pool = fixture::snapshot(0.02).await;
+        let rules = EconomyRules { craft: RarityValues { bronze: 0, silver: 0, gold: 0 }, ..Default::default() };
+        let report = simulate_economy(&pool, &rules, 5, 1, 1);
+        assert!(report.players.iter().all(|p| p.packs == 0 && p.crafted == 16 * 3 && p.completed));
+        assert_eq!(report.spent().unwrap().max, 0.0);
+    }
+
+    #[test]
+    async fn crafting_saves_packs() {
+        // GIVEN
+        let pool = fixture::snapshot(0.02).await;
+        let cheap = EconomyRules {
+            disenchant: RarityValues { bronze: 50, silver: 50, gold: 50 },
+            craft: RarityValues { bronze: 100, silver: 100, gold: 100 },
+            ..Default::default()
+        };
+        // WHEN
+        let with_crafting = simulate_economy(&pool, &cheap, 200, 5, 4);
+        let without = simulate_economy(&pool, &without_crafting(), 200, 5, 4);
+        // THEN
+        assert!(with_crafting.packs().unwrap().mean < without.packs().unwrap().mean);
+        assert!(with_crafting.crafted().unwrap().mean > 0.0);
+        assert!(with_crafting.players.iter().all(|p| p.dust_earned >= u64::from(p.crafted) * 100));
+    }
+
+    #[test]
+    async fn max_packs_stops_player() {
+        let pool = fixture::snapshot(0.02).await;
+        let rules = EconomyRules { max_packs: 1, ..without_crafting() };
+        let report = simulate_economy(&pool, &rules, 10, 1, 2);
+        assert_eq!(report.completion_rate(), Some(0.0));
+        assert!(report.players.iter().all(|p| p.packs == 1 && p.crafted == 0));
+    }
+
+    #[test]
+    async fn huge_rules_then_no_overflow() {
+        // GIVEN
+        let pool = fixture::snapshot(0.02).await;
+        let rules = EconomyRules {
+            playset: RarityValues { bronze: u32::MAX, silver: u32::MAX, gold: u32::MAX },
+            disenchant: RarityValues { bronze: u64::MAX, silver: u64::MAX, gold: u64::MAX },
+            craft: RarityValues { bronze: u64::MAX, silver: u64::MAX, gold: u64::MAX },
+            max_packs: 2,
+            ..Default::default()
+        };
+        // WHEN
+        let report = simulate_economy(&pool, &rules, 3, 1, 1);
+        // THEN
+        assert_eq!(report.completion_rate(), Some(0.0));
+        assert!(report.players.iter().all(|p| p.packs == 2 && p.crafted == 0));
+    }
+
+    #[test]
+    async fn empty_report_then_no_completion_rate() {
+        assert_eq!(EconomyReport { players: vec![] }.completion_rate(), None);
+    }
+
+    #[test]
+    async fn cost_distribution_percentiles() {
+        // WHEN
+        let distribution = CostDistribution::new((1..=100).map(f64::from)).unwrap();
+        // THEN
+        assert_eq!(distribution.mean, 50.5);
+        assert_eq!(distribution.min, 1.0);
+        assert_eq!(distribution.p10, 10.0);
+        assert_eq!(distribution.median, 50.0);
+        assert_eq!(distribution.p90, 90.0);
+        assert_eq!(distribution.p99, 99.0);
+        assert_eq!(distribution.max, 100.0);
+        assert!(CostDistribution::new([]).is_none());
+    }
+
+    #[test]
+    async fn economy_rules_deserialize_with_defaults() {
+        // WHEN
+        let rules: EconomyRules = serde_json::from_value(serde_json::json!({
+            "pack_price": 150.0,
+            "craft": { "bronze": 30, "silver": 90, "gold": 300 },
+        })).unwrap();
+        // THEN
+        assert_eq!(rules.pack_price, 150.0);
+        assert_eq!(rules.craft.get(CardRarity::Gold), 300);
+        assert_eq!(rules.disenchant, EconomyRules::default().disenchant);
+    }
+}
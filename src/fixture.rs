@@ -0,0 +1,181 @@
+//! Тестовая БД в памяти с небольшим синтетическим набором карт
+
+use sqlx::SqlitePool;
+
+use crate::{BusterRules, CardBusterSlot, CardPool, CardRarity, DEFAULT_CARD_WEIGHT, Distribution, PoolSnapshot, SetCode, db};
+
+pub(crate) const KOV: &str = "КОВ";
+pub(crate) const HALL_OF_FAME: &str = "Зал Славы";
+
+struct FixtureCard {
+    set: &'static str,
+    slot: CardBusterSlot,
+    rarity: CardRarity,
+    has_image: bool,
+    weight: f64,
+    details: Option<FixtureDetails>,
+}
+
+impl FixtureCard {
+    fn name(&self, id_in_set: i64) -> String {
+        self.details.as_ref().map_or_else(|| format!("{} {}", self.set, id_in_set), |d| d.name.to_string())
+    }
+}
+
+/// Название, фракции, стоимость и текст правил вместо сгенерированного имени
+struct FixtureDetails {
+    name: &'static str,
+    factions: &'static [&'static str],
+    cost: i64,
+    text: &'static str,
+}
+
+#[derive(Default)]
+pub(crate) struct FixtureBuilder {
+    cards: Vec<FixtureCard>,
+}
+
+impl FixtureBuilder {
+    /// Пустая БД: только справочники и сеты из миграций
+    pub(crate) fn new() -> Self {
+        FixtureBuilder::default()
+    }
+
+    /// Стандартный набор:
+    /// - в КОВ по две карты в каждом пуле (слот, редкость), который нужен бустеру;
+    /// - в Зале Славы только золотые герои и основные карты, приказов и серебра нет
+    pub(crate) fn standard() -> Self {
+        let mut builder = FixtureBuilder::new();
+        for (slot, rarity) in [
+            (CardBusterSlot::Hero, CardRarity::Silver),
+            (CardBusterSlot::Hero, CardRarity::Gold),
+            (CardBusterSlot::Command, CardRarity::Bronze),
+            (CardBusterSlot::Command, CardRarity::Silver),
+            (CardBusterSlot::Command, CardRarity::Gold),
+            (CardBusterSlot::BasicCard, CardRarity::Bronze),
+            (CardBusterSlot::BasicCard, CardRarity::Silver),
+            (CardBusterSlot::BasicCard, CardRarity::Gold),
+        ] {
+            builder = builder.cards(KOV, slot, rarity, 2);
+        }
+        builder
+            .cards(HALL_OF_FAME, CardBusterSlot::Hero, CardRarity::Gold, 2)
+            .cards(HALL_OF_FAME, CardBusterSlot::BasicCard, CardRarity::Gold, 1)
+    }
+
+    pub(crate) fn cards(mut self, set: &'static str, slot: CardBusterSlot, rarity: CardRarity, count: usize) -> Self {
+        for _ in 0..count {
+            self.cards.push(FixtureCard { set, slot, rarity, has_image: true, weight: DEFAULT_CARD_WEIGHT, details: None });
+        }
+        self
+    }
+
+    /// Убирает все карты пула, чтобы проверить поведение при пустом пуле
+    pub(crate) fn without(mut self, set: &'static str, slot: CardBusterSlot, rarity: CardRarity) -> Self {
+        self.cards.retain(|c| !(c.set == set && c.slot == slot && c.rarity == rarity));
+        self
+    }
+
+    pub(crate) fn card_without_image(mut self, set: &'static str, slot: CardBusterSlot, rarity: CardRarity) -> Self {
+        self.cards.push(FixtureCard { set, slot, rarity, has_image: false, weight: DEFAULT_CARD_WEIGHT, details: None });
+        self
+    }
+
+    /// Карта с заданным весом в пуле
+    pub(crate) fn weighted_card(mut self, set: &'static str, slot: CardBusterSlot, rarity: CardRarity, weight: f64) -> Self {
+        self.cards.push(FixtureCard { set, slot, rarity, has_image: true, weight, details: None });
+        self
+    }
+
+    /// Карта с названием, фракциями, стоимостью и текстом правил для поиска
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn described_card(
+        mut self,
+        set: &'static str,
+        slot: CardBusterSlot,
+        rarity: CardRarity,
+        name: &'static str,
+        factions: &'static [&'static str],
+        cost: i64,
+        text: &'static str,
+    ) -> Self {
+        let details = Some(FixtureDetails { name, factions, cost, text });
+        self.cards.push(FixtureCard { set, slot, rarity, has_image: true, weight: DEFAULT_CARD_WEIGHT, details });
+        self
+    }
+
+    /// Название последней добавленной карты в БД после [`FixtureBuilder::build`]
+    pub(crate) fn last_card_name(&self) -> String {
+        let card = self.cards.last().expect("fixture has no cards");
+        let id_in_set = self.cards.iter().filter(|c| c.set == card.set).count() as i64;
+        card.name(id_in_set)
+    }
+
+    /// Карты получают номера в сете по порядку добавления и, если название не задано, имена вида `КОВ 3`
+    pub(crate) async fn build(self) -> SqlitePool {
+        let db_pull = db::open_url("sqlite::memory:").await.unwrap();
+        let mut next_id_in_set = std::collections::HashMap::new();
+        for card in self.cards.iter() {
+            let id_in_set: &mut i64 = next_id_in_set.entry(card.set).or_insert(0);
+            *id_in_set += 1;
+            let name = card.name(*id_in_set);
+            let cost = card.details.as_ref().map(|d| d.cost);
+            let text = card.details.as_ref().map(|d| d.text);
+            let image_url = card.has_image.then(|| format!("https://response-world.ru/storage/cards/{}.webp", name));
+            let rarity = card.rarity.db_name();
+            let buster_slot = card.slot.db_name();
+            sqlx::query!(
+                "INSERT INTO cards (name, rarity_id, type_id, set_id, id_in_set, image_url, weight, cost, text)
+                VALUES (
+                    ?,
+                    (SELECT id FROM rarities WHERE name = ?),
+                    (SELECT id FROM types WHERE name = ?),
+                    (SELECT id FROM sets WHERE short_name = ?),
+                    ?,
+                    ?,
+                    ?,
+                    ?,
+                    ?
+                )",
+                name,
+                rarity,
+                buster_slot,
+                card.set,
+                *id_in_set,
+                image_url,
+                card.weight,
+                cost,
+                text,
+            ).execute(&db_pull).await.unwrap();
+            for faction in card.details.iter().flat_map(|d| d.factions) {
+                sqlx::query!(
+                    "INSERT INTO card_factions (card_id, faction) VALUES ((SELECT MAX(id) FROM cards), ?)",
+                    faction,
+                ).execute(&db_pull).await.unwrap();
+            }
+        }
+        db_pull
+    }
+}
+
+/// БД со стандартным набором карт
+pub(crate) async fn standard() -> SqlitePool {
+    FixtureBuilder::standard().build().await
+}
+
+/// Правила бустера КОВ для тестов с заданным шансом Зала Славы
+pub(crate) fn rules(hall_of_fame_chanse: f64) -> BusterRules {
+    BusterRules::new(
+        SetCode::from(KOV),
+        hall_of_fame_chanse,
+        Distribution::new([0.8, 0.2]).unwrap(),
+        Distribution::new([0.75, 0.25]).unwrap(),
+        Distribution::new([0.7, 0.2, 0.1]).unwrap(),
+        Distribution::new([0.7, 0.3]).unwrap(),
+    ).unwrap()
+}
+
+/// Снимок стандартного набора карт по правилам [`rules`]
+pub(crate) async fn snapshot(hall_of_fame_chanse: f64) -> PoolSnapshot {
+    CardPool::load(&standard().await).await.unwrap().snapshot(&rules(hall_of_fame_chanse)).unwrap()
+}
@@ -0,0 +1,406 @@
+//! Форматы игры: какие сеты легальны на дату и бан-лист.
+//! Сет легален, если он вышел к этой дате, входит в список сетов формата (если список задан)
+//! и не ушел в ротацию. Статусы карт в бан-листе версионируются датой начала действия
+
+use std::{collections::HashMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    Card, SetCode, SetId, SetInfo, SetRegistry,
+    deck::{Deck, DeckProblem, DeckRules},
+    presets::check_date,
+    query,
+};
+
+/// Формат из таблицы `formats`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Format {
+    pub id: i64,
+    pub name: String,
+    /// Сеты, вышедшие больше стольких месяцев назад, не легальны. `None` — без ротации
+    pub rotation_months: Option<u32>,
+    /// Сеты формата. `None` — все сеты
+    pub sets: Option<Vec<SetCode>>,
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BanStatus {
+    #[default]
+    Legal,
+    Banned,
+    /// В колоде не больше одной копии
+    Restricted,
+}
+
+impl BanStatus {
+    /// Значение в таблице `format_bans`
+    pub fn key(&self) -> &'static str {
+        match self {
+            BanStatus::Legal => "legal",
+            BanStatus::Banned => "banned",
+            BanStatus::Restricted => "restricted",
+        }
+    }
+}
+
+impl TryFrom<String> for BanStatus {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "legal" => Ok(BanStatus::Legal),
+            "banned" => Ok(BanStatus::Banned),
+            "restricted" => Ok(BanStatus::Restricted),
+            s => Err(format!("Error. Not found ban status: {}", s).into()),
+        }
+    }
+}
+
+impl Format {
+    /// Легален ли сет на дату `date` в формате `YYYY-MM-DD`
+    pub fn is_set_legal(&self, set: &SetInfo, date: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        check_date(date)?;
+        let released = set.release_date.as_str() <= date;
+        let listed = self.sets.as_ref().is_none_or(|sets| sets.contains(&set.code()));
+        let rotated = self.rotation_months
+            .is_some_and(|months| set.release_date <= months_before(date, months));
+        Ok(released && listed && !rotated)
+    }
+
+    /// Легальные сеты и бан-лист формата на дату `date`
+    pub async fn legality(&self, db_pull: &SqlitePool, date: &str) -> Result<FormatLegality, Box<dyn std::error::Error>> {
+        check_date(date)?;
+        let mut sets = Vec::new();
+        for set in SetRegistry::load(db_pull).await?.iter() {
+            if self.is_set_legal(set, date)? {
+                sets.push(set.code());
+            }
+        }
+        let rows = sqlx::query!(
+            "SELECT card_id, set_id, cards.name as name, status
+            FROM format_bans
+            INNER JOIN cards ON cards.id = format_bans.card_id
+            WHERE format_id = ? AND effective_from = (
+                SELECT MAX(effective_from) FROM format_bans AS latest
+                WHERE latest.format_id = format_bans.format_id
+                    AND latest.card_id = format_bans.card_id
+                    AND latest.effective_from <= ?
+            )",
+            self.id,
+            date,
+            ).fetch_all(db_pull)
+            .await?;
+        let mut bans = HashMap::new();
+        let mut card_ids = HashMap::new();
+        for rec in rows {
+            bans.insert(rec.card_id, BanStatus::try_from(rec.status)?);
+            card_ids.insert((SetId(rec.set_id), rec.name), rec.card_id);
+        }
+        Ok(FormatLegality { format: self.clone(), date: date.to_string(), sets, bans, card_ids })
+    }
+}
+
+/// Состояние формата на дату: легальные сеты и статусы карт из бан-листа
+#[derive(Debug, Clone)]
+pub struct FormatLegality {
+    pub format: Format,
+    pub date: String,
+    pub sets: Vec<SetCode>,
+    /// Статусы по идентификатору карты в БД
+    bans: HashMap<i64, BanStatus>,
+    /// Идентификаторы карт бан-листа по сету и названию, чтобы проверять и карты из бустеров без идентификатора в БД.
+    /// Номер в сете для этого не подходит: он бывает у нескольких карт
+    card_ids: HashMap<(SetId, String), i64>,
+}
+
+impl FormatLegality {
+    pub fn ban_status(&self, card: &Card) -> BanStatus {
+        self.card_ids.get(&(card.set.id, card.name.clone()))
+            .map_or(BanStatus::Legal, |&card_id| self.ban_status_by_id(card_id))
+    }
+
+    /// Статус карты с идентификатором `card_id` в БД
+    pub fn ban_status_by_id(&self, card_id: i64) -> BanStatus {
+        self.bans.get(&card_id).copied().unwrap_or_default()
+    }
+
+    /// Карта из легального сета и не забанена. Ограниченные карты легальны
+    pub fn is_legal(&self, card: &Card) -> bool {
+        self.sets.contains(&card.set.code()) && self.ban_status(card) != BanStatus::Banned
+    }
+
+    /// Нелегальные карты пула, например силед-пула из открытых бустеров
+    pub fn illegal_cards<'a>(&self, cards: &'a [Card]) -> Vec<&'a Card> {
+        cards.iter().filter(|card| !self.is_legal(card)).collect()
+    }
+
+    /// Проверяет колоду по правилам построения, легальным сетам формата и бан-листу
+    pub fn check_deck(&self, deck: &Deck, cards: &[query::FoundCard], rules: &DeckRules) -> Vec<DeckProblem> {
+        let rules = DeckRules { sets: Some(self.sets.clone()), ..rules.clone() };
+        let mut problems = deck.check(cards, &rules);
+        for found in cards.iter() {
+            let count = if deck.hero == Some(found.id) { 1 } else { deck.cards.get(&found.id).copied().unwrap_or(0) };
+            match self.ban_status_by_id(found.id) {
+                BanStatus::Banned if count > 0 => problems.push(DeckProblem::Banned { card_id: found.id }),
+                BanStatus::Restricted if count > 1 => problems.push(DeckProblem::Restricted { card_id: found.id, count }),
+                _ => {}
+            }
+        }
+        problems
+    }
+}
+
+/// Формат по имени
+pub async fn load_format(db_pull: &SqlitePool, name: &str) -> Result<Format, Box<dyn std::error::Error>> {
+    let row = sqlx::query!("SELECT id as \"id!\", rotation_months FROM formats WHERE name = ?", name)
+        .fetch_optional(db_pull)
+        .await?
+        .ok_or_else(|| format!("Error. Not found format: {}", name))?;
+    format_from_row(db_pull, row.id, name.to_string(), row.rotation_months).await
+}
+
+pub async fn list_formats(db_pull: &SqlitePool) -> Result<Vec<Format>, Box<dyn std::error::Error>> {
+    let rows = sqlx::query!("SELECT id as \"id!\", name, rotation_months FROM formats ORDER BY id")
+        .fetch_all(db_pull)
+        .await?;
+    let mut formats = Vec::new();
+    for row in rows {
+        formats.push(format_from_row(db_pull, row.id, row.name, row.rotation_months).await?);
+    }
+    Ok(formats)
+}
+
+async fn format_from_row(db_pull: &SqlitePool, id: i64, name: String, rotation_months: Option<i64>) -> Result<Format, Box<dyn std::error::Error>> {
+    let sets: Vec<SetCode> = sqlx::query!(
+        "SELECT short_name FROM format_sets
+        INNER JOIN sets ON sets.id = format_sets.set_id
+        WHERE format_id = ?
+        ORDER BY set_id",
+        id,
+        ).fetch_all(db_pull)
+        .await?
+        .into_iter()
+        .map(|rec| SetCode(rec.short_name))
+        .collect();
+    Ok(Format {
+        id,
+        name,
+        rotation_months: rotation_months.map(u32::try_from).transpose()?,
+        sets: (!sets.is_empty()).then_some(sets),
+    })
+}
+
+/// Меняет статус карты в бан-листе формата с даты `effective_from`.
+/// `BanStatus::Legal` снимает бан или ограничение
+pub async fn set_ban_status(db_pull: &SqlitePool, format: &Format, card_id: i64, status: BanStatus, effective_from: &str) -> Result<(), Box<dyn std::error::Error>> {
+    check_date(effective_from)?;
+    let status = status.key();
+    sqlx::query!(
+        "INSERT INTO format_bans (format_id, card_id, status, effective_from) VALUES (?, ?, ?, ?)
+        ON CONFLICT (format_id, card_id, effective_from) DO UPDATE SET status = excluded.status",
+        format.id,
+        card_id,
+        status,
+        effective_from,
+        ).execute(db_pull)
+        .await?;
+    Ok(())
+}
+
+/// Проверяет колоду в формате на дату `date`
+pub async fn validate_deck(db_pull: &SqlitePool, deck: &Deck, rules: &DeckRules, format: &Format, date: &str) -> Result<Vec<DeckProblem>, Box<dyn std::error::Error>> {
+    let legality = format.legality(db_pull, date).await?;
+    let cards = query::cards_by_id(db_pull, &deck.card_ids()).await?;
+    Ok(legality.check_deck(deck, &cards, rules))
+}
+
+/// Дата на `months` месяцев раньше. День не меняется, поэтому результат
+/// может быть несуществующей датой вроде `2024-02-31`, для сравнения строк это не важно
+fn months_before(date: &str, months: u32) -> String {
+    let year: i64 = date[..4].parse().unwrap_or_default();
+    let month: i64 = date[5..7].parse().unwrap_or_default();
+    let total = year * 12 + (month - 1) - i64::from(months);
+    format!("{:04}-{:02}{}", total.div_euclid(12), total.rem_euclid(12) + 1, &date[7..])
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::{CardBusterSlot, CardPool, CardRarity, fixture::{FixtureBuilder, HALL_OF_FAME, KOV}};
+
+    const KOV_ID: SetId = SetId(2);
+
+    /// Все сеты из миграций вышли 2025-10-21. Карты: 1 — герой КОВ, 2 и 3 — бронза КОВ, 4 — герой Зала Славы
+    async fn format_fixture() -> SqlitePool {
+        FixtureBuilder::new()
+            .cards(KOV, CardBusterSlot::Hero, CardRarity::Silver, 1)
+            .cards(KOV, CardBusterSlot::BasicCard, CardRarity::Bronze, 2)
+            .cards(HALL_OF_FAME, CardBusterSlot::Hero, CardRarity::Gold, 1)
+            .build().await
+    }
+
+    fn deck() -> Deck {
+        let mut deck = Deck::new(1);
+        deck.add(2, 2);
+        deck.add(3, 1);
+        deck
+    }
+
+    fn rules() -> DeckRules {
+        DeckRules { deck_size: 3, ..Default::default() }
+    }
+
+    #[test]
+    async fn months_before_crosses_year() {
+        assert_eq!(months_before("2027-10-21", 24), "2025-10-21");
+        assert_eq!(months_before("2026-01-31", 2), "2025-11-31");
+    }
+
+    #[test]
+    async fn is_set_legal_with_invalid_date_then_error() {
+        // GIVEN
+        let db_pull = format_fixture().await;
+        let standard = load_format(&db_pull, "standard").await.unwrap();
+        let sets = SetRegistry::load(&db_pull).await.unwrap();
+        let kov = sets.get(KOV_ID).unwrap();
+        // THEN
+        assert!(standard.is_set_legal(kov, "2026-01-01").unwrap());
+        for date in ["", "2026", "2026-1-01", "01.01.2026"] {
+            assert!(standard.is_set_legal(kov, date).is_err());
+        }
+    }
+
+    #[test]
+    async fn standard_rotates_sets() {
+        // GIVEN
+        let db_pull = format_fixture().await;
+        let standard = load_format(&db_pull, "standard").await.unwrap();
+        // WHEN
+        let before_release = standard.legality(&db_pull, "2025-10-20").await.unwrap();
+        let last_day = standard.legality(&db_pull, "2027-10-20").await.unwrap();
+        let rotated = standard.legality(&db_pull, "2027-10-21").await.unwrap();
+        // THEN
+        assert!(before_release.sets.is_empty());
+        assert!(last_day.sets.contains(&SetCode::from(KOV)));
+        assert!(rotated.sets.is_empty());
+    }
+
+    #[test]
+    async fn eternal_without_rotation() {
+        let db_pull = format_fixture().await;
+        let eternal = load_format(&db_pull, "eternal").await.unwrap();
+        let legality = eternal.legality(&db_pull, "2040-01-01").await.unwrap();
+        assert_eq!(legality.sets, vec![SetCode::from("БАЗ"), SetCode::from(KOV), SetCode::from(HALL_OF_FAME)]);
+    }
+
+    #[test]
+    async fn format_with_set_list() {
+        // GIVEN
+        let db_pull = format_fixture().await;
+        sqlx::query!("INSERT INTO formats (id, name) VALUES (10, 'kov_only')").execute(&db_pull).await.unwrap();
+        sqlx::query!("INSERT INTO format_sets (format_id, set_id) VALUES (10, 2)").execute(&db_pull).await.unwrap();
+        // WHEN
+        let format = load_format(&db_pull, "kov_only").await.unwrap();
+        let legality = format.legality(&db_pull, "2026-01-01").await.unwrap();
+        // THEN
+        assert_eq!(format.sets, Some(vec![SetCode::from(KOV)]));
+        assert_eq!(legality.sets, vec![SetCode::from(KOV)]);
+        assert_eq!(list_formats(&db_pull).await.unwrap().len(), 3);
+    }
+
+    #[test]
+    async fn ban_list_by_date() {
+        // GIVEN
+        let db_pull = format_fixture().await;
+        let standard = load_format(&db_pull, "standard").await.unwrap();
+        set_ban_status(&db_pull, &standard, 2, BanStatus::Banned, "2026-01-01").await.unwrap();
+        set_ban_status(&db_pull, &standard, 2, BanStatus::Legal, "2026-06-01").await.unwrap();
+        // WHEN
+        let before = validate_deck(&db_pull, &deck(), &rules(), &standard, "2025-12-31").await.unwrap();
+        let banned = validate_deck(&db_pull, &deck(), &rules(), &standard, "2026-01-01").await.unwrap();
+        let unbanned = validate_deck(&db_pull, &deck(), &rules(), &standard, "2026-06-01").await.unwrap();
+        // THEN
+        assert_eq!(before, vec![]);
+        assert_eq!(banned, vec![DeckProblem::Banned { card_id: 2 }]);
+        assert_eq!(unbanned, vec![]);
+    }
+
+    #[test]
+    async fn restricted_card_allows_one_copy() {
+        // GIVEN
+        let db_pull = format_fixture().await;
+        let eternal = load_format(&db_pull, "eternal").await.unwrap();
+        set_ban_status(&db_pull, &eternal, 2, BanStatus::Restricted, "2026-01-01").await.unwrap();
+        set_ban_status(&db_pull, &eternal, 3, BanStatus::Restricted, "2026-01-01").await.unwrap();
+        // WHEN
+        let problems = validate_deck(&db_pull, &deck(), &rules(), &eternal, "2026-02-01").await.unwrap();
+        // THEN
+        assert_eq!(problems, vec![DeckProblem::Restricted { card_id: 2, count: 2 }]);
+    }
+
+    #[test]
+    async fn deck_with_rotated_set_then_illegal_set() {
+        let db_pull = format_fixture().await;
+        let standard = load_format(&db_pull, "standard").await.unwrap();
+        let problems = validate_deck(&db_pull, &deck(), &rules(), &standard, "2028-01-01").await.unwrap();
+        assert_eq!(problems.len(), 3);
+        assert!(problems.iter().all(|p| matches!(p, DeckProblem::IllegalSet { set, .. } if set.0 == KOV)));
+    }
+
+    #[test]
+    async fn sealed_pool_legality() {
+        // GIVEN
+        let db_pull = format_fixture().await;
+        let standard = load_format(&db_pull, "standard").await.unwrap();
+        set_ban_status(&db_pull, &standard, 4, BanStatus::Banned, "2026-01-01").await.unwrap();
+        let legality = standard.legality(&db_pull, "2026-02-01").await.unwrap();
+        let pool = CardPool::load(&db_pull).await.unwrap();
+        let hall_of_fame_hero = pool.bucket(CardBusterSlot::Hero, SetId(3), CardRarity::Gold).unwrap().cards[0].clone();
+        let kov_card = pool.bucket(CardBusterSlot::BasicCard, KOV_ID, CardRarity::Bronze).unwrap().cards[0].clone();
+        // WHEN
+        let cards = [kov_card, hall_of_fame_hero];
+        let illegal = legality.illegal_cards(&cards);
+        // THEN
+        assert_eq!(illegal.len(), 1);
+        assert_eq!(illegal[0].set.id, SetId(3));
+        assert_eq!(legality.ban_status(&cards[1]), BanStatus::Banned);
+    }
+
+    #[test]
+    async fn ban_does_not_spread_to_card_with_same_number() {
+        // GIVEN
+        let db_pull = format_fixture().await;
+        sqlx::query("UPDATE cards SET id_in_set = 2 WHERE id = 3").execute(&db_pull).await.unwrap();
+        let standard = load_format(&db_pull, "standard").await.unwrap();
+        set_ban_status(&db_pull, &standard, 3, BanStatus::Banned, "2026-01-01").await.unwrap();
+        let legality = standard.legality(&db_pull, "2026-02-01").await.unwrap();
+        let pool = CardPool::load(&db_pull).await.unwrap();
+        let bronze = &pool.bucket(CardBusterSlot::BasicCard, KOV_ID, CardRarity::Bronze).unwrap().cards;
+        let banned = bronze.iter().find(|card| card.name == format!("{} 3", KOV)).unwrap();
+        let allowed = bronze.iter().find(|card| card.name == format!("{} 2", KOV)).unwrap();
+        // WHEN
+        let problems = validate_deck(&db_pull, &deck(), &rules(), &standard, "2026-02-01").await.unwrap();
+        // THEN
+        assert_eq!(problems, vec![DeckProblem::Banned { card_id: 3 }]);
+        assert_eq!(legality.ban_status(banned), BanStatus::Banned);
+        assert_eq!(legality.ban_status(allowed), BanStatus::Legal);
+    }
+
+    #[test]
+    async fn set_ban_status_with_invalid_date_then_error() {
+        let db_pull = format_fixture().await;
+        let standard = load_format(&db_pull, "standard").await.unwrap();
+        assert!(set_ban_status(&db_pull, &standard, 2, BanStatus::Banned, "2026.01.01").await.is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Каталог локального хранилища изображений по умолчанию
+pub const IMAGE_STORE_DIR: &str = "data/images";
+
+/// Хранилище изображений карт, адресуемое по содержимому (sha256)
+#[derive(Debug, Clone)]
+pub struct ImageStore {
+    root: PathBuf,
+}
+
+/// Результат сохранения изображения в хранилище
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredImage {
+    pub hash: String,
+    /// Путь относительно корня хранилища, например `ab/abcdef....png`
+    pub path: String,
+}
+
+impl ImageStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ImageStore { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Сохраняет изображение, если такого содержимого еще нет в хранилище
+    pub fn store(&self, bytes: &[u8], extension: &str) -> std::io::Result<StoredImage> {
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        let path = relative_path(&hash, extension);
+        let full_path = self.root.join(&path);
+        if !full_path.exists() {
+            std::fs::create_dir_all(full_path.parent().unwrap_or(&self.root))?;
+            // Пишем во временный файл и переименовываем, чтобы не оставить обрезанное изображение
+            let tmp_path = full_path.with_extension("tmp");
+            std::fs::write(&tmp_path, bytes)?;
+            std::fs::rename(&tmp_path, &full_path)?;
+        }
+        Ok(StoredImage { hash, path })
+    }
+
+    /// Полный путь к файлу по относительному пути из БД.
+    /// Возвращает `None` для путей, выходящих за пределы хранилища
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let relative = Path::new(path);
+        if relative.is_absolute() || relative.components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+            return None;
+        }
+        Some(self.root.join(relative))
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.resolve(path).is_some_and(|p| p.is_file())
+    }
+}
+
+/// Хэш содержимого из пути хранилища вида `ab/abcdef....png`.
+/// `None`, если путь не похож на путь, созданный `ImageStore::store`
+pub fn hash_from_path(path: &str) -> Option<&str> {
+    let (prefix, file_name) = path.split_once('/')?;
+    let hash = file_name.split_once('.').map_or(file_name, |(hash, _)| hash);
+    let is_hash = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase());
+    (is_hash && hash.starts_with(prefix) && prefix.len() == 2).then_some(hash)
+}
+
+fn relative_path(hash: &str, extension: &str) -> String {
+    if extension.is_empty() {
+        format!("{}/{}", &hash[..2], hash)
+    } else {
+        format!("{}/{}.{}", &hash[..2], hash, extension)
+    }
+}
+
+/// Расширение файла из URL изображения (без query и fragment)
+pub fn extension_from_url(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    match file_name.rsplit_once('.') {
+        Some((name, ext)) if !name.is_empty() && !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()) => ext,
+        _ => "",
+    }
+}
+
+/// MIME тип по расширению файла
+pub fn content_type(path: &str) -> &'static str {
+    match Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> ImageStore {
+        let root = std::env::temp_dir().join(format!("response-sim-images-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        ImageStore::new(root)
+    }
+
+    #[test]
+    fn store_image_then_content_addressed_path() {
+        // GIVEN
+        let store = temp_store("path");
+        // WHEN
+        let stored = store.store(b"image", "png").unwrap();
+        // THEN
+        assert_eq!(stored.hash, "6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d");
+        assert_eq!(stored.path, format!("61/{}.png", stored.hash));
+        assert_eq!(std::fs::read(store.resolve(&stored.path).unwrap()).unwrap(), b"image");
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn store_same_image_twice_then_same_path() {
+        // GIVEN
+        let store = temp_store("twice");
+        // WHEN
+        let first = store.store(b"image", "png").unwrap();
+        let second = store.store(b"image", "png").unwrap();
+        // THEN
+        assert_eq!(first, second);
+        assert!(store.contains(&first.path));
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn resolve_path_outside_store_then_none() {
+        let store = ImageStore::new("data/images");
+        assert!(store.resolve("../database.db").is_none());
+        assert!(store.resolve("/etc/passwd").is_none());
+        assert!(store.resolve("ab/abc.png").is_some());
+    }
+
+    #[test]
+    fn hash_from_stored_path() {
+        let hash = "6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d";
+        assert_eq!(hash_from_path(&format!("61/{}.png", hash)), Some(hash));
+        assert_eq!(hash_from_path(&format!("61/{}", hash)), Some(hash));
+        assert_eq!(hash_from_path(&format!("62/{}.png", hash)), None);
+        assert_eq!(hash_from_path("61/abc.png"), None);
+        assert_eq!(hash_from_path("cards/1"), None);
+    }
+
+    #[test]
+    fn extension_from_url_with_query() {
+        assert_eq!(extension_from_url("https://response-world.ru/media/cards/1.webp?v=2"), "webp");
+        assert_eq!(extension_from_url("https://response-world.ru/media/cards/1"), "");
+        assert_eq!(extension_from_url("https://response-world.ru/media/cards/.hidden"), "");
+    }
+}
@@ -0,0 +1,242 @@
+//! Преобразование данных response-world.ru (JSON API и HTML страниц) в модель библиотеки
+
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
+
+use crate::{Card, CardBusterSlot, CardRarity, SetRegistry, scrape::ScrapedCard};
+
+pub const RESPONSE_WORLD_URL: &str = "https://response-world.ru/";
+
+#[serde_as]
+#[derive(Deserialize, Debug)]
+pub struct CardJson {
+    pub name: String,
+    pub images: serde_json::Value,
+    #[serde_as(as = "DisplayFromStr")]
+    pub id_in_set: u32,
+    pub rarity: RarityJson,
+    #[serde(default)]
+    pub fractions: Vec<FractionJson>,
+    pub types: Vec<CardTypeJson>,
+    pub card_set: SetJson,
+    /// Стоимость приходит то числом, то строкой
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    pub cost: Option<u32>,
+    /// Текст правил
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RarityJson {
+    pub display_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FractionJson {
+    pub display_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CardTypeJson {
+    pub display_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SetJson {
+    pub display_name: String,
+}
+
+/// Импортированная карта с данными, которые не нужны для генерации бустеров
+#[derive(Debug, Clone)]
+pub struct ImportedCard {
+    pub card: Card,
+    /// Пусто у нейтральных карт
+    pub factions: Vec<String>,
+    pub cost: Option<u32>,
+    pub text: Option<String>,
+}
+
+/// Создает карту из ответа `api/cards/{id}` (объект `card`).
+/// Сет карты должен быть заранее загружен в `sets`
+pub fn card_from_json(card_json: CardJson, sets: &SetRegistry) -> Result<ImportedCard, Box<dyn std::error::Error>> {
+    let image_url = card_json.images.get("card_path")
+        .and_then(|path| path.as_str().map(|s| RESPONSE_WORLD_URL.to_string() + s));
+    let types: Vec<&str> = card_json.types.iter().map(|t| t.display_name.as_str()).collect();
+    let card = card_from_fields(
+        card_json.name,
+        card_json.id_in_set,
+        card_json.rarity.display_name,
+        &types,
+        &card_json.card_set.display_name,
+        image_url,
+        sets,
+    )?;
+    let factions = card_json.fractions.into_iter().filter_map(|f| non_empty(f.display_name)).collect();
+    Ok(ImportedCard {
+        card,
+        factions,
+        cost: card_json.cost,
+        text: card_json.description.and_then(non_empty),
+    })
+}
+
+/// Создает карту из разобранной HTML страницы
+pub fn card_from_scraped(scraped: ScrapedCard, sets: &SetRegistry) -> Result<ImportedCard, Box<dyn std::error::Error>> {
+    let types: Vec<&str> = scraped.types.iter().map(String::as_str).collect();
+    let card = card_from_fields(scraped.name, scraped.id_in_set, scraped.rarity, &types, &scraped.set, scraped.image_url, sets)?;
+    Ok(ImportedCard { card, factions: scraped.factions, cost: scraped.cost, text: scraped.text })
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn card_from_fields(
+    name: String,
+    id_in_set: u32,
+    rarity: String,
+    types: &[&str],
+    set: &str,
+    image_url: Option<String>,
+    sets: &SetRegistry,
+) -> Result<Card, Box<dyn std::error::Error>> {
+    let rarity = CardRarity::try_from(rarity)?;
+    let buster_slot = buster_slot_from_types(types);
+    let set = sets.by_short_name(set).ok_or_else(|| format!("Error. Not found set: {}", set))?;
+    Ok(Card::new(name, id_in_set, rarity, buster_slot, set.clone(), image_url, crate::DEFAULT_CARD_WEIGHT))
+}
+
+/// Карта может иметь несколько типов, слот в бустере определяется самым значимым из них
+fn buster_slot_from_types(types: &[&str]) -> CardBusterSlot {
+    let slot_of = |slot: CardBusterSlot| types.iter().any(|t| CardBusterSlot::try_from(t.to_string()).ok() == Some(slot));
+    if slot_of(CardBusterSlot::Hero) {
+        CardBusterSlot::Hero
+    } else if slot_of(CardBusterSlot::Command) {
+        CardBusterSlot::Command
+    } else {
+        CardBusterSlot::BasicCard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SetId, SetInfo};
+
+    fn sets() -> SetRegistry {
+        let set = |id, short_name: &str, is_substitution_pool| SetInfo {
+            id: SetId(id),
+            name: short_name.to_string(),
+            short_name: short_name.to_string(),
+            release_date: "2025-10-21".to_string(),
+            card_count: 10,
+            is_substitution_pool,
+        };
+        SetRegistry::new(vec![set(2, "КОВ", false), set(3, "Зал Славы", true)])
+    }
+
+    fn card_json(rarity: &str, types: &[&str], set: &str) -> serde_json::Value {
+        serde_json::json!({
+            "name": "Лорд Доминус",
+            "images": { "card_path": "storage/cards/7.webp" },
+            "id_in_set": "7",
+            "rarity": { "display_name": rarity },
+            "types": types.iter().map(|t| serde_json::json!({ "display_name": t })).collect::<Vec<_>>(),
+            "card_set": { "display_name": set },
+        })
+    }
+
+    #[test]
+    fn card_from_json_with_faction_cost_and_text() {
+        // GIVEN
+        let mut value = card_json("Золото", &["Существо", "Герой"], "Зал Славы");
+        value["fractions"] = serde_json::json!([{ "display_name": "Империя" }, { "display_name": " Лес " }]);
+        value["cost"] = serde_json::json!("5");
+        value["description"] = serde_json::json!(" Возьмите карту. ");
+        // WHEN
+        let imported = card_from_json(serde_json::from_value(value).unwrap(), &sets()).unwrap();
+        // THEN
+        assert_eq!(imported.factions, vec!["Империя".to_string(), "Лес".to_string()]);
+        assert_eq!(imported.cost, Some(5));
+        assert_eq!(imported.text.as_deref(), Some("Возьмите карту."));
+    }
+
+    #[test]
+    fn card_from_json_without_faction_cost_and_text() {
+        let value = card_json("Бронза", &["Приказ"], "КОВ");
+        let imported = card_from_json(serde_json::from_value(value).unwrap(), &sets()).unwrap();
+        assert!(imported.factions.is_empty());
+        assert_eq!((imported.cost, imported.text), (None, None));
+    }
+
+    #[test]
+    fn card_from_scraped_fixture_page() {
+        // GIVEN
+        let html = include_str!("../tests/fixtures/card_hero.html");
+        let scraped = crate::scrape::parse_card_page(html, RESPONSE_WORLD_URL).unwrap();
+        // WHEN
+        let imported = card_from_scraped(scraped, &sets()).unwrap();
+        // THEN
+        assert_eq!(imported.card.buster_slot, CardBusterSlot::Hero);
+        assert_eq!(imported.card.set.id, SetId(3));
+        assert_eq!(imported.factions, vec!["Империя".to_string(), "Лес".to_string()]);
+        assert_eq!(imported.cost, Some(5));
+    }
+
+    #[test]
+    fn card_from_json_with_valid_values() {
+        // GIVEN
+        let value = card_json("Золото", &["Существо", "Герой"], "Зал Славы");
+        // WHEN
+        let card = card_from_json(serde_json::from_value(value).unwrap(), &sets()).unwrap().card;
+        // THEN
+        assert_eq!(card.name, "Лорд Доминус");
+        assert_eq!(card.id_in_set, 7);
+        assert_eq!(card.rarity, CardRarity::Gold);
+        assert_eq!(card.buster_slot, CardBusterSlot::Hero);
+        assert_eq!(card.set.id, SetId(3));
+        assert_eq!(card.image_url.as_deref(), Some("https://response-world.ru/storage/cards/7.webp"));
+    }
+
+    #[test]
+    fn card_from_json_without_special_type_then_basic_card() {
+        let value = card_json("Бронза", &["Существо"], "КОВ");
+        let card = card_from_json(serde_json::from_value(value).unwrap(), &sets()).unwrap().card;
+        assert_eq!(card.buster_slot, CardBusterSlot::BasicCard);
+    }
+
+    #[test]
+    fn card_from_json_with_unknown_rarity_then_err() {
+        let value = card_json("Платина", &["Приказ"], "КОВ");
+        assert!(card_from_json(serde_json::from_value(value).unwrap(), &sets()).is_err());
+    }
+
+    #[test]
+    fn card_from_json_with_unknown_set_then_err() {
+        let value = card_json("Бронза", &["Приказ"], "НЕТ");
+        assert!(card_from_json(serde_json::from_value(value).unwrap(), &sets()).is_err());
+    }
+
+    #[test]
+    fn card_from_scraped_command() {
+        // GIVEN
+        let scraped = ScrapedCard {
+            name: "Приказ об отступлении".to_string(),
+            id_in_set: 118,
+            rarity: "Бронза".to_string(),
+            types: vec!["Приказ".to_string()],
+            set: "КОВ".to_string(),
+            image_url: None,
+            factions: Vec::new(),
+            cost: Some(0),
+            text: None,
+        };
+        // WHEN
+        let card = card_from_scraped(scraped, &sets()).unwrap().card;
+        // THEN
+        assert_eq!(card.buster_slot, CardBusterSlot::Command);
+        assert_eq!(card.rarity, CardRarity::Bronze);
+        assert_eq!(card.set.id, SetId(2));
+    }
+}
@@ -0,0 +1,395 @@
+//! Фоновые задачи симуляции для сайта.
+//! Задача выполняется в отдельной задаче tokio, а ход и результат читаются по номеру задачи.
+//! Одновременно выполняется не больше [`MAX_RUNNING_JOBS`] задач, остальные ждут очереди
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, atomic::{AtomicU64, AtomicUsize, Ordering}},
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tokio::sync::Semaphore;
+
+use crate::{
+    BUSTER_SIZE, BusterRules, Card, CardPool, CardRarity, SetCode, presets,
+    simulation::{self, Accumulator, CardCounts, OpenedBuster, PoolSnapshot},
+};
+
+/// Наибольшее число бустеров в одной задаче
+pub const MAX_TRIALS: usize = 10_000_000;
+/// Сколько задач хранится одновременно. Если места нет, удаляется самая старая завершенная
+pub const MAX_JOBS: usize = 100;
+/// Сколько задач выполняется одновременно. Каждая задача сама занимает все ядра
+pub const MAX_RUNNING_JOBS: usize = 2;
+
+fn default_preset() -> String {
+    "official".to_string()
+}
+
+fn default_trials() -> usize {
+    100_000
+}
+
+/// Параметры задачи симуляции
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulationRequest {
+    pub set: SetCode,
+    #[serde(default = "default_preset")]
+    pub preset: String,
+    /// Версия набора правил, по умолчанию последняя
+    #[serde(default)]
+    pub version: Option<u32>,
+    /// Правила вместо набора из БД, сет правил должен совпадать с `set`
+    #[serde(default)]
+    pub rules: Option<BusterRules>,
+    #[serde(default = "default_trials")]
+    pub trials: usize,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+/// Гистограмма числа карт в бустере и ее процентили
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// `counts[k]` — число бустеров, в которых ровно `k` таких карт
+    pub counts: Vec<u64>,
+    pub mean: f64,
+    pub p10: usize,
+    pub median: usize,
+    pub p90: usize,
+    pub p99: usize,
+}
+
+impl Histogram {
+    /// `None`, если в гистограмме нет ни одного бустера
+    pub fn new(counts: Vec<u64>) -> Option<Self> {
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        // Процентиль по ближайшему рангу, как в `CostDistribution`
+        let percentile = |p: f64| {
+            let rank = ((p * total as f64).ceil() as u64).clamp(1, total);
+            let mut seen = 0;
+            counts.iter().position(|&count| {
+                seen += count;
+                seen >= rank
+            }).unwrap()
+        };
+        Some(Histogram {
+            mean: counts.iter().enumerate().map(|(k, &count)| k as f64 * count as f64).sum::<f64>() / total as f64,
+            p10: percentile(0.1),
+            median: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            counts,
+        })
+    }
+}
+
+/// Наблюдаемая частота карты
+#[derive(Debug, Clone, Serialize)]
+pub struct CardRate {
+    pub card: Card,
+    /// Среднее число копий карты в одном бустере
+    pub per_buster: f64,
+}
+
+/// Результат задачи
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationResult {
+    pub trials: usize,
+    pub seed: u64,
+    pub rules: BusterRules,
+    pub gold_cards: Histogram,
+    pub hall_of_fame_cards: Histogram,
+    /// Карты снимка в порядке снимка
+    pub cards: Vec<CardRate>,
+}
+
+/// Накопитель задачи: копии карт и гистограммы золотых карт и карт Зала Славы
+struct JobAccumulator {
+    cards: CardCounts,
+    gold: [u64; BUSTER_SIZE + 1],
+    hall_of_fame: [u64; BUSTER_SIZE + 1],
+}
+
+impl Accumulator for JobAccumulator {
+    fn new(pool: &PoolSnapshot) -> Self {
+        JobAccumulator { cards: CardCounts::new(pool), gold: [0; BUSTER_SIZE + 1], hall_of_fame: [0; BUSTER_SIZE + 1] }
+    }
+
+    fn record(&mut self, pool: &PoolSnapshot, buster: &OpenedBuster) {
+        self.cards.record(pool, buster);
+        let cards = || buster.cards.iter().map(|&i| pool.card(i));
+        self.gold[cards().filter(|card| card.rarity == CardRarity::Gold).count()] += 1;
+        self.hall_of_fame[cards().filter(|card| card.set.id != pool.set()).count()] += 1;
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.cards.merge(other.cards);
+        self.gold.iter_mut().zip(other.gold).for_each(|(c, o)| *c += o);
+        self.hall_of_fame.iter_mut().zip(other.hall_of_fame).for_each(|(c, o)| *c += o);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Done,
+    Failed,
+}
+
+/// Состояние задачи для API и страницы задачи
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    pub trials: usize,
+    /// Сколько бустеров уже открыто
+    pub done: usize,
+    pub result: Option<SimulationResult>,
+    pub error: Option<String>,
+}
+
+impl JobStatus {
+    /// Доля открытых бустеров от 0.0 до 1.0
+    pub fn progress(&self) -> f64 {
+        self.done as f64 / self.trials as f64
+    }
+}
+
+struct SimulationJob {
+    trials: usize,
+    done: AtomicUsize,
+    /// `None`, пока задача выполняется
+    outcome: Mutex<Option<Result<SimulationResult, String>>>,
+}
+
+impl SimulationJob {
+    fn is_finished(&self) -> bool {
+        self.outcome.lock().unwrap().is_some()
+    }
+}
+
+/// Задачи симуляции в памяти процесса. Клоны указывают на те же задачи
+#[derive(Clone)]
+pub struct SimulationJobs {
+    next_id: Arc<AtomicU64>,
+    jobs: Arc<Mutex<BTreeMap<u64, Arc<SimulationJob>>>>,
+    running: Arc<Semaphore>,
+}
+
+impl Default for SimulationJobs {
+    fn default() -> Self {
+        SimulationJobs {
+            next_id: Arc::default(),
+            jobs: Arc::default(),
+            running: Arc::new(Semaphore::new(MAX_RUNNING_JOBS)),
+        }
+    }
+}
+
+impl SimulationJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Проверяет запрос, подготавливает снимок пулов и запускает задачу.
+    /// Возвращает номер задачи, не дожидаясь окончания симуляции
+    pub async fn start(&self, db_pull: &SqlitePool, request: SimulationRequest) -> Result<u64, Box<dyn std::error::Error>> {
+        if !(1..=MAX_TRIALS).contains(&request.trials) {
+            return Err(format!("Error. Trials must be between 1 and {}", MAX_TRIALS).into());
+        }
+        let rules = match request.rules {
+            Some(rules) if rules.set() != &request.set => return Err(format!("Error. Rules are for set {}, not {}", rules.set(), request.set).into()),
+            Some(rules) => rules,
+            None => presets::load_preset(db_pull, &request.set, &request.preset, request.version).await?.rules,
+        };
+        let snapshot = CardPool::load(db_pull).await?.snapshot(&rules)?;
+
+        let (id, job) = self.insert(request.trials)?;
+        let (trials, seed) = (request.trials, request.seed);
+        self.spawn(job, move |done| run_job(&snapshot, rules, trials, seed, done));
+        Ok(id)
+    }
+
+    fn insert(&self, trials: usize) -> Result<(u64, Arc<SimulationJob>), Box<dyn std::error::Error>> {
+        let job = Arc::new(SimulationJob { trials, done: AtomicUsize::new(0), outcome: Mutex::new(None) });
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.len() >= MAX_JOBS {
+            let finished = jobs.iter().find(|(_, job)| job.is_finished()).map(|(&id, _)| id);
+            jobs.remove(&finished.ok_or("Error. Too many running simulations")?);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        jobs.insert(id, job.clone());
+        Ok((id, job))
+    }
+
+    /// Запускает `run`, когда освободится место среди выполняемых задач.
+    /// Паника в `run` завершает задачу с ошибкой, иначе задача навсегда осталась бы выполняемой
+    fn spawn<F>(&self, job: Arc<SimulationJob>, run: F)
+    where
+        F: FnOnce(&AtomicUsize) -> Result<SimulationResult, String> + Send + 'static,
+    {
+        let running = self.running.clone();
+        tokio::spawn(async move {
+            let _permit = running.acquire_owned().await;
+            let blocking_job = job.clone();
+            let outcome = tokio::task::spawn_blocking(move || run(&blocking_job.done)).await
+                .unwrap_or_else(|e| Err(format!("Error. Simulation failed: {}", e)));
+            *job.outcome.lock().unwrap() = Some(outcome);
+        });
+    }
+
+    /// `None` для неизвестной или удаленной задачи
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        let job = self.jobs.lock().unwrap().get(&id)?.clone();
+        let outcome = job.outcome.lock().unwrap().clone();
+        let (state, result, error) = match outcome {
+            None => (JobState::Running, None, None),
+            Some(Ok(result)) => (JobState::Done, Some(result), None),
+            Some(Err(error)) => (JobState::Failed, None, Some(error)),
+        };
+        Some(JobStatus { id, state, trials: job.trials, done: job.done.load(Ordering::Relaxed), result, error })
+    }
+}
+
+fn run_job(snapshot: &PoolSnapshot, rules: BusterRules, trials: usize, seed: u64, done: &AtomicUsize) -> Result<SimulationResult, String> {
+    let stats: JobAccumulator = simulation::simulate_with_progress(snapshot, trials, seed, simulation::default_threads(), done);
+    let cards = snapshot.cards().iter().enumerate()
+        .map(|(index, card)| CardRate { card: card.clone(), per_buster: stats.cards.per_buster(index) })
+        .collect();
+    Ok(SimulationResult {
+        trials,
+        seed,
+        rules,
+        gold_cards: Histogram::new(stats.gold.to_vec()).ok_or("Error. No boosters opened")?,
+        hall_of_fame_cards: Histogram::new(stats.hall_of_fame.to_vec()).ok_or("Error. No boosters opened")?,
+        cards,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture;
+
+    fn request(trials: usize) -> SimulationRequest {
+        serde_json::from_value(serde_json::json!({ "set": "КОВ", "trials": trials, "seed": 3 })).unwrap()
+    }
+
+    async fn wait(jobs: &SimulationJobs, id: u64) -> JobStatus {
+        loop {
+            let status = jobs.status(id).unwrap();
+            if status.state != JobState::Running {
+                return status;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    }
+
+    #[test]
+    async fn start_runs_simulation_in_background() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        let jobs = SimulationJobs::new();
+        // WHEN
+        let id = jobs.start(&db_pull, request(10_000)).await.unwrap();
+        let status = wait(&jobs, id).await;
+        // THEN
+        assert_eq!(status.state, JobState::Done);
+        assert_eq!(status.done, 10_000);
+        assert_eq!(status.progress(), 1.0);
+        let result = status.result.unwrap();
+        assert_eq!(result.gold_cards.counts.iter().sum::<u64>(), 10_000);
+        assert_eq!(result.hall_of_fame_cards.counts.iter().sum::<u64>(), 10_000);
+        // Слот 4 всегда золотой
+        assert_eq!(result.gold_cards.counts[0], 0);
+        assert!(result.gold_cards.mean > 1.0);
+        let per_buster: f64 = result.cards.iter().map(|rate| rate.per_buster).sum();
+        assert!((per_buster - BUSTER_SIZE as f64).abs() < 1e-9);
+    }
+
+    #[test]
+    async fn same_request_then_same_result() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        let jobs = SimulationJobs::new();
+        // WHEN
+        let first_id = jobs.start(&db_pull, request(5_000)).await.unwrap();
+        let second_id = jobs.start(&db_pull, request(5_000)).await.unwrap();
+        let first = wait(&jobs, first_id).await.result.unwrap();
+        let second = wait(&jobs, second_id).await.result.unwrap();
+        // THEN
+        assert_ne!(first_id, second_id);
+        assert_eq!(first.gold_cards, second.gold_cards);
+        assert_eq!(first.hall_of_fame_cards, second.hall_of_fame_cards);
+    }
+
+    #[test]
+    async fn start_with_bad_request_then_error() {
+        let db_pull = fixture::standard().await;
+        let jobs = SimulationJobs::new();
+        assert!(jobs.start(&db_pull, request(0)).await.is_err());
+        assert!(jobs.start(&db_pull, request(MAX_TRIALS + 1)).await.is_err());
+        let unknown_preset = SimulationRequest { preset: "unknown".to_string(), ..request(10) };
+        assert!(jobs.start(&db_pull, unknown_preset).await.is_err());
+        assert!(jobs.status(1).is_none());
+    }
+
+    #[test]
+    async fn panicked_job_then_failed() {
+        // GIVEN
+        let jobs = SimulationJobs::new();
+        let (id, job) = jobs.insert(10).unwrap();
+        // WHEN
+        jobs.spawn(job, |_| panic!("broken pool"));
+        let status = wait(&jobs, id).await;
+        // THEN
+        assert_eq!(status.state, JobState::Failed);
+        assert!(status.error.unwrap().contains("panic"));
+    }
+
+    #[test]
+    async fn running_jobs_are_limited() {
+        // GIVEN
+        let jobs = SimulationJobs::new();
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        // WHEN
+        let mut ids = Vec::new();
+        for _ in 0..MAX_RUNNING_JOBS * 3 {
+            let (id, job) = jobs.insert(1).unwrap();
+            let (running, max_running) = (running.clone(), max_running.clone());
+            jobs.spawn(job, move |_| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Err("Error. Stub".to_string())
+            });
+            ids.push(id);
+        }
+        for id in ids {
+            wait(&jobs, id).await;
+        }
+        // THEN
+        assert_eq!(max_running.load(Ordering::SeqCst), MAX_RUNNING_JOBS);
+    }
+
+    #[test]
+    async fn histogram_percentiles() {
+        // WHEN
+        let histogram = Histogram::new(vec![0, 50, 40, 9, 1]).unwrap();
+        // THEN
+        assert_eq!(histogram.mean, 1.61);
+        assert_eq!((histogram.p10, histogram.median, histogram.p90, histogram.p99), (1, 1, 2, 3));
+        assert!(Histogram::new(vec![0, 0]).is_none());
+    }
+}
@@ -1,40 +1,125 @@
-use std::{collections::HashMap, fmt::Display};
+pub mod boxes;
+pub mod cli;
+pub mod collection;
+pub mod compare;
+pub mod dashboard;
+pub mod db;
+pub mod deck;
+pub mod distribution;
+pub mod economy;
+#[cfg(test)]
+mod fixture;
+pub mod formats;
+pub mod images;
+pub mod import;
+pub mod jobs;
+pub mod locale;
+pub mod presets;
+pub mod prices;
+pub mod query;
+pub mod scrape;
+pub mod sets;
+pub mod simulation;
+#[cfg(test)]
+mod stat_tests;
+pub mod validate;
 
-use rand::{Rng, seq::IndexedRandom};
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 
+use locale::Translation;
+
+pub use distribution::{Distribution, DynamicDistribution};
+pub use locale::{Locale, Localize};
+pub use sets::{SetCode, SetId, SetInfo, SetRegistry};
+pub use simulation::PoolSnapshot;
+
 const BUSTER_SIZE: usize = 18;
 
-#[derive(Debug, Clone)]
+/// В JSON сет записывается кодом, прочитать карту обратно можно через `CardData`
+#[derive(Debug, Clone, Serialize)]
 pub struct Card {
     pub name: String,
     pub id_in_set: u32,
     pub rarity: CardRarity,
     pub buster_slot: CardBusterSlot,
-    pub set: CardSet,
+    #[serde(serialize_with = "serialize_set_code")]
+    pub set: Arc<SetInfo>,
     pub image_url: Option<String>,
+    /// Относительный вес карты внутри пула (слот, сет, редкость), по умолчанию 1.0.
+    /// Меньший вес означает, что карта напечатана меньшим тиражом
+    pub weight: f64,
+}
+
+/// Вес карты, если издатель не печатает ее меньшим или большим тиражом
+pub const DEFAULT_CARD_WEIGHT: f64 = 1.0;
+
+fn serialize_set_code<S: serde::Serializer>(set: &Arc<SetInfo>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&set.short_name)
+}
+
+/// Карта из JSON или TOML в том виде, в каком ее записывает `Card`.
+/// Вес проверяется при десериализации, сет ищется по коду в `CardData::resolve`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardData {
+    pub name: String,
+    pub id_in_set: u32,
+    pub rarity: CardRarity,
+    pub buster_slot: CardBusterSlot,
+    pub set: SetCode,
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default = "default_card_weight", deserialize_with = "deserialize_card_weight")]
+    pub weight: f64,
+}
+
+fn default_card_weight() -> f64 {
+    DEFAULT_CARD_WEIGHT
+}
+
+fn deserialize_card_weight<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let weight = f64::deserialize(deserializer)?;
+    if !weight.is_finite() || weight < 0.0 {
+        return Err(serde::de::Error::custom(format!("Error. Invalid card weight: {}", weight)));
+    }
+    Ok(weight)
+}
+
+impl CardData {
+    /// Карта с сетом из реестра. Ошибка, если сета с таким кодом нет
+    pub fn resolve(self, sets: &SetRegistry) -> Result<Card, Box<dyn std::error::Error>> {
+        let set = sets.by_code(&self.set).ok_or_else(|| format!("Error. Not found set: {}", self.set))?.clone();
+        Ok(Card::new(self.name, self.id_in_set, self.rarity, self.buster_slot, set, self.image_url, self.weight))
+    }
 }
 
 impl Display for Card {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:30} {:3} {:9} {:7} {:15}", self.name, self.id_in_set, self.set, self.rarity, self.buster_slot)
+        self.fmt_localized(Locale::default(), f)
     }
 }
 
-impl Card {
-    /// Создает карту "пустышку", перед использованием нужно заполнить поля
-    fn plug() -> Self {
-        Card {
-            name: "Пустышка".to_string(),
-            id_in_set: 0,
-            rarity: CardRarity::Bronze,
-            buster_slot: CardBusterSlot::BasicCard,
-            set: CardSet::BAZ,
-            image_url: None,
-        }
+impl Localize for Card {
+    /// Название карты не переводится, оно хранится в БД только на русском
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:30} {:3} {:12} {:7} {:15}",
+            self.name,
+            self.id_in_set,
+            self.set.localized(locale),
+            self.rarity.localized(locale),
+            self.buster_slot.localized(locale),
+        )
     }
+}
 
-    fn new(name: String, id_in_set: u32, rarity: CardRarity, buster_slot: CardBusterSlot, set: CardSet, image_url: Option<String>) -> Self {
+impl Card {
+    fn new(name: String, id_in_set: u32, rarity: CardRarity, buster_slot: CardBusterSlot, set: Arc<SetInfo>, image_url: Option<String>, weight: f64) -> Self {
         Card {
             name,
             id_in_set,
@@ -42,11 +127,14 @@ impl Card {
             buster_slot,
             set,
             image_url,
+            weight,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+/// В JSON и TOML редкость записывается как `bronze`, `silver` или `gold`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CardRarity {
     Bronze,
     Silver,
@@ -55,12 +143,18 @@ pub enum CardRarity {
 
 impl Display for CardRarity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            CardRarity::Bronze => "Бронза",
-            CardRarity::Silver => "Серебро",
-            CardRarity::Gold => "Золото",
+        self.fmt_localized(Locale::default(), f)
+    }
+}
+
+impl Localize for CardRarity {
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let translation = match self {
+            CardRarity::Bronze => Translation { ru: "Бронза", en: "Bronze" },
+            CardRarity::Silver => Translation { ru: "Серебро", en: "Silver" },
+            CardRarity::Gold => Translation { ru: "Золото", en: "Gold" },
         };
-        f.pad(&s)
+        f.pad(translation.get(locale))
     }
 }
 
@@ -68,22 +162,31 @@ impl CardRarity {
     fn iter() -> impl Iterator<Item = CardRarity> {
         [CardRarity::Bronze, CardRarity::Silver, CardRarity::Gold].into_iter()
     }
+
+    /// Название в таблице `rarities`
+    pub fn db_name(&self) -> &'static str {
+        match self {
+            CardRarity::Bronze => "Бронза",
+            CardRarity::Silver => "Серебро",
+            CardRarity::Gold => "Золото",
+        }
+    }
 }
 
+/// Разбирает название из таблицы `rarities`
 impl TryFrom<String> for CardRarity {
     type Error = Box<dyn std::error::Error>;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        match value.as_str() {
-            "Бронза" => Ok(CardRarity::Bronze),
-            "Серебро" => Ok(CardRarity::Silver),
-            "Золото" => Ok(CardRarity::Gold),
-            r => Err(format!("Error. Not found rarity: {}", r).into())
-        }
+        CardRarity::iter()
+            .find(|rarity| rarity.db_name() == value)
+            .ok_or_else(|| format!("Error. Not found rarity: {}", value).into())
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+/// В JSON и TOML слот записывается как `hero`, `command` или `basic_card`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CardBusterSlot {
     Hero,
     Command,
@@ -92,12 +195,18 @@ pub enum CardBusterSlot {
 
 impl Display for CardBusterSlot {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-       let s = match self {
-            CardBusterSlot::Hero => "Герой",
-            CardBusterSlot::Command => "Приказ",
-            CardBusterSlot::BasicCard => "Основная карта",
+        self.fmt_localized(Locale::default(), f)
+    }
+}
+
+impl Localize for CardBusterSlot {
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let translation = match self {
+            CardBusterSlot::Hero => Translation { ru: "Герой", en: "Hero" },
+            CardBusterSlot::Command => Translation { ru: "Приказ", en: "Command" },
+            CardBusterSlot::BasicCard => Translation { ru: "Основная карта", en: "Basic card" },
         };
-        f.pad(&s) 
+        f.pad(translation.get(locale))
     }
 }
 
@@ -105,210 +214,566 @@ impl CardBusterSlot {
     fn iter() -> impl Iterator<Item = CardBusterSlot> {
         [CardBusterSlot::Hero, CardBusterSlot::Command, CardBusterSlot::BasicCard].into_iter()
     }
+
+    /// Название в таблице `types`
+    pub fn db_name(&self) -> &'static str {
+        match self {
+            CardBusterSlot::Hero => "Герой",
+            CardBusterSlot::Command => "Приказ",
+            CardBusterSlot::BasicCard => "Основная карта",
+        }
+    }
 }
 
+/// Разбирает название из таблицы `types`
 impl TryFrom<String> for CardBusterSlot {
     type Error = Box<dyn std::error::Error>;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        match value.as_str() {
-            "Герой" => Ok(CardBusterSlot::Hero),
-            "Приказ" => Ok(CardBusterSlot::Command),
-            "Основная карта" => Ok(CardBusterSlot::BasicCard),
-            s => Err(format!("Error. Not found buster slot: {}", s).into())
-        }
+        CardBusterSlot::iter()
+            .find(|slot| slot.db_name() == value)
+            .ok_or_else(|| format!("Error. Not found buster slot: {}", value).into())
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-pub enum CardSet {
-    BAZ,
-    KOV,
-    HallOfFame,
+/// Карты одного пула (слот, сет, редкость) и распределение по их весам
+#[derive(Debug, Default)]
+struct CardBucket {
+    cards: Vec<Card>,
+    /// `None`, если в пуле нет карт или у всех карт нулевой вес
+    distribution: Option<DynamicDistribution>,
 }
 
-impl Display for CardSet {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            CardSet::BAZ => "БАЗ",
-            CardSet::KOV => "КОВ",
-            CardSet::HallOfFame => "Зал Славы",
-        };
-        f.pad(&s)
+impl CardBucket {
+    /// Ошибка, если вес какой-то карты отрицательный или бесконечный
+    fn new(cards: Vec<Card>) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(card) = cards.iter().find(|c| !c.weight.is_finite() || c.weight < 0.0) {
+            return Err(format!("Error. Invalid weight {} of card {} {} {}", card.weight, card.set.short_name, card.id_in_set, card.name).into());
+        }
+        let weights: Vec<f64> = cards.iter().map(|c| c.weight).collect();
+        let distribution = positive_weights_distribution(&weights)?;
+        Ok(CardBucket { cards, distribution })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.distribution.is_none()
+    }
+
+    fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&Card> {
+        let distribution = self.distribution.as_ref()?;
+        Some(&self.cards[distribution.generate(rng)])
+    }
+
+    /// Вероятность выбрать каждую карту пула
+    fn chanses(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.distribution.iter().flat_map(|d| d.values().iter().copied().enumerate())
     }
 }
 
-impl TryFrom<String> for CardSet {
-    type Error = Box<dyn std::error::Error>;
+/// Все карты одного слота сета без разделения по редкости: пары (редкость, номер карты в пуле)
+#[derive(Debug, Default)]
+struct SlotBucket {
+    entries: Vec<(CardRarity, usize)>,
+    distribution: Option<DynamicDistribution>,
+}
 
-    fn try_from(value: String) -> Result<Self, Self::Error> {
-        match value.as_str() {
-            "БАЗ" => Ok(CardSet::BAZ),
-            "КОВ" => Ok(CardSet::KOV),
-            "Зал Славы" => Ok(CardSet::HallOfFame),
-            s => Err(format!("Error. Not found set: {}", s).into())
-        }
+impl SlotBucket {
+    fn new(entries: Vec<(CardRarity, usize)>, weights: &[f64]) -> Result<Self, Box<dyn std::error::Error>> {
+        let distribution = positive_weights_distribution(weights)?;
+        Ok(SlotBucket { entries, distribution })
+    }
+
+    fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(CardRarity, usize)> {
+        let distribution = self.distribution.as_ref()?;
+        Some(self.entries[distribution.generate(rng)])
     }
+
+    fn chanses(&self) -> impl Iterator<Item = ((CardRarity, usize), f64)> + '_ {
+        self.distribution.iter().flat_map(|d| self.entries.iter().copied().zip(d.values().iter().copied()))
+    }
+}
+
+/// Распределение по весам или `None`, если весов нет или все они нулевые
+fn positive_weights_distribution(weights: &[f64]) -> Result<Option<DynamicDistribution>, Box<dyn std::error::Error>> {
+    if weights.iter().all(|&w| w == 0.0) {
+        return Ok(None);
+    }
+    Ok(Some(DynamicDistribution::from_weights(weights)?))
+}
+
+/// Откуда берется карта, если выпал Зал Славы
+enum HallOfFameSource<'a> {
+    /// Пул Зала Славы той же редкости
+    Rarity(&'a CardBucket),
+    /// Все карты слота в Зале Славы, редкость перебрасывается
+    Slot(&'a SlotBucket),
+    /// Карта берется из основного сета, хотя Зал Славы выпал
+    Fallback,
+    /// Пул не участвует в броске на Зал Славы
+    Skip,
+}
+
+/// Фактическая вероятность Зала Славы в слоте бустера
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HallOfFameRate {
+    pub slot: CardBusterSlot,
+    /// Вероятность получить в слоте карту Зала Славы
+    pub chanse: f64,
+    /// Вероятность того, что Зал Славы выпал, но карта взята из основного сета
+    pub fallback: f64,
 }
 
+/// Ожидаемая частота выпадения карты по правилам бустера
+#[derive(Debug, Clone, Serialize)]
+pub struct PullRate {
+    pub card: Card,
+    /// Среднее число копий карты в одном бустере
+    pub per_buster: f64,
+    /// Вероятность получить хотя бы одну копию в бустере
+    pub at_least_one: f64,
+}
+
+/// Карты загруженных сетов, разложенные по пулам (слот, сет, редкость).
+/// После загрузки не меняется, поэтому один пул можно разделять через `Arc` между потоками и обработчиками
 #[derive(Debug)]
-pub struct CardBase {
-    cards: HashMap<(CardBusterSlot, CardSet, CardRarity), Vec<Card>>,
-    sets: Vec<CardSet>,
-    db_pull: SqlitePool,
-    rng: rand::rngs::ThreadRng,
+pub struct CardPool {
+    cards: HashMap<(CardBusterSlot, SetId, CardRarity), CardBucket>,
+    slots: HashMap<(CardBusterSlot, SetId), SlotBucket>,
+    sets: SetRegistry,
 }
 
-impl CardBase {
-    pub async fn new(db_pull: SqlitePool) -> Self {
-        let rng = rand::rng();
-        let mut new_card_base = CardBase {
+/// Все карты сета из БД в порядке добавления, веса не проверяются
+pub(crate) async fn load_set_cards(db_pull: &SqlitePool, set: &Arc<SetInfo>) -> Result<Vec<Card>, Box<dyn std::error::Error>> {
+    sqlx::query!(
+        "SELECT cards.name as name, id_in_set, rarities.name as rarity, types.name as buster_slot, image_url, weight
+        FROM cards
+        INNER JOIN rarities ON cards.rarity_id = rarities.id
+        INNER JOIN types ON cards.type_id = types.id
+        WHERE set_id = ?
+        ORDER BY cards.id",
+        set.id.0,
+        ).fetch_all(db_pull)
+        .await?
+        .into_iter()
+        .map(|rec| {
+            let rarity = CardRarity::try_from(rec.rarity)?;
+            let buster_slot = CardBusterSlot::try_from(rec.buster_slot)?;
+            Ok(Card::new(rec.name, u32::try_from(rec.id_in_set)?, rarity, buster_slot, set.clone(), rec.image_url, rec.weight))
+        })
+        .collect()
+}
+
+impl CardPool {
+    /// Загружает все сеты из БД
+    pub async fn load(db_pull: &SqlitePool) -> Result<Self, Box<dyn std::error::Error>> {
+        let sets = SetRegistry::load(db_pull).await?;
+        let ids: Vec<SetId> = sets.iter().map(|s| s.id).collect();
+        CardPool::load_with_registry(db_pull, sets, &ids).await
+    }
+
+    /// Загружает только указанные сеты и сет-подмену
+    pub async fn load_sets(db_pull: &SqlitePool, set_ids: &[SetId]) -> Result<Self, Box<dyn std::error::Error>> {
+        let sets = SetRegistry::load(db_pull).await?;
+        let mut ids = set_ids.to_vec();
+        ids.extend(sets.substitution_pool().map(|s| s.id));
+        CardPool::load_with_registry(db_pull, sets, &ids).await
+    }
+
+    async fn load_with_registry(db_pull: &SqlitePool, sets: SetRegistry, set_ids: &[SetId]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut pool = CardPool {
             cards: HashMap::new(),
-            sets: vec![CardSet::HallOfFame],
-            db_pull: db_pull,
-            rng,
+            slots: HashMap::new(),
+            sets,
         };
-        new_card_base.add_set(CardSet::HallOfFame).await.unwrap();
-        new_card_base
+        for &set in set_ids {
+            if !pool.has_set(set) {
+                pool.add_set(db_pull, set).await?;
+            }
+        }
+        Ok(pool)
     }
 
-    fn has_set(&self, set: CardSet) -> bool {
-        self.sets.contains(&set)
+    pub fn sets(&self) -> &SetRegistry {
+        &self.sets
     }
 
-    async fn add_set(&mut self, set: CardSet) -> Result<(), Box<dyn std::error::Error>>{
+    /// Загружен ли сет в пул
+    pub fn has_set(&self, set: SetId) -> bool {
+        self.cards.keys().any(|(_, s, _)| *s == set)
+    }
+
+    /// Идентификатор сета по коду, если сет загружен в пул
+    pub fn loaded_set(&self, code: &SetCode) -> Result<SetId, Box<dyn std::error::Error>> {
+        self.sets.by_code(code)
+            .map(|s| s.id)
+            .filter(|&set| self.has_set(set))
+            .ok_or_else(|| format!("Error. Set is not loaded: {}", code).into())
+    }
+
+    async fn add_set(&mut self, db_pull: &SqlitePool, set: SetId) -> Result<(), Box<dyn std::error::Error>>{
+        let set_info = self.sets.get(set).ok_or_else(|| format!("Error. Not found set: {}", set))?.clone();
+        let mut buckets: HashMap<(CardBusterSlot, CardRarity), Vec<Card>> = HashMap::new();
         CardBusterSlot::iter().for_each(|slot| { 
             CardRarity::iter().for_each(|rarity| {
-                self.cards.insert((slot, set, rarity), Vec::new());
+                buckets.insert((slot, rarity), Vec::new());
             } )
         } );
             
-        let set_str = set.to_string();
-        sqlx::query!(
-            "SELECT cards.name as name, id_in_set, rarities.name as rarity, types.name as buster_slot, sets.short_name as set_name, image_url
-            FROM cards
-            INNER JOIN rarities ON cards.rarity_id = rarities.id
-            INNER JOIN types ON cards.type_id = types.id
-            INNER JOIN sets ON cards.set_id = sets.id
-            WHERE set_id = (SELECT id from sets WHERE short_name = ?)",
-            set_str,
-            ).fetch_all(&self.db_pull)
-            .await?
-            .into_iter()
-            .map(|rec| {
-                let rarity = CardRarity::try_from(rec.rarity)?;
-                let buster_slot = CardBusterSlot::try_from(rec.buster_slot)?;
-                let set = CardSet::try_from(rec.set_name)?;
-                Ok(Card::new(rec.name, u32::try_from(rec.id_in_set)?, rarity, buster_slot, set, rec.image_url))
-            })
-            .collect::<Result<Vec<Card>, Box<dyn std::error::Error>>>()?
-            .into_iter().for_each(|c| self.cards.get_mut(&(c.buster_slot, set, c.rarity)).unwrap().push(c.clone()));
-        
-        self.sets.push(set);
+        load_set_cards(db_pull, &set_info).await?
+            .into_iter().for_each(|c| buckets.get_mut(&(c.buster_slot, c.rarity)).unwrap().push(c));
+
+        for ((slot, rarity), cards) in buckets.into_iter() {
+            self.cards.insert((slot, set, rarity), CardBucket::new(cards)?);
+        }
+        for slot in CardBusterSlot::iter() {
+            let (entries, weights): (Vec<_>, Vec<_>) = CardRarity::iter()
+                .flat_map(|rarity| self.cards[&(slot, set, rarity)].cards.iter().enumerate().map(move |(i, c)| ((rarity, i), c.weight)))
+                .unzip();
+            self.slots.insert((slot, set), SlotBucket::new(entries, &weights)?);
+        }
         Ok(())
     }
 
-    async fn generate_card(&mut self, slot: CardBusterSlot, rarity: CardRarity, set: CardSet, hall_of_fame: bool) -> Option<Card> {
-        if !self.has_set(set) {
-            self.add_set(set).await.ok()?;
+    fn bucket(&self, slot: CardBusterSlot, set: SetId, rarity: CardRarity) -> Option<&CardBucket> {
+        self.cards.get(&(slot, set, rarity)).filter(|b| !b.is_empty())
+    }
+
+    /// Источник карты для пула основного сета, если выпал Зал Славы, с учетом политики
+    fn hall_of_fame_source(&self, slot: CardBusterSlot, rarity: CardRarity, policy: HallOfFamePolicy) -> HallOfFameSource<'_> {
+        let Some(hall_of_fame_set) = self.sets.substitution_pool().map(|s| s.id) else {
+            return HallOfFameSource::Skip;
+        };
+        if let Some(bucket) = self.bucket(slot, hall_of_fame_set, rarity) {
+            return HallOfFameSource::Rarity(bucket);
+        }
+        let slot_bucket = self.slots.get(&(slot, hall_of_fame_set)).filter(|b| b.distribution.is_some());
+        match (policy, slot_bucket) {
+            (HallOfFamePolicy::Fallback, _) => HallOfFameSource::Fallback,
+            (HallOfFamePolicy::RerollRarity, Some(slot_bucket)) => HallOfFameSource::Slot(slot_bucket),
+            (HallOfFamePolicy::RerollRarity, None) => HallOfFameSource::Fallback,
+            (HallOfFamePolicy::Skip, _) => HallOfFameSource::Skip,
+        }
+    }
+
+    /// Карта пула и признак того, что выпавший Зал Славы пришлось заменить основным сетом
+    fn generate_card<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        slot: CardBusterSlot,
+        rarity: CardRarity,
+        set: SetId,
+        hall_of_fame: bool,
+        policy: HallOfFamePolicy,
+    ) -> Option<(&Card, bool)> {
+        let source = if hall_of_fame { self.hall_of_fame_source(slot, rarity, policy) } else { HallOfFameSource::Skip };
+        let hall_of_fame_set = self.sets.substitution_pool().map(|s| s.id);
+        match source {
+            HallOfFameSource::Rarity(bucket) => Some((bucket.choose(rng)?, false)),
+            HallOfFameSource::Slot(slot_bucket) => {
+                let (rarity, i) = slot_bucket.choose(rng)?;
+                Some((&self.cards[&(slot, hall_of_fame_set?, rarity)].cards[i], false))
+            }
+            HallOfFameSource::Fallback => Some((self.bucket(slot, set, rarity)?.choose(rng)?, true)),
+            HallOfFameSource::Skip => Some((self.bucket(slot, set, rarity)?.choose(rng)?, false)),
         }
+    }
+
+    /// Фактическая вероятность Зала Славы в каждом слоте бустера, в порядке `BusterRules::layout`.
+    /// Меньше `hall_of_fame_chanse`, если в Зале Славы нет карт нужного слота и редкости
+    pub fn hall_of_fame_rates(&self, rules: &BusterRules) -> Vec<HallOfFameRate> {
+        rules.layout().into_iter()
+            .map(|(slot, rarities)| {
+                let mut rate = HallOfFameRate { slot, chanse: 0.0, fallback: 0.0 };
+                for (rarity, rarity_chanse) in rarities {
+                    let chanse = rarity_chanse * rules.hall_of_fame_chanse;
+                    match self.hall_of_fame_source(slot, rarity, rules.hall_of_fame_policy) {
+                        HallOfFameSource::Rarity(_) | HallOfFameSource::Slot(_) => rate.chanse += chanse,
+                        HallOfFameSource::Fallback => rate.fallback += chanse,
+                        HallOfFameSource::Skip => {}
+                    }
+                }
+                rate
+            })
+            .collect()
+    }
 
-        if hall_of_fame {
-            if let Some(card) = self.cards[&(slot, CardSet::HallOfFame, rarity)].choose(&mut self.rng) {
-                Some(card.clone())
-            } else {
-                Some(self.cards[&(slot, set, rarity)].choose(&mut self.rng)?.clone())
+    /// Ожидаемые частоты выпадения карт сета и Зала Славы по правилам бустера,
+    /// с учетом весов карт и политики для пустых пулов Зала Славы
+    pub fn pull_rates(&self, rules: &BusterRules) -> Result<Vec<PullRate>, Box<dyn std::error::Error>> {
+        let set = self.loaded_set(&rules.set)?;
+        let hall_of_fame_set = self.sets.substitution_pool().map(|s| s.id);
+
+        // Для каждой карты: среднее число копий и вероятность не получить ее ни в одном слоте
+        let mut rates: HashMap<(CardBusterSlot, SetId, CardRarity, usize), (f64, f64)> = HashMap::new();
+        for (slot, rarities) in rules.layout() {
+            let mut in_slot: HashMap<(CardBusterSlot, SetId, CardRarity, usize), f64> = HashMap::new();
+            let mut add = |set: SetId, chanses: &mut dyn Iterator<Item = ((CardRarity, usize), f64)>, chanse: f64| {
+                if chanse <= 0.0 {
+                    return;
+                }
+                chanses.for_each(|((rarity, i), p)| *in_slot.entry((slot, set, rarity, i)).or_default() += chanse * p);
+            };
+            for (rarity, rarity_chanse) in rarities.into_iter().filter(|(_, p)| *p > 0.0) {
+                let mut bucket = self.cards[&(slot, set, rarity)].chanses().map(|(i, p)| ((rarity, i), p));
+                let hall_of_fame_chanse = rarity_chanse * rules.hall_of_fame_chanse;
+                match (hall_of_fame_set, self.hall_of_fame_source(slot, rarity, rules.hall_of_fame_policy)) {
+                    (Some(hall_of_fame_set), HallOfFameSource::Rarity(hall_of_fame_bucket)) => {
+                        add(hall_of_fame_set, &mut hall_of_fame_bucket.chanses().map(|(i, p)| ((rarity, i), p)), hall_of_fame_chanse);
+                        add(set, &mut bucket, rarity_chanse - hall_of_fame_chanse);
+                    }
+                    (Some(hall_of_fame_set), HallOfFameSource::Slot(slot_bucket)) => {
+                        add(hall_of_fame_set, &mut slot_bucket.chanses(), hall_of_fame_chanse);
+                        add(set, &mut bucket, rarity_chanse - hall_of_fame_chanse);
+                    }
+                    _ => add(set, &mut bucket, rarity_chanse),
+                }
             }
-        } else {
-            Some(self.cards[&(slot, set, rarity)].choose(&mut self.rng)?.clone())
+            in_slot.into_iter().for_each(|(key, p)| {
+                let rate = rates.entry(key).or_insert((0.0, 1.0));
+                rate.0 += p;
+                rate.1 *= 1.0 - p;
+            });
         }
+
+        let mut pull_rates: Vec<PullRate> = rates.into_iter()
+            .map(|((slot, set, rarity, i), (per_buster, miss))| PullRate {
+                card: self.cards[&(slot, set, rarity)].cards[i].clone(),
+                per_buster,
+                at_least_one: 1.0 - miss,
+            })
+            .collect();
+        pull_rates.sort_by_key(|r| (r.card.set.id, r.card.id_in_set));
+        Ok(pull_rates)
+    }
+
+    /// Неизменяемый снимок пулов для быстрой симуляции по правилам бустера
+    pub fn snapshot(&self, rules: &BusterRules) -> Result<PoolSnapshot, Box<dyn std::error::Error>> {
+        let set = self.loaded_set(&rules.set)?;
+        PoolSnapshot::new(self, rules, set)
+    }
+}
+
+/// Генератор бустеров: общий пул карт и собственный генератор случайных чисел.
+/// Создается дешево, например на каждый запрос
+#[derive(Debug)]
+pub struct CardBase {
+    pool: Arc<CardPool>,
+    rng: StdRng,
+    hall_of_fame_fallbacks: u64,
+}
+
+impl CardBase {
+    /// Загружает все сеты из БД в новый пул
+    pub async fn new(db_pull: SqlitePool) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(CardBase::from_pool(Arc::new(CardPool::load(&db_pull).await?)))
+    }
+
+    /// Генератор с фиксированным зерном: одинаковое зерно дает одинаковые бустеры
+    pub async fn with_seed(db_pull: SqlitePool, seed: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(CardBase::from_pool_with_seed(Arc::new(CardPool::load(&db_pull).await?), seed))
+    }
+
+    pub fn from_pool(pool: Arc<CardPool>) -> Self {
+        CardBase { pool, rng: StdRng::from_os_rng(), hall_of_fame_fallbacks: 0 }
+    }
+
+    pub fn from_pool_with_seed(pool: Arc<CardPool>, seed: u64) -> Self {
+        CardBase { pool, rng: StdRng::seed_from_u64(seed), hall_of_fame_fallbacks: 0 }
+    }
+
+    pub fn pool(&self) -> &Arc<CardPool> {
+        &self.pool
     }
 
-    pub async fn generate_buster(&mut self, rules: &BusterRules) -> Option<[Card; BUSTER_SIZE]> {
-        if !self.has_set(rules.set) {
-            self.add_set(rules.set).await.ok()?;
+    pub fn sets(&self) -> &SetRegistry {
+        self.pool.sets()
+    }
+
+    /// Сколько раз выпавший Зал Славы был заменен картой основного сета
+    pub fn hall_of_fame_fallbacks(&self) -> u64 {
+        self.hall_of_fame_fallbacks
+    }
+
+    fn generate_card(&mut self, slot: CardBusterSlot, rarity: CardRarity, set: SetId, hall_of_fame: bool, policy: HallOfFamePolicy) -> Option<Card> {
+        let (card, fallback) = self.pool.generate_card(&mut self.rng, slot, rarity, set, hall_of_fame, policy)?;
+        if fallback {
+            self.hall_of_fame_fallbacks += 1;
         }
+        Some(card.clone())
+    }
+
+    /// Карта для слота бустера, с броском на Зал Славы по правилам
+    fn generate_rules_card(&mut self, slot: CardBusterSlot, rarity: CardRarity, set: SetId, rules: &BusterRules) -> Option<Card> {
+        let hall_of_fame = rules.generate_hall_of_fame(&mut self.rng);
+        self.generate_card(slot, rarity, set, hall_of_fame, rules.hall_of_fame_policy)
+    }
 
-        let mut buster = std::array::from_fn(|_| Card::plug());
+    /// `None`, если сет не загружен в пул или пул одной из редкостей пуст
+    pub fn generate_buster(&mut self, rules: &BusterRules) -> Option<[Card; BUSTER_SIZE]> {
+        let set = self.pool.loaded_set(&rules.set).ok()?;
+
+        let mut buster = Vec::with_capacity(BUSTER_SIZE);
 
         // Герой серебро/золото
-        let hero_rarity = match rules.hero_chanse.generate() {
+        let hero_rarity = match rules.hero_chanse.generate(&mut self.rng) {
             0 => CardRarity::Silver,
             1 => CardRarity::Gold,
             _ => unreachable!(),
         };
-        buster[0] = self.generate_card(CardBusterSlot::Hero, hero_rarity, rules.set, rules.generate_hall_of_fame()).await?;
+        buster.push(self.generate_rules_card(CardBusterSlot::Hero, hero_rarity, set, rules)?);
 
         // Приказ случайно редкости
-        let command_rarity = match rules.command_chanse_random.generate() {
+        let command_rarity = match rules.command_chanse_random.generate(&mut self.rng) {
             0 => CardRarity::Bronze,
             1 => CardRarity::Silver,
             2 => CardRarity::Gold,
             _ => unreachable!(),
         };
-        buster[1] = self.generate_card(CardBusterSlot::Command, command_rarity, rules.set, rules.generate_hall_of_fame()).await?;
+        buster.push(self.generate_rules_card(CardBusterSlot::Command, command_rarity, set, rules)?);
 
         // Приказ бронза/серебро
-        let command_rarity = match rules.command_chanse_bronze_silver.generate() {
+        let command_rarity = match rules.command_chanse_bronze_silver.generate(&mut self.rng) {
             0 => CardRarity::Bronze,
             1 => CardRarity::Silver,
             _ => unreachable!(),
         };
-        buster[2] = self.generate_card(CardBusterSlot::Command, command_rarity, rules.set, rules.generate_hall_of_fame()).await?;
+        buster.push(self.generate_rules_card(CardBusterSlot::Command, command_rarity, set, rules)?);
 
         // Приказ бронза
-        buster[3] = self.generate_card(CardBusterSlot::Command, CardRarity::Bronze, rules.set, rules.generate_hall_of_fame()).await?;
+        buster.push(self.generate_rules_card(CardBusterSlot::Command, CardRarity::Bronze, set, rules)?);
 
         // Основная карта золото
-        buster[4] = self.generate_card(CardBusterSlot::BasicCard, CardRarity::Gold, rules.set, rules.generate_hall_of_fame()).await?;
+        buster.push(self.generate_rules_card(CardBusterSlot::BasicCard, CardRarity::Gold, set, rules)?);
 
         // Основная карта серебро/золото
-        let basic_card_rarity = match rules.basic_card_chanse.generate() {
+        let basic_card_rarity = match rules.basic_card_chanse.generate(&mut self.rng) {
             0 => CardRarity::Silver,
             1 => CardRarity::Gold,
             _ => unreachable!(),
         };
-        buster[5] = self.generate_card(CardBusterSlot::BasicCard, basic_card_rarity, rules.set, rules.generate_hall_of_fame()).await?;
+        buster.push(self.generate_rules_card(CardBusterSlot::BasicCard, basic_card_rarity, set, rules)?);
 
         // Основная карта серебро
-        buster[6] = self.generate_card(CardBusterSlot::BasicCard, CardRarity::Silver, rules.set, rules.generate_hall_of_fame()).await?;
-        buster[7] = self.generate_card(CardBusterSlot::BasicCard, CardRarity::Silver, rules.set, rules.generate_hall_of_fame()).await?;
-        buster[8] = self.generate_card(CardBusterSlot::BasicCard, CardRarity::Silver, rules.set, rules.generate_hall_of_fame()).await?;
+        for _ in 6..9 {
+            buster.push(self.generate_rules_card(CardBusterSlot::BasicCard, CardRarity::Silver, set, rules)?);
+        }
 
         // Основные карты бронза
-        for i in 9..BUSTER_SIZE {
-            buster[i] = self.generate_card(CardBusterSlot::BasicCard, CardRarity::Bronze, rules.set, rules.generate_hall_of_fame()).await?;
-        }   
+        for _ in 9..BUSTER_SIZE {
+            buster.push(self.generate_rules_card(CardBusterSlot::BasicCard, CardRarity::Bronze, set, rules)?);
+        }
 
-        Some(buster)
+        buster.try_into().ok()
     }
 }
 
-#[derive(Debug)]
+/// Что делать, если выпал Зал Славы, а в нем нет карт нужного слота и редкости
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HallOfFamePolicy {
+    /// Карта берется из основного сета, поэтому фактический шанс Зала Славы ниже заданного
+    #[default]
+    Fallback,
+    /// Карта берется из Зала Славы той же категории, но другой редкости, пропорционально весам.
+    /// Если в Зале Славы нет карт этого слота, карта берется из основного сета
+    RerollRarity,
+    /// Такие пулы не участвуют в броске на Зал Славы, и замена не считается
+    Skip,
+}
+
+impl HallOfFamePolicy {
+    /// Ключ политики в БД
+    pub fn key(&self) -> &'static str {
+        match self {
+            HallOfFamePolicy::Fallback => "fallback",
+            HallOfFamePolicy::RerollRarity => "reroll_rarity",
+            HallOfFamePolicy::Skip => "skip",
+        }
+    }
+}
+
+impl Localize for HallOfFamePolicy {
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let translation = match self {
+            HallOfFamePolicy::Fallback => Translation { ru: "замена основным сетом", en: "fallback to set" },
+            HallOfFamePolicy::RerollRarity => Translation { ru: "переброс редкости", en: "reroll rarity" },
+            HallOfFamePolicy::Skip => Translation { ru: "пропуск", en: "skip" },
+        };
+        f.pad(translation.get(locale))
+    }
+}
+
+impl TryFrom<String> for HallOfFamePolicy {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "fallback" => Ok(HallOfFamePolicy::Fallback),
+            "reroll_rarity" => Ok(HallOfFamePolicy::RerollRarity),
+            "skip" => Ok(HallOfFamePolicy::Skip),
+            p => Err(format!("Error. Not found hall of fame policy: {}", p).into())
+        }
+    }
+}
+
+/// При десериализации проверяется так же, как в `BusterRules::new`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "BusterRulesData")]
 pub struct BusterRules {
-    set: CardSet,
+    set: SetCode,
+    hall_of_fame_chanse: f64,
+    hall_of_fame_policy: HallOfFamePolicy,
+    hero_chanse: Distribution<2>,
+    command_chanse_bronze_silver: Distribution<2>,
+    command_chanse_random: Distribution<3>,
+    basic_card_chanse: Distribution<2>,
+}
+
+/// Непроверенные правила из JSON или TOML
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BusterRulesData {
+    set: SetCode,
     hall_of_fame_chanse: f64,
+    #[serde(default)]
+    hall_of_fame_policy: HallOfFamePolicy,
     hero_chanse: Distribution<2>,
     command_chanse_bronze_silver: Distribution<2>,
     command_chanse_random: Distribution<3>,
     basic_card_chanse: Distribution<2>,
 }
 
+impl TryFrom<BusterRulesData> for BusterRules {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(data: BusterRulesData) -> Result<Self, Self::Error> {
+        Ok(BusterRules::new(
+            data.set,
+            data.hall_of_fame_chanse,
+            data.hero_chanse,
+            data.command_chanse_bronze_silver,
+            data.command_chanse_random,
+            data.basic_card_chanse,
+        )?.with_hall_of_fame_policy(data.hall_of_fame_policy))
+    }
+}
+
 impl BusterRules {
     pub fn new(
-        set: CardSet,
+        set: SetCode,
         hall_of_fame_chanse: f64,
         hero_chanse: Distribution<2>,
         command_chanse_bronze_silver: Distribution<2>,
         command_chanse_random: Distribution<3>,
         basic_card_chanse: Distribution<2>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        if hall_of_fame_chanse < 0.0 || hall_of_fame_chanse > 1.0 {
+        if !(0.0..=1.0).contains(&hall_of_fame_chanse) {
             return Err("hall_of_fame_chanse must be between 0.0 and 1.0".into());
         }
         Ok(BusterRules {
             set,
             hall_of_fame_chanse,
+            hall_of_fame_policy: HallOfFamePolicy::default(),
             hero_chanse,
             command_chanse_bronze_silver,
             command_chanse_random,
@@ -316,46 +781,42 @@ impl BusterRules {
         })
     }
 
-    fn generate_hall_of_fame(&self) -> bool {
-        let mut rng = rand::rng();
-        rng.random::<f64>() < self.hall_of_fame_chanse
+    pub fn with_hall_of_fame_policy(mut self, policy: HallOfFamePolicy) -> Self {
+        self.hall_of_fame_policy = policy;
+        self
     }
-}
 
-#[derive(Debug)]
-pub struct Distribution<const SIZE: usize> {
-    values: [f64; SIZE],
-}
+    pub fn hall_of_fame_policy(&self) -> HallOfFamePolicy {
+        self.hall_of_fame_policy
+    }
 
-impl<const SIZE: usize> Distribution<SIZE> {
-    pub fn new(values: [f64; SIZE]) -> Result<Distribution<SIZE>, Box<dyn std::error::Error>> {
-        if values.iter().any(|&v| v < 0.0 || v > 1.0) {
-            return Err("Distribution values must be between 0.0 and 1.0".into());
-        }
-        if (1.0 - values.iter().sum::<f64>()).abs() > f64::EPSILON {
-            return Err("Sum of distribution values must be 1.0".into());
-        }
-        Ok(Distribution {
-            values,
-        })
+    /// Код основного сета бустера
+    pub fn set(&self) -> &SetCode {
+        &self.set
     }
 
-    fn values(&self) -> &[f64; SIZE] {
-        &self.values
+    /// Слоты бустера по порядку и вероятности редкостей в каждом из них,
+    /// в том же порядке, что и в `CardBase::generate_buster`
+    pub fn layout(&self) -> Vec<(CardBusterSlot, Vec<(CardRarity, f64)>)> {
+        let hero = self.hero_chanse.values();
+        let command_random = self.command_chanse_random.values();
+        let command_bronze_silver = self.command_chanse_bronze_silver.values();
+        let basic_card = self.basic_card_chanse.values();
+        let mut layout = vec![
+            (CardBusterSlot::Hero, vec![(CardRarity::Silver, hero[0]), (CardRarity::Gold, hero[1])]),
+            (CardBusterSlot::Command, vec![(CardRarity::Bronze, command_random[0]), (CardRarity::Silver, command_random[1]), (CardRarity::Gold, command_random[2])]),
+            (CardBusterSlot::Command, vec![(CardRarity::Bronze, command_bronze_silver[0]), (CardRarity::Silver, command_bronze_silver[1])]),
+            (CardBusterSlot::Command, vec![(CardRarity::Bronze, 1.0)]),
+            (CardBusterSlot::BasicCard, vec![(CardRarity::Gold, 1.0)]),
+            (CardBusterSlot::BasicCard, vec![(CardRarity::Silver, basic_card[0]), (CardRarity::Gold, basic_card[1])]),
+        ];
+        layout.extend((6..9).map(|_| (CardBusterSlot::BasicCard, vec![(CardRarity::Silver, 1.0)])));
+        layout.extend((9..BUSTER_SIZE).map(|_| (CardBusterSlot::BasicCard, vec![(CardRarity::Bronze, 1.0)])));
+        layout
     }
 
-    /// Возвращает usize от 0 до SIZE-1 в соответствии с распределением
-    fn generate(&self) -> usize {
-        let mut rng = rand::rng();
-        let random_value: f64 = rng.random();
-        let mut cumulative_sum = 0.0;
-        for (index, &value) in self.values().iter().enumerate() {
-            cumulative_sum += value;
-            if random_value <= cumulative_sum {
-                return index;
-            }
-        }
-        SIZE - 1
+    fn generate_hall_of_fame<R: Rng + ?Sized>(&self, rng: &mut R) -> bool {
+        rng.random::<f64>() < self.hall_of_fame_chanse
     }
 }
 
@@ -364,134 +825,11 @@ mod tests {
     use tokio::test; 
     use super::*;
 
-    #[test]
-    async fn create_distribution_when_valid_values_then_ok1() {
-        // GIVEN
-        let my_distribution = [0.2, 0.5, 0.3];
-        // WHEN
-        let result = Distribution::<3>::new(my_distribution);
-        // THEN
-        assert!(result.is_ok());
-    }
-
-    #[test]
-    async fn create_distribution_when_valid_values_then_ok2() {
-        // GIVEN
-        let my_distribution = [1.0, 0.0, 0.0, 0.0];
-        // WHEN
-        let result = Distribution::<4>::new(my_distribution);
-        // THEN
-        assert!(result.is_ok());
-    }
-
-    #[test]
-    async fn create_distribution_when_valid_values_then_ok3() {
-        // GIVEN
-        let my_distribution = [0.001, 0.999];
-        // WHEN
-        let result = Distribution::<2>::new(my_distribution);
-        // THEN
-        assert!(result.is_ok());
-    }
-
-    #[test]
-    async fn create_distribution_when_wrong_sum_then_err1() {
-        // GIVEN
-        let my_distribution = [0.5, 0.5, 0.5, 0.5];
-        // WHEN
-        let result = Distribution::<4>::new(my_distribution);
-        // THEN
-        assert!(result.is_err());
-    }
-
-    #[test]
-    async fn create_distribution_when_wrong_sum_then_err2() {
-        // GIVEN
-        let my_distribution = [0.2, 0.1, 0.2, 0.1];
-        // WHEN
-        let result = Distribution::<4>::new(my_distribution);
-        // THEN
-        assert!(result.is_err());
-    }
-
-    #[test]
-    async fn create_distribution_when_wrong_element_then_err1() {
-        // GIVEN
-        let my_distribution = [0.2, 20.0, 0.2, 0.1];
-        // WHEN
-        let result = Distribution::<4>::new(my_distribution);
-        // THEN
-        assert!(result.is_err());
-    }
-
-    #[test]
-    async fn create_distribution_when_wrong_element_then_err2() {
-        // GIVEN
-        let my_distribution = [0.1, 0.2, 0.3, -0.4];
-        // WHEN
-        let result = Distribution::<4>::new(my_distribution);
-        // THEN
-        assert!(result.is_err());
-    }
-
-    #[test]
-    async fn get_values_when_valid_values_then_ok() {
-        // GIVEN
-        let my_distribution = [0.1, 0.2, 0.3, 0.4];
-        // WHEN
-        let result = Distribution::<4>::new(my_distribution);
-        let d = result.unwrap();
-        // THEN
-        assert_eq!(d.values(), &[0.1, 0.2, 0.3, 0.4]);
-    }
-
-    #[test]
-    async fn generate_with_guaranteed_distribution1() {
-        // GIVEN
-        let my_distribution = [1.0, 0.0, 0.0, 0.0, 0.0];
-        // WHEN
-        let result = Distribution::<5>::new(my_distribution);
-        let d = result.unwrap();
-        let value = d.generate();
-        // THEN
-        assert_eq!(value, 0);
-    }
-
-    #[test]
-    async fn generate_with_guaranteed_distribution2() {
-        // GIVEN
-        let my_distribution = [0.0, 0.0, 1.0, 0.0, 0.0];
-        // WHEN
-        let result = Distribution::<5>::new(my_distribution);
-        let d = result.unwrap();
-        let value = d.generate();
-        // THEN
-        assert_eq!(value, 2);
-    }
+    use crate::fixture::{self, FixtureBuilder};
 
-    #[test]
-    async fn generate_with_guaranteed_distribution3() {
-        // GIVEN
-        let my_distribution = [0.0, 0.0, 0.0, 0.0, 1.0];
-        // WHEN
-        let result = Distribution::<5>::new(my_distribution);
-        let d = result.unwrap();
-        let value = d.generate();
-        // THEN
-        assert_eq!(value, 4);
-    }
-
-    #[test]
-    async fn generate_with_random_distribution() {
-        // GIVEN
-        let my_distribution = [0.2, 0.2, 0.2, 0.2, 0.2];
-        // WHEN
-        let result = Distribution::<5>::new(my_distribution);
-        let d = result.unwrap();
-        let value = d.generate();
-        // THEN
-        assert!(value <= 4);
-    }
+    /// Идентификаторы сетов из миграции fill_dicts
+    const KOV: SetId = SetId(2);
+    const HALL_OF_FAME: SetId = SetId(3);
 
     #[test]
     async fn create_buster_rules_with_valid_values() {
@@ -499,7 +837,7 @@ mod tests {
         let hall_of_fame_chanse = 0.1;
         let my_distribution2 = [0.2, 0.8];
         let my_distribution3 = [0.2, 0.4, 0.4];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -523,7 +861,7 @@ mod tests {
         let hall_of_fame_chanse = 1.001;
         let my_distribution2 = [0.2, 0.8];
         let my_distribution3 = [0.2, 0.4, 0.4];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -547,7 +885,7 @@ mod tests {
         let hall_of_fame_chanse = -0.1;
         let my_distribution2 = [0.2, 0.8];
         let my_distribution3 = [0.2, 0.4, 0.4];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -576,8 +914,8 @@ mod tests {
     async fn generate_buster_check_commands() {
         let buster = standart_buster_when_given().await;
         // THEN
-        for i in 1..=3 {
-            assert_eq!(buster[i].buster_slot, CardBusterSlot::Command);
+        for card in &buster[1..=3] {
+            assert_eq!(card.buster_slot, CardBusterSlot::Command);
         }
     }
 
@@ -585,8 +923,8 @@ mod tests {
     async fn generate_buster_check_basic_cards() {
         let buster = standart_buster_when_given().await;
         // THEN
-        for i in 4..BUSTER_SIZE {
-            assert_eq!(buster[i].buster_slot, CardBusterSlot::BasicCard);
+        for card in &buster[4..] {
+            assert_eq!(card.buster_slot, CardBusterSlot::BasicCard);
         }
     }
 
@@ -602,9 +940,9 @@ mod tests {
         assert_eq!(buster[6].rarity, CardRarity::Silver);
         assert_eq!(buster[7].rarity, CardRarity::Silver);
         assert_eq!(buster[8].rarity, CardRarity::Silver);
-        for i in 9..BUSTER_SIZE {
-            assert_eq!(buster[i].rarity, CardRarity::Bronze);
-        }   
+        for card in &buster[9..] {
+            assert_eq!(card.rarity, CardRarity::Bronze);
+        }
     }
 
     #[test]
@@ -613,7 +951,7 @@ mod tests {
         let hall_of_fame_chanse = 0.5;
         let my_distribution2 = [0.0, 1.0];
         let my_distribution3 = [0.0, 0.0, 1.0];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -627,9 +965,9 @@ mod tests {
             command_chanse3,
             basic_card_chanse,
         ).unwrap();
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let buster = card_base.generate_buster(&rules).await.unwrap();
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let buster = card_base.generate_buster(&rules).unwrap();
         // THEN
         buster.iter().for_each(|c| println!("{:?} {:?}", c.buster_slot, c.rarity));
         assert_eq!(buster[0].rarity, CardRarity::Gold);
@@ -644,7 +982,7 @@ mod tests {
         let hall_of_fame_chanse = 0.5;
         let my_distribution2 = [1.0, 0.0];
         let my_distribution3 = [1.0, 0.0, 0.0];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -658,9 +996,9 @@ mod tests {
             command_chanse3,
             basic_card_chanse,
         ).unwrap();
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let buster = card_base.generate_buster(&rules).await.unwrap();
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let buster = card_base.generate_buster(&rules).unwrap();
         // THEN
         assert_eq!(buster[0].rarity, CardRarity::Silver);
         assert_eq!(buster[1].rarity, CardRarity::Bronze);
@@ -674,7 +1012,7 @@ mod tests {
         let hall_of_fame_chanse = 1.0;
         let my_distribution2 = [0.0, 1.0];
         let my_distribution3 = [0.0, 0.0, 1.0];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -688,13 +1026,13 @@ mod tests {
             command_chanse3,
             basic_card_chanse,
         ).unwrap();
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let buster = card_base.generate_buster(&rules).await.unwrap();
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let buster = card_base.generate_buster(&rules).unwrap();
         // THEN
-        assert_eq!(buster[0].set, CardSet::HallOfFame);
-        assert_eq!(buster[4].set, CardSet::HallOfFame);
-        assert_eq!(buster[5].set, CardSet::HallOfFame);
+        assert_eq!(buster[0].set.id, HALL_OF_FAME);
+        assert_eq!(buster[4].set.id, HALL_OF_FAME);
+        assert_eq!(buster[5].set.id, HALL_OF_FAME);
     }
 
     #[test]
@@ -703,7 +1041,7 @@ mod tests {
         let hall_of_fame_chanse = 0.0;
         let my_distribution2 = [0.5, 0.5];
         let my_distribution3 = [0.8, 0.15, 0.05];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -717,12 +1055,12 @@ mod tests {
             command_chanse3,
             basic_card_chanse,
         ).unwrap();
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let buster = card_base.generate_buster(&rules).await.unwrap();
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let buster = card_base.generate_buster(&rules).unwrap();
         // THEN
         for card in buster.iter() {
-            assert_ne!(card.set, CardSet::HallOfFame);
+            assert_ne!(card.set.id, HALL_OF_FAME);
         }
     }
 
@@ -731,12 +1069,12 @@ mod tests {
         // GIVEN
         let slot = CardBusterSlot::Hero;
         let rarity = CardRarity::Silver;
-        let set = CardSet::KOV;
+        let set = KOV;
         let hall_of_fame = false;
         // WHEN
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let card = card_base.generate_card(slot, rarity, set, hall_of_fame).await;
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(slot, rarity, set, hall_of_fame, HallOfFamePolicy::Fallback);
         // THEN
         assert!(card.is_some());
     }
@@ -746,46 +1084,46 @@ mod tests {
         // GIVEN
         let slot = CardBusterSlot::Hero;
         let rarity = CardRarity::Silver;
-        let set = CardSet::KOV;
+        let set = KOV;
         let hall_of_fame = false;
         // WHEN
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let card = card_base.generate_card(slot, rarity, set, hall_of_fame).await.unwrap();
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(slot, rarity, set, hall_of_fame, HallOfFamePolicy::Fallback).unwrap();
         // THEN
         assert_eq!(card.buster_slot, CardBusterSlot::Hero);
         assert_eq!(card.rarity, CardRarity::Silver);
-        assert_eq!(card.set, CardSet::KOV);
+        assert_eq!(card.set.id, KOV);
     }
 
-    /// На январь 2026 в Зале Славы нет приказов
+    /// В Зале Славы фикстуры нет приказов, а замена берется из того же Зала Славы
     #[test]
     async fn generate_card_with_hall_of_fame_command() {
         // GIVEN
         let slot = CardBusterSlot::Command;
         let rarity = CardRarity::Gold;
-        let set = CardSet::HallOfFame;
+        let set = HALL_OF_FAME;
         let hall_of_fame = true;
         // WHEN
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let card = card_base.generate_card(slot, rarity, set, hall_of_fame).await;
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(slot, rarity, set, hall_of_fame, HallOfFamePolicy::Fallback);
         // THEN
         assert!(card.is_none());
     }
 
-    /// На январь 2026 в Зале Славы все карты золотой редкости
+    /// В Зале Славы фикстуры нет серебряных карт, а замена берется из того же Зала Славы
     #[test]
     async fn generate_card_with_hall_of_fame_silver() {
         // GIVEN
         let slot = CardBusterSlot::BasicCard;
         let rarity = CardRarity::Silver;
-        let set = CardSet::HallOfFame;
+        let set = HALL_OF_FAME;
         let hall_of_fame = true;
         // WHEN
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let card = card_base.generate_card(slot, rarity, set, hall_of_fame).await;
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(slot, rarity, set, hall_of_fame, HallOfFamePolicy::Fallback);
         // THEN
         assert!(card.is_none());
     }
@@ -795,14 +1133,14 @@ mod tests {
         // GIVEN
         let slot = CardBusterSlot::BasicCard;
         let rarity = CardRarity::Gold;
-        let set = CardSet::KOV;
+        let set = KOV;
         let hall_of_fame = true;
         // WHEN
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let card = card_base.generate_card(slot, rarity, set, hall_of_fame).await.unwrap();
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(slot, rarity, set, hall_of_fame, HallOfFamePolicy::Fallback).unwrap();
         // THEN
-        assert_eq!(card.set, CardSet::HallOfFame);
+        assert_eq!(card.set.id, HALL_OF_FAME);
     }
 
     #[test]
@@ -810,14 +1148,447 @@ mod tests {
         // GIVEN
         let slot = CardBusterSlot::BasicCard;
         let rarity = CardRarity::Gold;
-        let set = CardSet::KOV;
+        let set = KOV;
         let hall_of_fame = false;
         // WHEN
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let card = card_base.generate_card(slot, rarity, set, hall_of_fame).await.unwrap();
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(slot, rarity, set, hall_of_fame, HallOfFamePolicy::Fallback).unwrap();
+        // THEN
+        assert_eq!(card.set.id, KOV);
+    }
+
+    /// В Зале Славы фикстуры нет приказов, поэтому карта берется из основного сета
+    #[test]
+    async fn generate_card_with_hall_of_fame_when_pool_empty_then_fallback_to_set() {
+        // GIVEN
+        let slot = CardBusterSlot::Command;
+        let rarity = CardRarity::Silver;
+        let set = KOV;
+        let hall_of_fame = true;
+        // WHEN
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(slot, rarity, set, hall_of_fame, HallOfFamePolicy::Fallback).unwrap();
+        // THEN
+        assert_eq!(card.set.id, KOV);
+        assert_eq!(card.buster_slot, CardBusterSlot::Command);
+        assert_eq!(card.rarity, CardRarity::Silver);
+    }
+
+    #[test]
+    async fn generate_card_when_pool_empty_then_none() {
+        // GIVEN
+        let db_pull = FixtureBuilder::standard()
+            .without(fixture::KOV, CardBusterSlot::Hero, CardRarity::Silver)
+            .build()
+            .await;
+        // WHEN
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let card = card_base.generate_card(CardBusterSlot::Hero, CardRarity::Silver, KOV, false, HallOfFamePolicy::Fallback);
+        // THEN
+        assert!(card.is_none());
+    }
+
+    #[test]
+    async fn generate_buster_when_required_pool_empty_then_none() {
+        // GIVEN
+        let rules = fixture::rules(0.0);
+        let db_pull = FixtureBuilder::standard()
+            .without(fixture::KOV, CardBusterSlot::Command, CardRarity::Bronze)
+            .build()
+            .await;
+        // WHEN
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let buster = card_base.generate_buster(&rules);
+        // THEN
+        assert!(buster.is_none());
+    }
+
+    #[test]
+    async fn generate_buster_when_unused_pool_empty_then_some() {
+        // GIVEN
+        let hero_chanse = Distribution::<2>::new([1.0, 0.0]).unwrap();
+        let command_chanse2 = Distribution::<2>::new([1.0, 0.0]).unwrap();
+        let command_chanse3 = Distribution::<3>::new([1.0, 0.0, 0.0]).unwrap();
+        let basic_card_chanse = Distribution::<2>::new([1.0, 0.0]).unwrap();
+        let rules = BusterRules::new(SetCode::from(fixture::KOV), 0.0, hero_chanse, command_chanse2, command_chanse3, basic_card_chanse).unwrap();
+        let db_pull = FixtureBuilder::standard()
+            .without(fixture::KOV, CardBusterSlot::Hero, CardRarity::Gold)
+            .build()
+            .await;
+        // WHEN
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let buster = card_base.generate_buster(&rules);
+        // THEN
+        assert!(buster.is_some());
+    }
+
+    #[test]
+    async fn buster_rules_layout_matches_buster_size() {
+        // GIVEN
+        let rules = fixture::rules(0.0);
+        // WHEN
+        let layout = rules.layout();
+        // THEN
+        assert_eq!(layout.len(), BUSTER_SIZE);
+        for (_, rarities) in layout.iter() {
+            assert!((rarities.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    async fn pull_rates_without_hall_of_fame_sum_to_buster_size() {
+        // GIVEN
+        let rules = fixture::rules(0.0);
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        // WHEN
+        let rates = card_pool.pull_rates(&rules).unwrap();
+        // THEN
+        let total: f64 = rates.iter().map(|r| r.per_buster).sum();
+        assert!((total - BUSTER_SIZE as f64).abs() < 1e-9);
+        assert!(rates.iter().all(|r| r.card.set.id == KOV));
+        // В фикстуре по две карты в пуле, серебряный герой выпадает с шансом 0.8
+        let silver_hero = rates.iter().find(|r| r.card.buster_slot == CardBusterSlot::Hero && r.card.rarity == CardRarity::Silver).unwrap();
+        assert!((silver_hero.per_buster - 0.4).abs() < 1e-9);
+        assert!((silver_hero.at_least_one - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    async fn pull_rates_with_hall_of_fame_fallback() {
+        // GIVEN
+        let rules = fixture::rules(0.5);
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        // WHEN
+        let rates = card_pool.pull_rates(&rules).unwrap();
+        // THEN
+        let total: f64 = rates.iter().map(|r| r.per_buster).sum();
+        assert!((total - BUSTER_SIZE as f64).abs() < 1e-9);
+        // Приказов в Зале Славы нет, поэтому бронзовые приказы КОВ не теряют шанс
+        let bronze_commands: f64 = rates.iter()
+            .filter(|r| r.card.buster_slot == CardBusterSlot::Command && r.card.rarity == CardRarity::Bronze)
+            .map(|r| r.per_buster)
+            .sum();
+        assert!((bronze_commands - (0.7 + 0.75 + 1.0)).abs() < 1e-9);
+        // Золотая основная карта всегда выпадает в пятом слоте, половину раз из Зала Славы
+        let hall_of_fame_basic: f64 = rates.iter()
+            .filter(|r| r.card.set.id == HALL_OF_FAME && r.card.buster_slot == CardBusterSlot::BasicCard)
+            .map(|r| r.per_buster)
+            .sum();
+        assert!((hall_of_fame_basic - 0.5 * (1.0 + 0.3)).abs() < 1e-9);
+    }
+
+    #[test]
+    async fn pull_rates_with_short_printed_card() {
+        // GIVEN
+        let rules = fixture::rules(0.0);
+        let db_pull = FixtureBuilder::standard()
+            .weighted_card(fixture::KOV, CardBusterSlot::Hero, CardRarity::Gold, 0.5)
+            .build()
+            .await;
+        let card_pool = CardPool::load(&db_pull).await.unwrap();
+        // WHEN
+        let rates = card_pool.pull_rates(&rules).unwrap();
+        // THEN
+        let gold_heroes: Vec<&PullRate> = rates.iter()
+            .filter(|r| r.card.buster_slot == CardBusterSlot::Hero && r.card.rarity == CardRarity::Gold)
+            .collect();
+        assert_eq!(gold_heroes.len(), 3);
+        let short_printed = gold_heroes.iter().find(|r| r.card.weight == 0.5).unwrap();
+        assert!((short_printed.per_buster - 0.2 * 0.5 / 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    async fn generate_card_with_zero_weight_then_never_chosen() {
+        // GIVEN
+        let db_pull = FixtureBuilder::standard()
+            .without(fixture::KOV, CardBusterSlot::Hero, CardRarity::Gold)
+            .cards(fixture::KOV, CardBusterSlot::Hero, CardRarity::Gold, 1)
+            .weighted_card(fixture::KOV, CardBusterSlot::Hero, CardRarity::Gold, 0.0)
+            .build()
+            .await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        // WHEN
+        let mut weights = Vec::new();
+        for _ in 0..100 {
+            weights.push(card_base.generate_card(CardBusterSlot::Hero, CardRarity::Gold, KOV, false, HallOfFamePolicy::Fallback).unwrap().weight);
+        }
+        // THEN
+        assert!(weights.iter().all(|&w| w == DEFAULT_CARD_WEIGHT));
+    }
+
+    #[test]
+    async fn card_pool_load_with_infinite_weight_then_error_with_card() {
+        // GIVEN
+        let db_pull = FixtureBuilder::standard()
+            .weighted_card(fixture::KOV, CardBusterSlot::Hero, CardRarity::Gold, f64::INFINITY)
+            .build()
+            .await;
+        // WHEN
+        let error = CardPool::load(&db_pull).await.unwrap_err();
+        // THEN
+        assert!(error.to_string().contains("card КОВ 17"), "{}", error);
+    }
+
+    #[test]
+    async fn insert_card_with_negative_weight_then_rejected_by_db() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // WHEN
+        let result = sqlx::query("UPDATE cards SET weight = -1 WHERE id_in_set = 1").execute(&db_pull).await;
+        // THEN
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn card_pool_shared_between_threads() {
+        // GIVEN
+        let rules = fixture::rules(0.5);
+        let card_pool = Arc::new(CardPool::load(&fixture::standard().await).await.unwrap());
+        // WHEN
+        let busters: Vec<_> = (0..4u64)
+            .map(|seed| {
+                let card_pool = card_pool.clone();
+                std::thread::spawn(move || CardBase::from_pool_with_seed(card_pool, seed).generate_buster(&fixture::rules(0.5)))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect();
+        // THEN
+        assert!(busters.iter().all(|b| b.is_some()));
+        let mut card_base = CardBase::from_pool_with_seed(card_pool, 0);
+        assert_eq!(
+            card_base.generate_buster(&rules).unwrap().map(|c| c.name),
+            busters[0].clone().unwrap().map(|c| c.name),
+        );
+    }
+
+    #[test]
+    async fn card_pool_load_sets_then_only_requested_and_hall_of_fame() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // WHEN
+        let card_pool = CardPool::load_sets(&db_pull, &[KOV]).await.unwrap();
+        // THEN
+        assert!(card_pool.has_set(KOV));
+        assert!(card_pool.has_set(HALL_OF_FAME));
+        assert!(!card_pool.has_set(SetId(1)));
+    }
+
+    #[test]
+    async fn card_pool_load_sets_with_unknown_set_then_error() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // WHEN
+        let card_pool = CardPool::load_sets(&db_pull, &[SetId(100)]).await;
+        // THEN
+        assert!(card_pool.is_err());
+    }
+
+    #[test]
+    async fn generate_buster_when_set_not_loaded_then_none() {
+        // GIVEN
+        let rules = fixture::rules(0.0);
+        let card_pool = CardPool::load_sets(&fixture::standard().await, &[]).await.unwrap();
+        let mut card_base = CardBase::from_pool(Arc::new(card_pool));
+        // WHEN
+        let buster = card_base.generate_buster(&rules);
+        // THEN
+        assert!(buster.is_none());
+        assert!(card_base.pool().pull_rates(&rules).is_err());
+    }
+
+    #[test]
+    async fn hall_of_fame_rates_with_fallback_policy() {
+        // GIVEN
+        let rules = fixture::rules(0.1);
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        // WHEN
+        let rates = card_pool.hall_of_fame_rates(&rules);
+        // THEN
+        assert_eq!(rates.len(), BUSTER_SIZE);
+        // Золотые герои в Зале Славы есть, серебряных нет
+        assert!((rates[0].chanse - 0.02).abs() < 1e-9);
+        assert!((rates[0].fallback - 0.08).abs() < 1e-9);
+        // Приказов в Зале Славы нет
+        assert_eq!(rates[1].chanse, 0.0);
+        assert!((rates[1].fallback - 0.1).abs() < 1e-9);
+        assert!((rates[4].chanse - 0.1).abs() < 1e-9);
+        assert_eq!(rates[4].fallback, 0.0);
+    }
+
+    #[test]
+    async fn hall_of_fame_rates_with_reroll_policy() {
+        // GIVEN
+        let rules = fixture::rules(0.1).with_hall_of_fame_policy(HallOfFamePolicy::RerollRarity);
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        // WHEN
+        let rates = card_pool.hall_of_fame_rates(&rules);
+        // THEN
+        // Серебряный герой перебрасывается на золотого, а приказов в Зале Славы нет совсем
+        assert!((rates[0].chanse - 0.1).abs() < 1e-9);
+        assert_eq!(rates[0].fallback, 0.0);
+        assert!((rates[1].fallback - 0.1).abs() < 1e-9);
+        assert!((rates[BUSTER_SIZE - 1].chanse - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    async fn hall_of_fame_rates_with_skip_policy_then_no_fallback() {
+        // GIVEN
+        let rules = fixture::rules(0.1).with_hall_of_fame_policy(HallOfFamePolicy::Skip);
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        // WHEN
+        let rates = card_pool.hall_of_fame_rates(&rules);
+        // THEN
+        assert!(rates.iter().all(|r| r.fallback == 0.0));
+        assert_eq!(rates[1].chanse, 0.0);
+    }
+
+    #[test]
+    async fn generate_card_with_reroll_policy_then_other_rarity_from_hall_of_fame() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        // WHEN
+        let card = card_base.generate_card(CardBusterSlot::BasicCard, CardRarity::Bronze, KOV, true, HallOfFamePolicy::RerollRarity).unwrap();
+        // THEN
+        assert_eq!(card.set.id, HALL_OF_FAME);
+        assert_eq!(card.buster_slot, CardBusterSlot::BasicCard);
+        assert_eq!(card.rarity, CardRarity::Gold);
+        assert_eq!(card_base.hall_of_fame_fallbacks(), 0);
+    }
+
+    #[test]
+    async fn generate_buster_counts_hall_of_fame_fallbacks() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        let mut skipping = CardBase::new(fixture::standard().await).await.unwrap();
+        // WHEN
+        card_base.generate_buster(&fixture::rules(1.0)).unwrap();
+        skipping.generate_buster(&fixture::rules(1.0).with_hall_of_fame_policy(HallOfFamePolicy::Skip)).unwrap();
+        // THEN
+        // Три приказа, три серебряные и девять бронзовых основных карт всегда заменяются
+        assert!((15..=17).contains(&card_base.hall_of_fame_fallbacks()));
+        assert_eq!(skipping.hall_of_fame_fallbacks(), 0);
+    }
+
+    #[test]
+    async fn pull_rates_with_reroll_policy() {
+        // GIVEN
+        let rules = fixture::rules(0.5).with_hall_of_fame_policy(HallOfFamePolicy::RerollRarity);
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        // WHEN
+        let rates = card_pool.pull_rates(&rules).unwrap();
+        // THEN
+        let total: f64 = rates.iter().map(|r| r.per_buster).sum();
+        assert!((total - BUSTER_SIZE as f64).abs() < 1e-9);
+        // Все 14 основных карт наполовину перебрасываются в Зал Славы
+        let hall_of_fame_basic: f64 = rates.iter()
+            .filter(|r| r.card.set.id == HALL_OF_FAME && r.card.buster_slot == CardBusterSlot::BasicCard)
+            .map(|r| r.per_buster)
+            .sum();
+        assert!((hall_of_fame_basic - 0.5 * 14.0).abs() < 1e-9);
+    }
+
+    #[test]
+    async fn serialize_card_with_stable_identifiers() {
+        // GIVEN
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        let card = card_pool.bucket(CardBusterSlot::BasicCard, KOV, CardRarity::Silver).unwrap().cards[0].clone();
+        // WHEN
+        let json = serde_json::to_value(&card).unwrap();
+        let restored = serde_json::from_value::<CardData>(json.clone()).unwrap().resolve(card_pool.sets()).unwrap();
         // THEN
-        assert_eq!(card.set, CardSet::KOV);
+        assert_eq!(json["rarity"], "silver");
+        assert_eq!(json["buster_slot"], "basic_card");
+        assert_eq!(json["set"], "КОВ");
+        assert_eq!(restored.name, card.name);
+        assert_eq!(restored.set, card.set);
+    }
+
+    #[test]
+    async fn deserialize_card_with_invalid_weight_or_unknown_set_then_error() {
+        // GIVEN
+        let sets = CardPool::load(&fixture::standard().await).await.unwrap().sets().clone();
+        let json = serde_json::json!({
+            "name": "Лучник",
+            "id_in_set": 3,
+            "rarity": "silver",
+            "buster_slot": "basic_card",
+            "set": "КОВ",
+        });
+        let with = |key: &str, value: serde_json::Value| {
+            let mut json = json.clone();
+            json[key] = value;
+            serde_json::from_value::<CardData>(json)
+        };
+        // THEN
+        assert_eq!(serde_json::from_value::<CardData>(json.clone()).unwrap().weight, DEFAULT_CARD_WEIGHT);
+        assert!(with("weight", serde_json::json!(-1.0)).is_err());
+        assert!(with("weight", serde_json::json!(0.0)).is_ok());
+        assert!(with("set", serde_json::json!(2)).is_err());
+        assert!(with("set", serde_json::json!("НЕТ")).unwrap().resolve(&sets).is_err());
+    }
+
+    #[test]
+    async fn db_names_round_trip() {
+        for rarity in CardRarity::iter() {
+            assert_eq!(CardRarity::try_from(rarity.db_name().to_string()).unwrap(), rarity);
+        }
+        for slot in CardBusterSlot::iter() {
+            assert_eq!(CardBusterSlot::try_from(slot.db_name().to_string()).unwrap(), slot);
+        }
+        assert!(CardRarity::try_from("Платина".to_string()).is_err());
+        assert!(CardBusterSlot::try_from("Существо".to_string()).is_err());
+    }
+
+    #[test]
+    async fn buster_rules_toml_round_trip() {
+        // GIVEN
+        let rules = fixture::rules(0.02).with_hall_of_fame_policy(HallOfFamePolicy::RerollRarity);
+        // WHEN
+        let text = toml::to_string(&rules).unwrap();
+        let restored: BusterRules = toml::from_str(&text).unwrap();
+        // THEN
+        assert!(text.contains("hall_of_fame_policy = \"reroll_rarity\""));
+        assert_eq!(restored.set, SetCode::from(fixture::KOV));
+        assert_eq!(restored.hall_of_fame_policy(), HallOfFamePolicy::RerollRarity);
+        assert_eq!(restored.command_chanse_random.values(), rules.command_chanse_random.values());
+    }
+
+    #[test]
+    async fn deserialize_buster_rules_without_policy_then_fallback() {
+        // GIVEN
+        let json = r#"{
+            "set": "КОВ",
+            "hall_of_fame_chanse": 0.02,
+            "hero_chanse": [0.8, 0.2],
+            "command_chanse_bronze_silver": [0.75, 0.25],
+            "command_chanse_random": [0.7, 0.2, 0.1],
+            "basic_card_chanse": [0.7, 0.3]
+        }"#;
+        // WHEN
+        let rules: BusterRules = serde_json::from_str(json).unwrap();
+        // THEN
+        assert_eq!(rules.hall_of_fame_policy(), HallOfFamePolicy::Fallback);
+    }
+
+    #[test]
+    async fn deserialize_buster_rules_when_invalid_then_err() {
+        let valid = serde_json::to_value(fixture::rules(0.02)).unwrap();
+        let with = |key: &str, value: serde_json::Value| {
+            let mut json = valid.clone();
+            json[key] = value;
+            serde_json::from_value::<BusterRules>(json)
+        };
+        assert!(with("hall_of_fame_chanse", serde_json::json!(1.5)).is_err());
+        assert!(with("hero_chanse", serde_json::json!([0.5, 0.6])).is_err());
+        assert!(with("hall_of_fame_policy", serde_json::json!("always")).is_err());
+        assert!(with("unknown", serde_json::json!(1)).is_err());
+        assert!(with("set", serde_json::json!("БАЗ")).is_ok());
+        assert!(with("set", serde_json::json!(2)).is_err());
     }
 
     async fn standart_buster_when_given() -> [Card; BUSTER_SIZE] {
@@ -825,7 +1596,7 @@ mod tests {
         let hall_of_fame_chanse = 0.5;
         let my_distribution2 = [0.5, 0.5];
         let my_distribution3 = [0.8, 0.15, 0.05];
-        let set = CardSet::KOV;
+        let set = SetCode::from(fixture::KOV);
         // WHEN
         let hero_chanse = Distribution::<2>::new(my_distribution2).unwrap();
         let command_chanse2 = Distribution::<2>::new(my_distribution2).unwrap();
@@ -839,9 +1610,8 @@ mod tests {
             command_chanse3,
             basic_card_chanse,
         ).unwrap();
-        let db_pull = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
-        let mut card_base = CardBase::new(db_pull).await;
-        let buster = card_base.generate_buster(&rules).await.unwrap();
-        buster
+        let db_pull = fixture::standard().await;
+        let mut card_base = CardBase::new(db_pull).await.unwrap();
+        card_base.generate_buster(&rules).unwrap()
     }
 }
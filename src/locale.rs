@@ -0,0 +1,140 @@
+//! Язык отображения названий редкостей, слотов и сетов.
+//! Названия в БД и идентификаторы в JSON от языка не зависят
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Переменная окружения с языком для CLI
+pub const LOCALE_ENV: &str = "RESPONSE_SIM_LOCALE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Ru,
+    En,
+}
+
+impl Locale {
+    /// Язык из `RESPONSE_SIM_LOCALE`, по умолчанию русский
+    pub fn from_env() -> Self {
+        std::env::var(LOCALE_ENV).ok().and_then(|l| l.parse().ok()).unwrap_or_default()
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::Ru => "ru",
+            Locale::En => "en",
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = Box<dyn std::error::Error>;
+
+    /// Принимает и полные теги вроде `en-US` или `ru_RU.UTF-8`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.split(['-', '_', '.']).next().unwrap_or_default().to_ascii_lowercase();
+        match language.as_str() {
+            "ru" => Ok(Locale::Ru),
+            "en" => Ok(Locale::En),
+            _ => Err(format!("Error. Not supported locale: {}", s).into()),
+        }
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.code())
+    }
+}
+
+/// Перевод строки на каждый из языков
+#[derive(Debug, Clone, Copy)]
+pub struct Translation {
+    pub ru: &'static str,
+    pub en: &'static str,
+}
+
+impl Translation {
+    pub fn get(&self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::Ru => self.ru,
+            Locale::En => self.en,
+        }
+    }
+}
+
+/// Переводы названий сетов по короткому названию из БД: полное и короткое название
+const SET_TRANSLATIONS: [(&str, Translation, Translation); 3] = [
+    ("БАЗ", Translation { ru: "Базовый", en: "Base Set" }, Translation { ru: "БАЗ", en: "BAS" }),
+    ("КОВ", Translation { ru: "Королевства Ванстера", en: "Kingdoms of Vanster" }, Translation { ru: "КОВ", en: "KOV" }),
+    ("Зал Славы", Translation { ru: "Зал Славы", en: "Hall of Fame" }, Translation { ru: "Зал Славы", en: "Hall of Fame" }),
+];
+
+/// Полное и короткое название сета. `None` для сетов без перевода
+pub(crate) fn set_translation(short_name: &str) -> Option<(Translation, Translation)> {
+    SET_TRANSLATIONS.iter()
+        .find(|(key, _, _)| *key == short_name)
+        .map(|(_, name, short_name)| (*name, *short_name))
+}
+
+/// Значение, которое отображается по-разному на разных языках
+pub trait Localize {
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result;
+
+    /// Обертка для `format!`, ширина и выравнивание сохраняются
+    fn localized(&self, locale: Locale) -> Localized<'_, Self> {
+        Localized { value: self, locale }
+    }
+}
+
+pub struct Localized<'a, T: ?Sized> {
+    value: &'a T,
+    locale: Locale,
+}
+
+impl<T: Localize + ?Sized> Display for Localized<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt_localized(self.locale, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::{CardBusterSlot, CardRarity};
+
+    #[test]
+    async fn parse_locale_tags() {
+        assert_eq!("en".parse::<Locale>().unwrap(), Locale::En);
+        assert_eq!("en-US".parse::<Locale>().unwrap(), Locale::En);
+        assert_eq!("ru_RU.UTF-8".parse::<Locale>().unwrap(), Locale::Ru);
+        assert!("de".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    async fn localized_keeps_padding() {
+        // WHEN
+        let en = format!("[{:8}]", CardRarity::Gold.localized(Locale::En));
+        let ru = format!("[{:8}]", CardRarity::Gold.localized(Locale::Ru));
+        // THEN
+        assert_eq!(en, "[Gold    ]");
+        assert_eq!(ru, "[Золото  ]");
+    }
+
+    #[test]
+    async fn display_uses_default_locale() {
+        assert_eq!(CardBusterSlot::Command.to_string(), "Приказ");
+        assert_eq!(CardBusterSlot::Command.localized(Locale::En).to_string(), "Command");
+    }
+
+    #[test]
+    async fn set_translation_for_unknown_set_then_none() {
+        assert!(set_translation("КОВ").is_some());
+        assert!(set_translation("НОВ").is_none());
+    }
+}
@@ -0,0 +1,354 @@
+//! Именованные наборы правил бустера из таблицы `booster_rules`.
+//! Каждое изменение шансов издателем сохраняется новой версией с датой начала действия,
+//! старые версии не меняются
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{BusterRules, Distribution, HallOfFamePolicy, Locale, Localize, SetCode, locale::Translation};
+
+/// Версия набора правил для сета
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RulesPreset {
+    pub id: i64,
+    pub name: String,
+    pub version: u32,
+    /// Дата начала действия в формате `YYYY-MM-DD`
+    pub effective_from: String,
+    pub rules: BusterRules,
+}
+
+impl Display for RulesPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_localized(Locale::default(), f)
+    }
+}
+
+impl Localize for RulesPreset {
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let since = Translation { ru: "с", en: "from" }.get(locale);
+        write!(f, "{} v{} ({} {})", self.name, self.version, since, self.effective_from)
+    }
+}
+
+/// Набор правил в командной строке: `NAME` или `NAME:VERSION`, без версии — последняя
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetSpec {
+    pub name: String,
+    pub version: Option<u32>,
+}
+
+impl Default for PresetSpec {
+    fn default() -> Self {
+        PresetSpec { name: "official".to_string(), version: None }
+    }
+}
+
+impl FromStr for PresetSpec {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (name, version) = match spec.split_once(':') {
+            Some((name, version)) => {
+                let version = version.parse().map_err(|_| format!("Error. Invalid preset version: {}", spec))?;
+                (name, Some(version))
+            }
+            None => (spec, None),
+        };
+        if name.is_empty() {
+            return Err(format!("Error. Empty preset name: {}", spec).into());
+        }
+        Ok(PresetSpec { name: name.to_string(), version })
+    }
+}
+
+impl Display for PresetSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.version {
+            Some(version) => write!(f, "{}:{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl PresetSpec {
+    /// Загружает набор правил сета `set`, см. [`load_preset`]
+    pub async fn load(&self, db_pull: &SqlitePool, set: &SetCode) -> Result<RulesPreset, Box<dyn std::error::Error>> {
+        load_preset(db_pull, set, &self.name, self.version).await
+    }
+}
+
+struct PresetRow {
+    id: i64,
+    set_code: String,
+    name: String,
+    version: i64,
+    effective_from: String,
+    hall_of_fame_chanse: f64,
+    hall_of_fame_policy: String,
+    hero_silver: f64,
+    hero_gold: f64,
+    command_bronze_silver_bronze: f64,
+    command_bronze_silver_silver: f64,
+    command_random_bronze: f64,
+    command_random_silver: f64,
+    command_random_gold: f64,
+    basic_card_silver: f64,
+    basic_card_gold: f64,
+}
+
+impl TryFrom<PresetRow> for RulesPreset {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(row: PresetRow) -> Result<Self, Self::Error> {
+        let rules = BusterRules::new(
+            SetCode(row.set_code),
+            row.hall_of_fame_chanse,
+            Distribution::new([row.hero_silver, row.hero_gold])?,
+            Distribution::new([row.command_bronze_silver_bronze, row.command_bronze_silver_silver])?,
+            Distribution::new([row.command_random_bronze, row.command_random_silver, row.command_random_gold])?,
+            Distribution::new([row.basic_card_silver, row.basic_card_gold])?,
+        )?.with_hall_of_fame_policy(HallOfFamePolicy::try_from(row.hall_of_fame_policy)?);
+        Ok(RulesPreset {
+            id: row.id,
+            name: row.name,
+            version: u32::try_from(row.version)?,
+            effective_from: row.effective_from,
+            rules,
+        })
+    }
+}
+
+/// Версия `version` набора `name`, а если версия не указана, то последняя
+pub async fn load_preset(db_pull: &SqlitePool, set: &SetCode, name: &str, version: Option<u32>) -> Result<RulesPreset, Box<dyn std::error::Error>> {
+    let filter = PresetFilter { set: Some(set), name: Some(name), version, ..Default::default() };
+    fetch_presets(db_pull, filter).await?
+        .pop()
+        .ok_or_else(|| format!("Error. Not found booster rules: {} {}", set, name).into())
+}
+
+/// Версия набора `name`, действовавшая на дату `date` в формате `YYYY-MM-DD`
+pub async fn load_effective_preset(db_pull: &SqlitePool, set: &SetCode, name: &str, date: &str) -> Result<RulesPreset, Box<dyn std::error::Error>> {
+    check_date(date)?;
+    let filter = PresetFilter { set: Some(set), name: Some(name), effective_on: Some(date), ..Default::default() };
+    fetch_presets(db_pull, filter).await?
+        .into_iter()
+        .max_by(|a, b| (&a.effective_from, a.version).cmp(&(&b.effective_from, b.version)))
+        .ok_or_else(|| format!("Error. Not found booster rules: {} {} on {}", set, name, date).into())
+}
+
+/// Все версии всех наборов, по сетам, именам и версиям
+pub async fn list_presets(db_pull: &SqlitePool) -> Result<Vec<RulesPreset>, Box<dyn std::error::Error>> {
+    fetch_presets(db_pull, PresetFilter::default()).await
+}
+
+/// Условия выборки наборов правил, незаданные поля не ограничивают выборку
+#[derive(Default)]
+struct PresetFilter<'a> {
+    set: Option<&'a SetCode>,
+    name: Option<&'a str>,
+    version: Option<u32>,
+    /// Только версии, начавшие действовать не позже этой даты
+    effective_on: Option<&'a str>,
+}
+
+/// Наборы правил по сетам, именам и версиям
+async fn fetch_presets(db_pull: &SqlitePool, filter: PresetFilter<'_>) -> Result<Vec<RulesPreset>, Box<dyn std::error::Error>> {
+    let set = filter.set.map(|set| set.0.as_str());
+    let version = filter.version.map(i64::from);
+    sqlx::query_as!(
+        PresetRow,
+        "SELECT booster_rules.id as \"id!\", sets.short_name as set_code, booster_rules.name as name, version, effective_from,
+            hall_of_fame_chanse, hall_of_fame_policy,
+            hero_silver, hero_gold,
+            command_bronze_silver_bronze, command_bronze_silver_silver,
+            command_random_bronze, command_random_silver, command_random_gold,
+            basic_card_silver, basic_card_gold
+        FROM booster_rules
+        INNER JOIN sets ON sets.id = booster_rules.set_id
+        WHERE (? IS NULL OR sets.short_name = ?)
+            AND (? IS NULL OR booster_rules.name = ?)
+            AND (? IS NULL OR version = ?)
+            AND (? IS NULL OR effective_from <= ?)
+        ORDER BY set_id, booster_rules.name, version",
+        set,
+        set,
+        filter.name,
+        filter.name,
+        version,
+        version,
+        filter.effective_on,
+        filter.effective_on,
+        ).fetch_all(db_pull)
+        .await?
+        .into_iter()
+        .map(RulesPreset::try_from)
+        .collect()
+}
+
+/// Сохраняет правила как следующую версию набора `name` для сета из правил.
+/// Новая версия не может начать действовать раньше предыдущей
+pub async fn save_preset(db_pull: &SqlitePool, name: &str, effective_from: &str, rules: &BusterRules) -> Result<RulesPreset, Box<dyn std::error::Error>> {
+    check_date(effective_from)?;
+    let mut transaction = db_pull.begin().await?;
+    let set_id = sqlx::query_scalar!("SELECT id FROM sets WHERE short_name = ?", rules.set.0)
+        .fetch_optional(&mut *transaction)
+        .await?
+        .ok_or_else(|| format!("Error. Not found set: {}", rules.set))?;
+    let latest = sqlx::query!(
+        "SELECT version, effective_from FROM booster_rules
+        WHERE set_id = ? AND name = ?
+        ORDER BY version DESC
+        LIMIT 1",
+        set_id,
+        name,
+        ).fetch_optional(&mut *transaction)
+        .await?;
+    if let Some(latest) = latest.as_ref().filter(|l| l.effective_from.as_str() > effective_from) {
+        return Err(format!("Error. Booster rules {} v{} are effective from {}", name, latest.version, latest.effective_from).into());
+    }
+    let version = latest.map_or(1, |l| l.version + 1);
+
+    let policy = rules.hall_of_fame_policy.key();
+    let hero = rules.hero_chanse.values();
+    let command_bronze_silver = rules.command_chanse_bronze_silver.values();
+    let command_random = rules.command_chanse_random.values();
+    let basic_card = rules.basic_card_chanse.values();
+    sqlx::query!(
+        "INSERT INTO booster_rules (
+            set_id, name, version, effective_from, hall_of_fame_chanse, hall_of_fame_policy,
+            hero_silver, hero_gold,
+            command_bronze_silver_bronze, command_bronze_silver_silver,
+            command_random_bronze, command_random_silver, command_random_gold,
+            basic_card_silver, basic_card_gold
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        set_id,
+        name,
+        version,
+        effective_from,
+        rules.hall_of_fame_chanse,
+        policy,
+        hero[0],
+        hero[1],
+        command_bronze_silver[0],
+        command_bronze_silver[1],
+        command_random[0],
+        command_random[1],
+        command_random[2],
+        basic_card[0],
+        basic_card[1],
+        ).execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    load_preset(db_pull, &rules.set, name, Some(u32::try_from(version)?)).await
+}
+
+/// Проверяет формат даты `YYYY-MM-DD`, чтобы даты сравнивались как строки
+pub(crate) fn check_date(date: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let valid = date.len() == 10 && date.char_indices().all(|(i, c)| match i {
+        4 | 7 => c == '-',
+        _ => c.is_ascii_digit(),
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Error. Date must be YYYY-MM-DD: {}", date).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture;
+
+    #[test]
+    async fn preset_localized() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // WHEN
+        let preset = load_preset(&db_pull, &SetCode::from(fixture::KOV), "official", None).await.unwrap();
+        // THEN
+        assert_eq!(preset.to_string(), "official v1 (с 2025-10-21)");
+        assert_eq!(preset.localized(Locale::En).to_string(), "official v1 (from 2025-10-21)");
+    }
+
+    #[test]
+    async fn preset_spec_from_str() {
+        assert_eq!("official".parse::<PresetSpec>().unwrap(), PresetSpec { name: "official".to_string(), version: None });
+        assert_eq!("official:2".parse::<PresetSpec>().unwrap(), PresetSpec { name: "official".to_string(), version: Some(2) });
+        assert_eq!("official:2".parse::<PresetSpec>().unwrap().to_string(), "official:2");
+        assert!("official:".parse::<PresetSpec>().is_err());
+        assert!("official:-1".parse::<PresetSpec>().is_err());
+        assert!(":1".parse::<PresetSpec>().is_err());
+    }
+
+    #[test]
+    async fn load_preset_from_migration() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // WHEN
+        let preset = load_preset(&db_pull, &SetCode::from(fixture::KOV), "official", None).await.unwrap();
+        // THEN
+        assert_eq!(preset.version, 1);
+        assert_eq!(preset.rules.hall_of_fame_chanse, 0.02);
+        assert_eq!(preset.rules.hero_chanse.values(), &[0.8, 0.2]);
+        assert_eq!(preset.rules.hall_of_fame_policy(), HallOfFamePolicy::Fallback);
+    }
+
+    #[test]
+    async fn save_preset_then_next_version() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        let rules = fixture::rules(0.05).with_hall_of_fame_policy(HallOfFamePolicy::RerollRarity);
+        // WHEN
+        let saved = save_preset(&db_pull, "official", "2026-03-01", &rules).await.unwrap();
+        // THEN
+        assert_eq!(saved.version, 2);
+        assert_eq!(saved.rules.hall_of_fame_chanse, 0.05);
+        assert_eq!(saved.rules.hall_of_fame_policy(), HallOfFamePolicy::RerollRarity);
+        assert_eq!(saved.rules.command_chanse_random.values(), &[0.7, 0.2, 0.1]);
+        let first = load_preset(&db_pull, &SetCode::from(fixture::KOV), "official", Some(1)).await.unwrap();
+        assert_eq!(first.rules.hall_of_fame_chanse, 0.02);
+    }
+
+    #[test]
+    async fn load_effective_preset_by_date() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        save_preset(&db_pull, "official", "2026-03-01", &fixture::rules(0.05)).await.unwrap();
+        // WHEN
+        let before = load_effective_preset(&db_pull, &SetCode::from(fixture::KOV), "official", "2026-02-28").await.unwrap();
+        let after = load_effective_preset(&db_pull, &SetCode::from(fixture::KOV), "official", "2026-03-01").await.unwrap();
+        let too_early = load_effective_preset(&db_pull, &SetCode::from(fixture::KOV), "official", "2024-01-01").await;
+        // THEN
+        assert_eq!(before.version, 1);
+        assert_eq!(after.version, 2);
+        assert!(too_early.is_err());
+    }
+
+    #[test]
+    async fn save_preset_effective_before_latest_then_error() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // WHEN
+        let result = save_preset(&db_pull, "official", "2025-01-01", &fixture::rules(0.05)).await;
+        // THEN
+        assert!(result.is_err());
+        assert_eq!(list_presets(&db_pull).await.unwrap().len(), 1);
+    }
+
+    #[test]
+    async fn save_preset_with_invalid_date_then_error() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // WHEN
+        let result = save_preset(&db_pull, "community", "01.03.2026", &fixture::rules(0.05)).await;
+        // THEN
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Цены карт и ценность бустера: ожидаемая стоимость карт, распределение стоимости
+//! бустеров и вероятность окупить цену бустера. Бустеры открываются по тем же правилам,
+//! что и в `simulation::simulate`
+
+use std::collections::HashMap;
+
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    BusterRules, Card, CardPool, SetId, SetRegistry,
+    economy::CostDistribution,
+    simulation::{BLOCK_SIZE, OpenedBuster, PoolSnapshot, block_seed, run_blocks},
+};
+
+/// Вариант карты, если в CSV он не указан
+pub const DEFAULT_VARIANT: &str = "normal";
+
+/// Строка прайс-листа
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceRecord {
+    /// Короткое название сета, как в таблице `sets`
+    pub set: String,
+    pub id_in_set: u32,
+    pub variant: String,
+    pub price: f64,
+}
+
+/// Разбирает CSV с заголовком `set,id_in_set,variant,price`.
+/// Поля можно брать в двойные кавычки, пустые строки пропускаются
+pub fn parse_price_csv(csv: &str) -> Result<Vec<PriceRecord>, Box<dyn std::error::Error>> {
+    let mut lines = csv.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or("Error. Empty price list")?;
+    let header = split_csv_line(header.trim_start_matches('\u{feff}'))?;
+    if header != ["set", "id_in_set", "variant", "price"] {
+        return Err(format!("Error. Price list header must be set,id_in_set,variant,price, got: {}", header.join(",")).into());
+    }
+    lines.map(|(i, line)| {
+        let line_error = |e: &dyn std::fmt::Display| format!("Error. Price list line {}: {}", i + 1, e);
+        let fields = split_csv_line(line).map_err(|e| line_error(&e))?;
+        let [set, id_in_set, variant, price] = <[String; 4]>::try_from(fields)
+            .map_err(|fields| line_error(&format!("expected 4 fields, got {}", fields.len())))?;
+        let price: f64 = price.parse().map_err(|e| line_error(&e))?;
+        if !price.is_finite() || price < 0.0 {
+            return Err(line_error(&format!("invalid price {}", price)).into());
+        }
+        Ok(PriceRecord {
+            set,
+            id_in_set: id_in_set.parse().map_err(|e| line_error(&e))?,
+            variant: if variant.is_empty() { DEFAULT_VARIANT.to_string() } else { variant },
+            price,
+        })
+    }).collect()
+}
+
+fn split_csv_line(line: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unclosed quote".into());
+    }
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
+
+/// Сохраняет цены в таблицу `prices` одной транзакцией, существующие цены заменяются.
+/// Возвращает число сохраненных строк
+pub async fn import_prices(db_pull: &SqlitePool, records: &[PriceRecord]) -> Result<usize, Box<dyn std::error::Error>> {
+    let sets = SetRegistry::load(db_pull).await?;
+    let mut transaction = db_pull.begin().await?;
+    for record in records {
+        let set = sets.by_short_name(&record.set).ok_or_else(|| format!("Error. Not found set: {}", record.set))?;
+        sqlx::query!(
+            "INSERT INTO prices (set_id, id_in_set, variant, price) VALUES (?, ?, ?, ?)
+            ON CONFLICT (set_id, id_in_set, variant) DO UPDATE SET price = excluded.price",
+            set.id.0,
+            record.id_in_set,
+            record.variant,
+            record.price,
+            ).execute(&mut *transaction)
+            .await?;
+    }
+    transaction.commit().await?;
+    Ok(records.len())
+}
+
+/// Цены одного варианта карт по сету и номеру в сете
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceTable {
+    prices: HashMap<(SetId, u32), f64>,
+}
+
+impl PriceTable {
+    pub async fn load(db_pull: &SqlitePool, variant: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let prices = sqlx::query!("SELECT set_id, id_in_set, price FROM prices WHERE variant = ?", variant)
+            .fetch_all(db_pull)
+            .await?
+            .into_iter()
+            .map(|rec| Ok(((SetId(rec.set_id), u32::try_from(rec.id_in_set)?), rec.price)))
+            .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+        Ok(PriceTable { prices })
+    }
+
+    pub fn price(&self, card: &Card) -> Option<f64> {
+        self.prices.get(&(card.set.id, card.id_in_set)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+}
+
+/// Ценность бустера по прайс-листу
+#[derive(Debug, Clone, Serialize)]
+pub struct PackValueReport {
+    /// Точное ожидание стоимости карт бустера по частотам карт
+    pub expected_value: f64,
+    /// Распределение стоимости открытых бустеров
+    pub values: CostDistribution,
+    pub retail_price: f64,
+    /// Доля бустеров дороже цены бустера
+    pub above_retail: f64,
+    /// Карты, которые могут выпасть, но не имеют цены. Считаются бесплатными
+    pub unpriced_cards: Vec<Card>,
+}
+
+/// Оценивает бустер по правилам `rules`: ожидание считается точно,
+/// распределение и вероятность окупаемости — по `trials` открытым бустерам
+pub fn pack_value(
+    pool: &CardPool,
+    rules: &BusterRules,
+    prices: &PriceTable,
+    retail_price: f64,
+    trials: usize,
+    seed: u64,
+    threads: usize,
+) -> Result<PackValueReport, Box<dyn std::error::Error>> {
+    let pull_rates = pool.pull_rates(rules)?;
+    let expected_value = pull_rates.iter()
+        .map(|rate| rate.per_buster * prices.price(&rate.card).unwrap_or(0.0))
+        .sum();
+    let snapshot = pool.snapshot(rules)?;
+    let values = simulate_pack_values(&snapshot, prices, trials, seed, threads);
+    let above_retail = values.iter().filter(|&&value| value > retail_price).count() as f64 / values.len() as f64;
+    let unpriced_cards = pull_rates.into_iter()
+        .filter(|rate| rate.per_buster > 0.0 && prices.price(&rate.card).is_none())
+        .map(|rate| rate.card)
+        .collect();
+    Ok(PackValueReport {
+        expected_value,
+        values: CostDistribution::new(values).ok_or("Error. No boosters opened")?,
+        retail_price,
+        above_retail,
+        unpriced_cards,
+    })
+}
+
+/// Стоимости `trials` бустеров. Блоки и зерна как в `simulation::simulate`,
+/// поэтому при том же зерне открываются те же бустеры
+fn simulate_pack_values(snapshot: &PoolSnapshot, prices: &PriceTable, trials: usize, seed: u64, threads: usize) -> Vec<f64> {
+    let card_prices: Vec<f64> = snapshot.cards().iter().map(|card| prices.price(card).unwrap_or(0.0)).collect();
+    run_blocks(trials.div_ceil(BLOCK_SIZE), threads, |block| {
+        let mut rng = StdRng::seed_from_u64(block_seed(seed, block));
+        let mut buster = OpenedBuster::default();
+        let start = block * BLOCK_SIZE;
+        (start..trials.min(start + BLOCK_SIZE)).map(|_| {
+            snapshot.generate_into(&mut rng, &mut buster);
+            buster.cards.iter().map(|&index| card_prices[index]).sum::<f64>()
+        }).collect::<Vec<f64>>()
+    }).into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture;
+
+    /// Цена 1 у всех 16 карт КОВ в стандартной фикстуре
+    fn flat_price_csv() -> String {
+        let mut csv = "set,id_in_set,variant,price\n".to_string();
+        (1..=16).for_each(|id| csv += &format!("КОВ,{},normal,1\n", id));
+        csv
+    }
+
+    #[test]
+    async fn parse_price_csv_with_quotes_and_default_variant() {
+        // GIVEN
+        let csv = "\u{feff}set,id_in_set,variant,price\r\n\"Зал Славы\",7,,12.5\r\n\r\nКОВ,3,\"foil, \"\"alt\"\"\",0.25\r\n";
+        // WHEN
+        let records = parse_price_csv(csv).unwrap();
+        // THEN
+        assert_eq!(records, vec![
+            PriceRecord { set: "Зал Славы".to_string(), id_in_set: 7, variant: DEFAULT_VARIANT.to_string(), price: 12.5 },
+            PriceRecord { set: "КОВ".to_string(), id_in_set: 3, variant: "foil, \"alt\"".to_string(), price: 0.25 },
+        ]);
+    }
+
+    #[test]
+    async fn parse_price_csv_with_invalid_rows_then_error() {
+        assert!(parse_price_csv("name,price\nКОВ,1").is_err());
+        assert!(parse_price_csv("set,id_in_set,variant,price\nКОВ,1,normal").is_err());
+        assert!(parse_price_csv("set,id_in_set,variant,price\nКОВ,1,normal,-1").is_err());
+        assert!(parse_price_csv("set,id_in_set,variant,price\nКОВ,x,normal,1").is_err());
+        assert!(parse_price_csv("set,id_in_set,variant,price\n\"КОВ,1,normal,1").is_err());
+    }
+
+    #[test]
+    async fn import_prices_replaces_existing() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        import_prices(&db_pull, &parse_price_csv("set,id_in_set,variant,price\nКОВ,1,normal,2\nКОВ,1,foil,10").unwrap()).await.unwrap();
+        // WHEN
+        import_prices(&db_pull, &parse_price_csv("set,id_in_set,variant,price\nКОВ,1,normal,3").unwrap()).await.unwrap();
+        let normal = PriceTable::load(&db_pull, "normal").await.unwrap();
+        let foil = PriceTable::load(&db_pull, "foil").await.unwrap();
+        // THEN
+        assert_eq!(normal.prices, HashMap::from([((SetId(2), 1), 3.0)]));
+        assert_eq!(foil.prices, HashMap::from([((SetId(2), 1), 10.0)]));
+    }
+
+    #[test]
+    async fn import_prices_with_unknown_set_then_nothing_saved() {
+        let db_pull = fixture::standard().await;
+        let records = parse_price_csv("set,id_in_set,variant,price\nКОВ,1,normal,2\nНОВ,1,normal,2").unwrap();
+        assert!(import_prices(&db_pull, &records).await.is_err());
+        assert!(PriceTable::load(&db_pull, "normal").await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn pack_value_with_flat_prices() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        import_prices(&db_pull, &parse_price_csv(&flat_price_csv()).unwrap()).await.unwrap();
+        let prices = PriceTable::load(&db_pull, DEFAULT_VARIANT).await.unwrap();
+        let pool = CardPool::load(&db_pull).await.unwrap();
+        // WHEN
+        let report = pack_value(&pool, &fixture::rules(0.0), &prices, 17.5, 5000, 1, 2).unwrap();
+        // THEN
+        // Без Зала Славы все 18 карт из КОВ и стоят по 1
+        assert!((report.expected_value - 18.0).abs() < 1e-9);
+        assert_eq!(report.values.min, 18.0);
+        assert_eq!(report.values.max, 18.0);
+        assert_eq!(report.above_retail, 1.0);
+        assert!(report.unpriced_cards.is_empty());
+    }
+
+    #[test]
+    async fn pack_value_with_expensive_gold_hero() {
+        // GIVEN
+        let db_pull = fixture::standard().await;
+        // Золотые герои КОВ в стандартной фикстуре имеют номера 3 и 4
+        let csv = "set,id_in_set,variant,price\nКОВ,3,normal,100\nКОВ,4,normal,100";
+        import_prices(&db_pull, &parse_price_csv(csv).unwrap()).await.unwrap();
+        let prices = PriceTable::load(&db_pull, DEFAULT_VARIANT).await.unwrap();
+        let pool = CardPool::load(&db_pull).await.unwrap();
+        // WHEN
+        let report = pack_value(&pool, &fixture::rules(0.0), &prices, 50.0, 20_000, 3, 4).unwrap();
+        // THEN
+        assert!((report.expected_value - 20.0).abs() < 1e-9);
+        assert!((report.values.mean - 20.0).abs() < 1.5);
+        assert!((report.above_retail - 0.2).abs() < 0.015);
+        assert_eq!(report.values.median, 0.0);
+        assert_eq!(report.unpriced_cards.len(), 14);
+    }
+}
@@ -0,0 +1,426 @@
+//! Поиск карт по всем сетам с фильтрами.
+//! Текст правил ищется через полнотекстовый индекс `cards_fts` (FTS5),
+//! название — по подстрокам, а если их нет, то с опечатками
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{Card, CardBusterSlot, CardRarity, SetCode, SetId, SetRegistry};
+
+/// Фильтры поиска карт. Незаданные поля не ограничивают выборку.
+/// Поля совпадают с параметрами `GET /api/cards`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CardQuery {
+    /// Слова названия в любом порядке, каждое может быть частью слова.
+    /// Регистр не учитывается: `лорд дом` найдет «Лорд Доминус», а `лучник` — «Демон-лучник».
+    /// Если таких карт нет, ищутся названия с опечатками, см. [`typo_tolerance`]
+    pub name: Option<String>,
+    /// Фраза из текста правил
+    pub text: Option<String>,
+    pub set: Option<SetCode>,
+    pub rarity: Option<CardRarity>,
+    pub slot: Option<CardBusterSlot>,
+    /// Одна из фракций карты
+    pub faction: Option<String>,
+    pub cost_min: Option<u32>,
+    pub cost_max: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+/// Найденная карта с данными, которые не нужны для генерации бустеров
+#[derive(Debug, Clone, Serialize)]
+pub struct FoundCard {
+    /// Идентификатор карты в БД, как в `/images/cards/{id}`
+    pub id: i64,
+    #[serde(flatten)]
+    pub card: Card,
+    /// Пусто у нейтральных карт
+    pub factions: Vec<String>,
+    pub cost: Option<u32>,
+    pub text: Option<String>,
+}
+
+struct CardRow {
+    id: i64,
+    name: String,
+    set_id: i64,
+    id_in_set: i64,
+    rarity: String,
+    buster_slot: String,
+    image_url: Option<String>,
+    weight: f64,
+    /// JSON массив фракций
+    factions: String,
+    cost: Option<i64>,
+    text: Option<String>,
+}
+
+impl CardRow {
+    fn into_found_card(self, sets: &SetRegistry) -> Result<FoundCard, Box<dyn std::error::Error>> {
+        let set = sets.get(SetId(self.set_id)).ok_or_else(|| format!("Error. Not found set: {}", self.set_id))?.clone();
+        let rarity = CardRarity::try_from(self.rarity)?;
+        let buster_slot = CardBusterSlot::try_from(self.buster_slot)?;
+        Ok(FoundCard {
+            id: self.id,
+            card: Card::new(self.name, u32::try_from(self.id_in_set)?, rarity, buster_slot, set, self.image_url, self.weight),
+            factions: serde_json::from_str(&self.factions)?,
+            cost: self.cost.map(u32::try_from).transpose()?,
+            text: self.text,
+        })
+    }
+}
+
+impl CardQuery {
+    /// Карты, подходящие под все фильтры, по сетам и номерам в сете
+    pub async fn execute(&self, db_pull: &SqlitePool) -> Result<Vec<FoundCard>, Box<dyn std::error::Error>> {
+        if let (Some(min), Some(max)) = (self.cost_min, self.cost_max) && min > max {
+            return Err(format!("Error. Empty cost range: {}..{}", min, max).into());
+        }
+        let sets = SetRegistry::load(db_pull).await?;
+        let fts_query = self.fts_query();
+        let set = self.set.as_ref()
+            .map(|code| sets.by_code(code).map(|s| s.id.0).ok_or_else(|| format!("Error. Not found set: {}", code)))
+            .transpose()?;
+        let rarity = self.rarity.map(|r| r.db_name());
+        let slot = self.slot.map(|s| s.db_name());
+        let cost_min = self.cost_min.map(i64::from);
+        let cost_max = self.cost_max.map(i64::from);
+        let limit = self.limit.map_or(Ok(usize::MAX), usize::try_from)?;
+        let rows = sqlx::query_as!(
+            CardRow,
+            "SELECT cards.id as \"id!\", cards.name as name, set_id, id_in_set, rarities.name as rarity, types.name as buster_slot,
+                image_url, weight, cost, text,
+                (SELECT json_group_array(faction) FROM (SELECT faction FROM card_factions WHERE card_id = cards.id ORDER BY rowid))
+                    as \"factions!: String\"
+            FROM cards
+            INNER JOIN rarities ON cards.rarity_id = rarities.id
+            INNER JOIN types ON cards.type_id = types.id
+            WHERE (? IS NULL OR cards.id IN (SELECT rowid FROM cards_fts WHERE cards_fts MATCH ?))
+                AND (? IS NULL OR set_id = ?)
+                AND (? IS NULL OR rarities.name = ?)
+                AND (? IS NULL OR types.name = ?)
+                AND (? IS NULL OR EXISTS (SELECT 1 FROM card_factions WHERE card_id = cards.id AND faction = ?))
+                AND (? IS NULL OR cost >= ?)
+                AND (? IS NULL OR cost <= ?)
+            ORDER BY set_id, id_in_set",
+            fts_query,
+            fts_query,
+            set,
+            set,
+            rarity,
+            rarity,
+            slot,
+            slot,
+            self.faction,
+            self.faction,
+            cost_min,
+            cost_min,
+            cost_max,
+            cost_max,
+            ).fetch_all(db_pull)
+            .await?;
+        let rows = match self.name.as_deref().and_then(NameMatcher::new) {
+            None => rows,
+            Some(matcher) => {
+                let (found, other): (Vec<_>, Vec<_>) = rows.into_iter().partition(|row| matcher.contains(&row.name));
+                if found.is_empty() {
+                    other.into_iter().filter(|row| matcher.is_close(&row.name)).collect()
+                } else {
+                    found
+                }
+            }
+        };
+        rows.into_iter()
+            .take(limit)
+            .map(|row| row.into_found_card(&sets))
+            .collect()
+    }
+
+    /// Запрос FTS5 по тексту правил. Фраза берется в кавычки,
+    /// чтобы операторы FTS5 во вводе пользователя не разбирались
+    fn fts_query(&self) -> Option<String> {
+        self.text.as_deref()
+            .filter(|text| !text.trim().is_empty())
+            .map(|text| format!("text : {}", fts_string(text)))
+    }
+}
+
+/// Слова запроса по названию в нижнем регистре
+struct NameMatcher {
+    words: Vec<Vec<char>>,
+}
+
+impl NameMatcher {
+    /// `None`, если в запросе нет слов
+    fn new(query: &str) -> Option<Self> {
+        let words: Vec<Vec<char>> = query.split_whitespace().map(|word| word.to_lowercase().chars().collect()).collect();
+        (!words.is_empty()).then_some(NameMatcher { words })
+    }
+
+    /// Каждое слово запроса — подстрока названия
+    fn contains(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.words.iter().all(|word| name.contains(&word.iter().collect::<String>()))
+    }
+
+    /// Каждое слово запроса похоже на слово названия или на его начало той же длины
+    fn is_close(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        let name_words: Vec<Vec<char>> = name.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.chars().collect())
+            .collect();
+        self.words.iter().all(|word| {
+            let tolerance = typo_tolerance(word.len());
+            name_words.iter().any(|name_word| {
+                let prefix = &name_word[..name_word.len().min(word.len())];
+                edit_distance(word, name_word) <= tolerance || edit_distance(word, prefix) <= tolerance
+            })
+        })
+    }
+}
+
+/// Сколько опечаток допускается в слове запроса длины `len`:
+/// в коротких словах ни одной, от 4 букв одна, от 8 букв две
+fn typo_tolerance(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Расстояние Левенштейна: вставки, удаления и замены символов
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, &a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Карты по идентификаторам в БД, по возрастанию идентификатора.
+/// Неизвестные идентификаторы пропускаются
+pub async fn cards_by_id(db_pull: &SqlitePool, ids: &[i64]) -> Result<Vec<FoundCard>, Box<dyn std::error::Error>> {
+    let sets = SetRegistry::load(db_pull).await?;
+    let ids = serde_json::to_string(ids)?;
+    sqlx::query_as!(
+        CardRow,
+        "SELECT cards.id as \"id!\", cards.name as name, set_id, id_in_set, rarities.name as rarity, types.name as buster_slot,
+            image_url, weight, cost, text,
+            (SELECT json_group_array(faction) FROM (SELECT faction FROM card_factions WHERE card_id = cards.id ORDER BY rowid))
+                as \"factions!: String\"
+        FROM cards
+        INNER JOIN rarities ON cards.rarity_id = rarities.id
+        INNER JOIN types ON cards.type_id = types.id
+        WHERE cards.id IN (SELECT value FROM json_each(?))
+        ORDER BY cards.id",
+        ids,
+        ).fetch_all(db_pull)
+        .await?
+        .into_iter()
+        .map(|row| row.into_found_card(&sets))
+        .collect()
+}
+
+fn fts_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture::{FixtureBuilder, HALL_OF_FAME, KOV};
+
+    async fn search_fixture() -> SqlitePool {
+        FixtureBuilder::standard()
+            .described_card(KOV, CardBusterSlot::Hero, CardRarity::Gold, "Лорд Доминус", &["Империя"], 5, "Наносит 3 урона всем врагам")
+            .described_card(KOV, CardBusterSlot::Command, CardRarity::Silver, "Приказ лорда", &["Империя"], 2, "Герой получает щит")
+            .described_card(HALL_OF_FAME, CardBusterSlot::Hero, CardRarity::Gold, "Ёжик-разведчик", &["Лес"], 1, "Наносит 1 урон")
+            .described_card(HALL_OF_FAME, CardBusterSlot::BasicCard, CardRarity::Gold, "Демон-лучник", &["Лес"], 3, "")
+            .build().await
+    }
+
+    fn names(cards: &[FoundCard]) -> Vec<&str> {
+        cards.iter().map(|c| c.card.name.as_str()).collect()
+    }
+
+    #[test]
+    async fn query_by_name_prefix_in_any_order() {
+        // GIVEN
+        let db_pull = search_fixture().await;
+        let query = CardQuery { name: Some("дом ЛОРД".to_string()), ..Default::default() };
+        // WHEN
+        let cards = query.execute(&db_pull).await.unwrap();
+        // THEN
+        assert_eq!(names(&cards), vec!["Лорд Доминус"]);
+        assert_eq!(cards[0].factions, vec!["Империя".to_string()]);
+        assert_eq!(cards[0].cost, Some(5));
+    }
+
+    #[test]
+    async fn query_by_name_ignores_case() {
+        let db_pull = search_fixture().await;
+        let query = CardQuery { name: Some("ЁЖИК".to_string()), ..Default::default() };
+        let cards = query.execute(&db_pull).await.unwrap();
+        assert_eq!(names(&cards), vec!["Ёжик-разведчик"]);
+    }
+
+    #[test]
+    async fn query_by_name_substring_inside_word() {
+        let db_pull = search_fixture().await;
+        let query = CardQuery { name: Some("ЛУЧНИК".to_string()), ..Default::default() };
+        let cards = query.execute(&db_pull).await.unwrap();
+        assert_eq!(names(&cards), vec!["Демон-лучник"]);
+    }
+
+    #[test]
+    async fn query_by_misspelled_name_then_close_names() {
+        // GIVEN
+        let db_pull = search_fixture().await;
+        let typo = CardQuery { name: Some("доменус".to_string()), ..Default::default() };
+        let typo_in_prefix = CardQuery { name: Some("лучнек".to_string()), ..Default::default() };
+        let short_word = CardQuery { name: Some("лрд".to_string()), ..Default::default() };
+        // WHEN
+        let typo = typo.execute(&db_pull).await.unwrap();
+        let typo_in_prefix = typo_in_prefix.execute(&db_pull).await.unwrap();
+        let short_word = short_word.execute(&db_pull).await.unwrap();
+        // THEN
+        assert_eq!(names(&typo), vec!["Лорд Доминус"]);
+        assert_eq!(names(&typo_in_prefix), vec!["Демон-лучник"]);
+        assert!(short_word.is_empty());
+    }
+
+    #[test]
+    async fn edit_distance_counts_insertions_deletions_and_substitutions() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&chars("доминус"), &chars("доминус")), 0);
+        assert_eq!(edit_distance(&chars("доменус"), &chars("доминус")), 1);
+        assert_eq!(edit_distance(&chars("доминс"), &chars("доминус")), 1);
+        assert_eq!(edit_distance(&chars("лорд"), &chars("")), 4);
+    }
+
+    #[test]
+    async fn query_by_text_and_filters() {
+        // GIVEN
+        let db_pull = search_fixture().await;
+        let query = CardQuery {
+            text: Some("наносит".to_string()),
+            slot: Some(CardBusterSlot::Hero),
+            cost_max: Some(3),
+            ..Default::default()
+        };
+        // WHEN
+        let cards = query.execute(&db_pull).await.unwrap();
+        // THEN
+        assert_eq!(names(&cards), vec!["Ёжик-разведчик"]);
+        assert_eq!(cards[0].card.set.id, SetId(3));
+    }
+
+    #[test]
+    async fn query_by_set_rarity_and_faction() {
+        // GIVEN
+        let db_pull = search_fixture().await;
+        let query = CardQuery {
+            set: Some(SetCode::from("КОВ")),
+            rarity: Some(CardRarity::Silver),
+            faction: Some("Империя".to_string()),
+            ..Default::default()
+        };
+        // WHEN
+        let cards = query.execute(&db_pull).await.unwrap();
+        // THEN
+        assert_eq!(names(&cards), vec!["Приказ лорда"]);
+    }
+
+    #[test]
+    async fn query_by_faction_finds_multi_faction_cards() {
+        // GIVEN
+        let db_pull = FixtureBuilder::new()
+            .described_card(KOV, CardBusterSlot::BasicCard, CardRarity::Gold, "Посланник", &["Империя", "Лес"], 2, "")
+            .described_card(KOV, CardBusterSlot::BasicCard, CardRarity::Gold, "Страж", &["Империя"], 2, "")
+            .cards(KOV, CardBusterSlot::BasicCard, CardRarity::Gold, 1)
+            .build().await;
+        let query = |faction: &str| CardQuery { faction: Some(faction.to_string()), ..Default::default() };
+        // WHEN
+        let empire = query("Империя").execute(&db_pull).await.unwrap();
+        let forest = query("Лес").execute(&db_pull).await.unwrap();
+        // THEN
+        assert_eq!(names(&empire), vec!["Посланник", "Страж"]);
+        assert_eq!(names(&forest), vec!["Посланник"]);
+        assert_eq!(forest[0].factions, vec!["Империя".to_string(), "Лес".to_string()]);
+        let all = CardQuery::default().execute(&db_pull).await.unwrap();
+        assert!(all[2].factions.is_empty());
+    }
+
+    #[test]
+    async fn query_with_fts_operators_in_input_then_literal_words() {
+        let db_pull = search_fixture().await;
+        let query = CardQuery { name: Some("лорд OR \"NEAR(".to_string()), ..Default::default() };
+        let cards = query.execute(&db_pull).await.unwrap();
+        assert!(cards.is_empty());
+    }
+
+    #[test]
+    async fn query_without_filters_then_all_cards_with_limit() {
+        // GIVEN
+        let db_pull = search_fixture().await;
+        // WHEN
+        let all = CardQuery::default().execute(&db_pull).await.unwrap();
+        let limited = CardQuery { limit: Some(4), ..Default::default() }.execute(&db_pull).await.unwrap();
+        // THEN
+        assert_eq!(all.len(), 16 + 3 + 4);
+        assert_eq!(limited.len(), 4);
+        assert_eq!(limited[0].card.name, "КОВ 1");
+    }
+
+    #[test]
+    async fn query_with_unknown_set_then_error() {
+        let db_pull = search_fixture().await;
+        let query = CardQuery { set: Some(SetCode::from("НЕТ")), ..Default::default() };
+        assert!(query.execute(&db_pull).await.is_err());
+    }
+
+    #[test]
+    async fn query_with_empty_cost_range_then_error() {
+        let db_pull = search_fixture().await;
+        let query = CardQuery { cost_min: Some(3), cost_max: Some(1), ..Default::default() };
+        assert!(query.execute(&db_pull).await.is_err());
+    }
+
+    #[test]
+    async fn cards_by_id_skips_unknown() {
+        // GIVEN
+        let db_pull = search_fixture().await;
+        // WHEN
+        let cards = cards_by_id(&db_pull, &[3, 1, 1000]).await.unwrap();
+        // THEN
+        assert_eq!(cards.iter().map(|c| c.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(cards[0].card.name, "КОВ 1");
+    }
+
+    #[test]
+    async fn query_deserialize_from_parameters() {
+        // WHEN
+        let query: CardQuery = serde_json::from_value(serde_json::json!({
+            "name": "лорд",
+            "set": "КОВ",
+            "rarity": "gold",
+            "slot": "basic_card",
+            "cost_min": 1,
+        })).unwrap();
+        // THEN
+        assert_eq!(query.set, Some(SetCode::from("КОВ")));
+        assert_eq!(query.rarity, Some(CardRarity::Gold));
+        assert_eq!(query.slot, Some(CardBusterSlot::BasicCard));
+        assert_eq!(query.cost_min, Some(1));
+    }
+}
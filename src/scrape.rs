@@ -0,0 +1,230 @@
+//! Разбор HTML страниц response-world.ru.
+//! Используется как запасной вариант, когда JSON API не отдает нужные данные
+
+use std::sync::LazyLock;
+
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+/// Сет со страницы списка сетов
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrapedSet {
+    pub name: String,
+    pub short_name: String,
+    /// Дата выхода в формате `YYYY-MM-DD`, как в таблице `sets`
+    pub release_date: String,
+    pub card_count: u32,
+}
+
+/// Карта со страницы карты. Поля не проверены и содержат строки как на сайте
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrapedCard {
+    pub name: String,
+    pub id_in_set: u32,
+    pub rarity: String,
+    pub types: Vec<String>,
+    pub set: String,
+    pub image_url: Option<String>,
+    /// Пусто у нейтральных карт
+    pub factions: Vec<String>,
+    pub cost: Option<u32>,
+    /// Текст правил
+    pub text: Option<String>,
+}
+
+fn selector(selectors: &'static str) -> Selector {
+    Selector::parse(selectors).expect("invalid static selector")
+}
+
+static SET_ITEM: LazyLock<Selector> = LazyLock::new(|| selector(".set-card"));
+static SET_NAME: LazyLock<Selector> = LazyLock::new(|| selector(".set-card__name"));
+static SET_SHORT_NAME: LazyLock<Selector> = LazyLock::new(|| selector(".set-card__short-name"));
+static SET_RELEASE_DATE: LazyLock<Selector> = LazyLock::new(|| selector(".set-card__release-date"));
+static SET_CARD_COUNT: LazyLock<Selector> = LazyLock::new(|| selector(".set-card__count"));
+
+static CARD_NAME: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__name"));
+static CARD_NUMBER: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__number"));
+static CARD_RARITY: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__rarity"));
+static CARD_TYPE: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__types .card-page__type"));
+static CARD_SET: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__set"));
+static CARD_IMAGE: LazyLock<Selector> = LazyLock::new(|| selector("img.card-page__image"));
+static CARD_FACTION: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__faction"));
+static CARD_COST: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__cost"));
+static CARD_TEXT: LazyLock<Selector> = LazyLock::new(|| selector(".card-page__text"));
+
+/// Разбирает страницу со списком сетов
+pub fn parse_set_listing(html: &str) -> Result<Vec<ScrapedSet>, Box<dyn std::error::Error>> {
+    let document = Html::parse_document(html);
+    document.select(&SET_ITEM).map(|item| {
+        let name = required_text(item, &SET_NAME, "set name")?;
+        let short_name = required_text(item, &SET_SHORT_NAME, "set short name")?;
+        let release_date_element = item.select(&SET_RELEASE_DATE).next()
+            .ok_or_else(|| format!("Error. Not found release date of set: {}", name))?;
+        // Предпочитаем машиночитаемый атрибут `datetime`, текст может быть в формате ДД.ММ.ГГГГ
+        let release_date = match release_date_element.value().attr("datetime") {
+            Some(date) => normalize_date(date)?,
+            None => normalize_date(&element_text(release_date_element))?,
+        };
+        let card_count = first_number(&required_text(item, &SET_CARD_COUNT, "card count")?)
+            .ok_or_else(|| format!("Error. Not found card count of set: {}", name))?;
+        Ok(ScrapedSet { name, short_name, release_date, card_count })
+    }).collect()
+}
+
+/// Разбирает страницу карты. Относительные ссылки на изображения дополняются `base_url`.
+/// Фракции, стоимость и текст правил есть не у всех карт и не обязательны
+pub fn parse_card_page(html: &str, base_url: &str) -> Result<ScrapedCard, Box<dyn std::error::Error>> {
+    let document = Html::parse_document(html);
+    let root = document.root_element();
+    let name = required_text(root, &CARD_NAME, "card name")?;
+    let id_in_set = first_number(&required_text(root, &CARD_NUMBER, "card number")?)
+        .ok_or_else(|| format!("Error. Not found number of card: {}", name))?;
+    let rarity = required_text(root, &CARD_RARITY, "card rarity")?;
+    let types = root.select(&CARD_TYPE).map(element_text).filter(|t| !t.is_empty()).collect();
+    let set = required_text(root, &CARD_SET, "card set")?;
+    let image_url = root.select(&CARD_IMAGE).next()
+        .and_then(|img| img.value().attr("src"))
+        .map(|src| absolute_url(base_url, src));
+    let factions = root.select(&CARD_FACTION).map(element_text).filter(|f| !f.is_empty()).collect();
+    let cost = optional_text(root, &CARD_COST).and_then(|cost| first_number(&cost));
+    let text = optional_text(root, &CARD_TEXT);
+    Ok(ScrapedCard { name, id_in_set, rarity, types, set, image_url, factions, cost, text })
+}
+
+fn element_text(element: ElementRef) -> String {
+    element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn required_text(element: ElementRef, selector: &Selector, what: &str) -> Result<String, Box<dyn std::error::Error>> {
+    element.select(selector).next()
+        .map(element_text)
+        .filter(|text| !text.is_empty())
+        .ok_or_else(|| format!("Error. Not found {} on page", what).into())
+}
+
+fn optional_text(element: ElementRef, selector: &Selector) -> Option<String> {
+    element.select(selector).next()
+        .map(element_text)
+        .filter(|text| !text.is_empty())
+}
+
+fn first_number(text: &str) -> Option<u32> {
+    text.split(|c: char| !c.is_ascii_digit())
+        .find(|part| !part.is_empty())
+        .and_then(|part| part.parse().ok())
+}
+
+/// Приводит `YYYY-MM-DD`, `YYYY-MM-DDThh:mm...` или `ДД.ММ.ГГГГ` к `YYYY-MM-DD`
+fn normalize_date(date: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let date = date.trim();
+    let parts: Vec<&str> = if date.contains('.') {
+        date.split('.').rev().collect()
+    } else {
+        date.get(..10).unwrap_or(date).split('-').collect()
+    };
+    match parts.as_slice() {
+        [year, month, day]
+            if year.len() == 4 && month.len() == 2 && day.len() == 2
+                && parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            Ok(format!("{}-{}-{}", year, month, day))
+        }
+        _ => Err(format!("Error. Unknown date format: {}", date).into()),
+    }
+}
+
+fn absolute_url(base_url: &str, src: &str) -> String {
+    if src.starts_with("http://") || src.starts_with("https://") {
+        src.to_string()
+    } else {
+        format!("{}/{}", base_url.trim_end_matches('/'), src.trim_start_matches('/'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_URL: &str = "https://response-world.ru/";
+
+    #[test]
+    fn parse_set_listing_fixture() {
+        // GIVEN
+        let html = include_str!("../tests/fixtures/sets.html");
+        // WHEN
+        let sets = parse_set_listing(html).unwrap();
+        // THEN
+        assert_eq!(sets, vec![
+            ScrapedSet { name: "Базовый".to_string(), short_name: "БАЗ".to_string(), release_date: "2025-10-21".to_string(), card_count: 132 },
+            ScrapedSet { name: "Королевства Ванстера".to_string(), short_name: "КОВ".to_string(), release_date: "2025-10-21".to_string(), card_count: 273 },
+            ScrapedSet { name: "Зал Славы".to_string(), short_name: "Зал Славы".to_string(), release_date: "2025-10-21".to_string(), card_count: 10 },
+        ]);
+    }
+
+    #[test]
+    fn parse_set_listing_without_sets() {
+        let sets = parse_set_listing("<html><body><p>Скоро</p></body></html>").unwrap();
+        assert!(sets.is_empty());
+    }
+
+    #[test]
+    fn parse_set_listing_with_broken_date_then_err() {
+        let html = r#"<div class="set-card">
+            <span class="set-card__name">Базовый</span>
+            <span class="set-card__short-name">БАЗ</span>
+            <span class="set-card__release-date">осенью</span>
+            <span class="set-card__count">132 карты</span>
+        </div>"#;
+        assert!(parse_set_listing(html).is_err());
+    }
+
+    #[test]
+    fn parse_card_page_hero_fixture() {
+        // GIVEN
+        let html = include_str!("../tests/fixtures/card_hero.html");
+        // WHEN
+        let card = parse_card_page(html, BASE_URL).unwrap();
+        // THEN
+        assert_eq!(card.name, "Лорд Доминус");
+        assert_eq!(card.id_in_set, 7);
+        assert_eq!(card.rarity, "Золото");
+        assert_eq!(card.types, vec!["Существо".to_string(), "Герой".to_string()]);
+        assert_eq!(card.set, "Зал Славы");
+        assert_eq!(card.image_url.as_deref(), Some("https://response-world.ru/storage/cards/hof_7.webp"));
+        assert_eq!(card.factions, vec!["Империя".to_string(), "Лес".to_string()]);
+        assert_eq!(card.cost, Some(5));
+        assert_eq!(card.text.as_deref(), Some("Когда Лорд Доминус входит в игру, возьмите карту. Ваши существа получают +1 к силе."));
+    }
+
+    #[test]
+    fn parse_card_page_without_image_fixture() {
+        // GIVEN
+        let html = include_str!("../tests/fixtures/card_command.html");
+        // WHEN
+        let card = parse_card_page(html, BASE_URL).unwrap();
+        // THEN
+        assert_eq!(card.name, "Приказ об отступлении");
+        assert_eq!(card.id_in_set, 118);
+        assert_eq!(card.rarity, "Бронза");
+        assert_eq!(card.types, vec!["Приказ".to_string()]);
+        assert_eq!(card.set, "КОВ");
+        assert_eq!(card.image_url, None);
+        assert!(card.factions.is_empty());
+        assert_eq!(card.cost, Some(0));
+        assert_eq!(card.text.as_deref(), Some("Верните целевое существо в руку владельца."));
+    }
+
+    #[test]
+    fn parse_card_page_without_name_then_err() {
+        let html = include_str!("../tests/fixtures/sets.html");
+        assert!(parse_card_page(html, BASE_URL).is_err());
+    }
+
+    #[test]
+    fn normalize_date_formats() {
+        assert_eq!(normalize_date("2025-10-21").unwrap(), "2025-10-21");
+        assert_eq!(normalize_date("2025-10-21T00:00:00+03:00").unwrap(), "2025-10-21");
+        assert_eq!(normalize_date(" 21.10.2025 ").unwrap(), "2025-10-21");
+        assert!(normalize_date("21/10/2025").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use std::{fmt::Display, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::locale::{self, Locale, Localize};
+
+/// Идентификатор сета в таблице `sets`. Зависит от порядка импорта,
+/// поэтому в JSON и TOML сет записывается кодом `SetCode`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct SetId(pub i64);
+
+impl Display for SetId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Код сета — короткое название из таблицы `sets`, например `КОВ`.
+/// Одинаков во всех БД, в отличие от `SetId`
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SetCode(pub String);
+
+impl Display for SetCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+impl From<&str> for SetCode {
+    fn from(code: &str) -> Self {
+        SetCode(code.to_string())
+    }
+}
+
+/// В JSON идентификатор из БД не записывается, сет определяется по `short_name`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SetInfo {
+    #[serde(skip)]
+    pub id: SetId,
+    pub name: String,
+    pub short_name: String,
+    /// Дата выхода в формате `YYYY-MM-DD`
+    pub release_date: String,
+    pub card_count: u32,
+    /// Карты сета не продаются в своих бустерах, а с некоторым шансом подменяют
+    /// карты основного сета (как Зал Славы)
+    pub is_substitution_pool: bool,
+}
+
+impl SetInfo {
+    pub fn code(&self) -> SetCode {
+        SetCode(self.short_name.clone())
+    }
+
+    /// Полное название на языке `locale`. Для сетов без перевода название из БД
+    pub fn localized_name(&self, locale: Locale) -> &str {
+        locale::set_translation(&self.short_name).map_or(&self.name, |(name, _)| name.get(locale))
+    }
+
+    /// Короткое название на языке `locale`. Для сетов без перевода название из БД
+    pub fn localized_short_name(&self, locale: Locale) -> &str {
+        locale::set_translation(&self.short_name).map_or(&self.short_name, |(_, short_name)| short_name.get(locale))
+    }
+}
+
+impl Display for SetInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_localized(Locale::default(), f)
+    }
+}
+
+impl Localize for SetInfo {
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.localized_short_name(locale))
+    }
+}
+
+/// Все известные сеты, загруженные из БД
+#[derive(Debug, Clone, Default)]
+pub struct SetRegistry {
+    sets: Vec<Arc<SetInfo>>,
+}
+
+impl SetRegistry {
+    pub fn new(sets: Vec<SetInfo>) -> Self {
+        SetRegistry {
+            sets: sets.into_iter().map(Arc::new).collect(),
+        }
+    }
+
+    pub async fn load(db_pull: &SqlitePool) -> Result<Self, Box<dyn std::error::Error>> {
+        let sets = sqlx::query!(
+            "SELECT id, name, short_name, release_date, card_count, is_substitution_pool
+            FROM sets
+            ORDER BY id"
+            ).fetch_all(db_pull)
+            .await?
+            .into_iter()
+            .map(|rec| {
+                Ok(SetInfo {
+                    id: SetId(rec.id),
+                    name: rec.name,
+                    short_name: rec.short_name,
+                    release_date: rec.release_date,
+                    card_count: u32::try_from(rec.card_count)?,
+                    is_substitution_pool: rec.is_substitution_pool != 0,
+                })
+            })
+            .collect::<Result<Vec<SetInfo>, Box<dyn std::error::Error>>>()?;
+        Ok(SetRegistry::new(sets))
+    }
+
+    pub fn get(&self, id: SetId) -> Option<&Arc<SetInfo>> {
+        self.sets.iter().find(|s| s.id == id)
+    }
+
+    pub fn by_short_name(&self, short_name: &str) -> Option<&Arc<SetInfo>> {
+        self.sets.iter().find(|s| s.short_name == short_name)
+    }
+
+    pub fn by_code(&self, code: &SetCode) -> Option<&Arc<SetInfo>> {
+        self.by_short_name(&code.0)
+    }
+
+    /// Сет, которым подменяются карты основного сета при выпадении Зала Славы
+    pub fn substitution_pool(&self) -> Option<&Arc<SetInfo>> {
+        self.sets.iter().find(|s| s.is_substitution_pool)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<SetInfo>> {
+        self.sets.iter()
+    }
+}
@@ -0,0 +1,345 @@
+//! Быстрая синхронная симуляция открытия бустеров.
+//! Генерация работает с номерами карт в неизменяемом снимке пулов и не выделяет память,
+//! испытания делятся на блоки с собственными зернами, поэтому результат не зависит от числа потоков
+
+use std::{collections::HashMap, sync::atomic::{AtomicUsize, Ordering}};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{BUSTER_SIZE, BusterRules, Card, CardBucket, CardBusterSlot, CardPool, CardRarity, DynamicDistribution, HallOfFameSource, SetId};
+
+/// Число бустеров в блоке. У каждого блока свое зерно
+pub(crate) const BLOCK_SIZE: usize = 4096;
+
+/// Номера карт бустера в `PoolSnapshot::cards`, в порядке слотов
+pub type BusterIndices = [usize; BUSTER_SIZE];
+
+/// Открытый бустер
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpenedBuster {
+    pub cards: BusterIndices,
+    /// Сколько раз выпавший Зал Славы был заменен картой основного сета
+    pub hall_of_fame_fallbacks: u32,
+}
+
+/// Пул одной редкости в слоте: номера карт и распределение по их весам
+#[derive(Debug, Clone)]
+struct SnapshotBucket {
+    cards: Vec<usize>,
+    distribution: DynamicDistribution,
+}
+
+impl SnapshotBucket {
+    fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        self.cards[self.distribution.generate(rng)]
+    }
+}
+
+/// Что происходит, если выпал Зал Славы. Политика `BusterRules` уже применена
+#[derive(Debug, Clone)]
+enum SnapshotHallOfFame {
+    Bucket(SnapshotBucket),
+    Fallback,
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+struct RarityPlan {
+    /// `None` только для редкостей с нулевой вероятностью
+    base: Option<SnapshotBucket>,
+    hall_of_fame: SnapshotHallOfFame,
+}
+
+#[derive(Debug, Clone)]
+struct SlotPlan {
+    rarities: DynamicDistribution,
+    plans: Vec<RarityPlan>,
+}
+
+/// Неизменяемый снимок карт сета и Зала Славы, подготовленный для правил бустера
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    set: SetId,
+    cards: Vec<Card>,
+    slots: Vec<SlotPlan>,
+    hall_of_fame_chanse: f64,
+}
+
+impl PoolSnapshot {
+    /// Проверяет, что для каждой возможной редкости каждого слота есть карты основного сета
+    pub(crate) fn new(base: &CardPool, rules: &BusterRules, set: SetId) -> Result<PoolSnapshot, Box<dyn std::error::Error>> {
+        let hall_of_fame_set = base.sets.substitution_pool().map(|s| s.id);
+        let mut cards = Vec::new();
+        let mut first_index: HashMap<(CardBusterSlot, SetId, CardRarity), usize> = HashMap::new();
+        // Номер первой карты пула в снимке, пул копируется в снимок при первом обращении
+        let mut index = |key: (CardBusterSlot, SetId, CardRarity)| -> usize {
+            let source: &CardBucket = &base.cards[&key];
+            *first_index.entry(key).or_insert_with(|| {
+                cards.extend(source.cards.iter().cloned());
+                cards.len() - source.cards.len()
+            })
+        };
+
+        let mut slots = Vec::new();
+        for (slot, rarities) in rules.layout() {
+            let mut plans = Vec::new();
+            for &(rarity, chanse) in rarities.iter() {
+                let base_bucket = base.bucket(slot, set, rarity).map(|source| {
+                    let start = index((slot, set, rarity));
+                    SnapshotBucket { cards: (start..start + source.cards.len()).collect(), distribution: source.distribution.clone().unwrap() }
+                });
+                if chanse > 0.0 && base_bucket.is_none() {
+                    return Err(format!("Error. Empty pool: {} {} {}", rules.set, slot, rarity).into());
+                }
+                let hall_of_fame = match (hall_of_fame_set, base.hall_of_fame_source(slot, rarity, rules.hall_of_fame_policy)) {
+                    (Some(set), HallOfFameSource::Rarity(source)) => {
+                        let start = index((slot, set, rarity));
+                        SnapshotHallOfFame::Bucket(SnapshotBucket {
+                            cards: (start..start + source.cards.len()).collect(),
+                            distribution: source.distribution.clone().unwrap(),
+                        })
+                    }
+                    (Some(set), HallOfFameSource::Slot(source)) => SnapshotHallOfFame::Bucket(SnapshotBucket {
+                        cards: source.entries.iter().map(|&(rarity, i)| index((slot, set, rarity)) + i).collect(),
+                        distribution: source.distribution.clone().unwrap(),
+                    }),
+                    (_, HallOfFameSource::Fallback) => SnapshotHallOfFame::Fallback,
+                    _ => SnapshotHallOfFame::Skip,
+                };
+                plans.push(RarityPlan { base: base_bucket, hall_of_fame });
+            }
+            let rarities = DynamicDistribution::new(rarities.iter().map(|(_, p)| *p).collect())?;
+            slots.push(SlotPlan { rarities, plans });
+        }
+
+        Ok(PoolSnapshot {
+            set,
+            cards,
+            slots,
+            hall_of_fame_chanse: rules.hall_of_fame_chanse,
+        })
+    }
+
+    /// Основной сет правил, для которых подготовлен снимок
+    pub fn set(&self) -> SetId {
+        self.set
+    }
+
+    /// Все карты снимка, номера в `BusterIndices` указывают на них
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    pub fn card(&self, index: usize) -> &Card {
+        &self.cards[index]
+    }
+
+    /// Заполняет `buster` номерами карт без выделения памяти
+    pub fn generate_into<R: Rng + ?Sized>(&self, rng: &mut R, buster: &mut OpenedBuster) {
+        buster.hall_of_fame_fallbacks = 0;
+        for (slot, index) in self.slots.iter().zip(buster.cards.iter_mut()) {
+            let plan = &slot.plans[slot.rarities.generate(rng)];
+            let hall_of_fame = rng.random::<f64>() < self.hall_of_fame_chanse;
+            let bucket = match (&plan.hall_of_fame, &plan.base) {
+                (SnapshotHallOfFame::Bucket(hall_of_fame_bucket), _) if hall_of_fame => hall_of_fame_bucket,
+                (SnapshotHallOfFame::Fallback, Some(base)) if hall_of_fame => {
+                    buster.hall_of_fame_fallbacks += 1;
+                    base
+                }
+                (_, Some(base)) => base,
+                _ => unreachable!("rarities with empty pools have zero chanse"),
+            };
+            *index = bucket.choose(rng);
+        }
+    }
+}
+
+/// Накопитель результатов симуляции. Каждый блок испытаний заполняет свой накопитель,
+/// затем накопители объединяются в порядке блоков
+pub trait Accumulator: Send + Sized {
+    fn new(pool: &PoolSnapshot) -> Self;
+
+    fn record(&mut self, pool: &PoolSnapshot, buster: &OpenedBuster);
+
+    fn merge(&mut self, other: Self);
+}
+
+/// Число бустеров и число копий каждой карты снимка
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardCounts {
+    pub busters: u64,
+    pub counts: Vec<u64>,
+    /// Сколько раз выпавший Зал Славы был заменен картой основного сета
+    pub hall_of_fame_fallbacks: u64,
+}
+
+impl CardCounts {
+    /// Наблюдаемое среднее число копий карты в бустере
+    pub fn per_buster(&self, index: usize) -> f64 {
+        self.counts[index] as f64 / self.busters as f64
+    }
+}
+
+impl Accumulator for CardCounts {
+    fn new(pool: &PoolSnapshot) -> Self {
+        CardCounts { busters: 0, counts: vec![0; pool.cards.len()], hall_of_fame_fallbacks: 0 }
+    }
+
+    fn record(&mut self, _pool: &PoolSnapshot, buster: &OpenedBuster) {
+        self.busters += 1;
+        self.hall_of_fame_fallbacks += buster.hall_of_fame_fallbacks as u64;
+        buster.cards.iter().for_each(|&i| self.counts[i] += 1);
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.busters += other.busters;
+        self.hall_of_fame_fallbacks += other.hall_of_fame_fallbacks;
+        self.counts.iter_mut().zip(other.counts).for_each(|(c, o)| *c += o);
+    }
+}
+
+/// Зерно блока: перемешивание SplitMix64, чтобы соседние блоки не давали похожих зерен
+pub(crate) fn block_seed(seed: u64, block: usize) -> u64 {
+    let mut z = seed.wrapping_add((block as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+fn run_block<A: Accumulator>(pool: &PoolSnapshot, seed: u64, block: usize, trials: usize) -> A {
+    let mut rng = StdRng::seed_from_u64(block_seed(seed, block));
+    let mut accumulator = A::new(pool);
+    let mut buster = OpenedBuster::default();
+    let start = block * BLOCK_SIZE;
+    for _ in start..trials.min(start + BLOCK_SIZE) {
+        pool.generate_into(&mut rng, &mut buster);
+        accumulator.record(pool, &buster);
+    }
+    accumulator
+}
+
+/// Открывает `trials` бустеров в `threads` потоках.
+/// При одинаковом зерне результат одинаков при любом числе потоков
+pub fn simulate<A: Accumulator>(pool: &PoolSnapshot, trials: usize, seed: u64, threads: usize) -> A {
+    simulate_with_progress(pool, trials, seed, threads, &AtomicUsize::new(0))
+}
+
+/// Как `simulate`, но после каждого блока добавляет число открытых в нем бустеров к `done`
+pub(crate) fn simulate_with_progress<A: Accumulator>(pool: &PoolSnapshot, trials: usize, seed: u64, threads: usize, done: &AtomicUsize) -> A {
+    let results = run_blocks(trials.div_ceil(BLOCK_SIZE), threads, |block| {
+        let result = run_block::<A>(pool, seed, block, trials);
+        done.fetch_add(BLOCK_SIZE.min(trials - block * BLOCK_SIZE), Ordering::Relaxed);
+        result
+    });
+    let mut accumulator = A::new(pool);
+    results.into_iter().for_each(|result| accumulator.merge(result));
+    accumulator
+}
+
+/// Выполняет `run` для блоков `0..blocks` в `threads` потоках.
+/// Результаты возвращаются в порядке блоков
+pub(crate) fn run_blocks<T: Send>(blocks: usize, threads: usize, run: impl Fn(usize) -> T + Sync) -> Vec<T> {
+    let next_block = AtomicUsize::new(0);
+    let mut results: Vec<(usize, T)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.clamp(1, blocks.max(1)))
+            .map(|_| scope.spawn(|| {
+                let mut results = Vec::new();
+                loop {
+                    let block = next_block.fetch_add(1, Ordering::Relaxed);
+                    if block >= blocks {
+                        break results;
+                    }
+                    results.push((block, run(block)));
+                }
+            }))
+            .collect();
+        workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+    });
+
+    results.sort_by_key(|(block, _)| *block);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Число потоков по умолчанию: все доступные ядра
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::test;
+    use super::*;
+
+    use crate::fixture::{self, FixtureBuilder};
+    use crate::HallOfFamePolicy;
+
+    #[test]
+    async fn simulate_counts_all_trials() {
+        // GIVEN
+        let pool = fixture::snapshot(0.1).await;
+        // WHEN
+        let counts: CardCounts = simulate(&pool, BLOCK_SIZE * 2 + 17, 1, 3);
+        // THEN
+        assert_eq!(counts.busters, (BLOCK_SIZE * 2 + 17) as u64);
+        assert_eq!(counts.counts.iter().sum::<u64>(), counts.busters * BUSTER_SIZE as u64);
+    }
+
+    #[test]
+    async fn simulate_with_same_seed_then_same_result_for_any_threads() {
+        // GIVEN
+        let pool = fixture::snapshot(0.3).await;
+        // WHEN
+        let single: CardCounts = simulate(&pool, 20000, 7, 1);
+        let parallel: CardCounts = simulate(&pool, 20000, 7, 4);
+        let other_seed: CardCounts = simulate(&pool, 20000, 8, 4);
+        // THEN
+        assert_eq!(single, parallel);
+        assert_ne!(single, other_seed);
+    }
+
+    #[test]
+    async fn generate_into_respects_slots_and_rarities() {
+        // GIVEN
+        let pool = fixture::snapshot(0.0).await;
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut buster = OpenedBuster::default();
+        // WHEN
+        pool.generate_into(&mut rng, &mut buster);
+        // THEN
+        let buster: Vec<&Card> = buster.cards.iter().map(|&i| pool.card(i)).collect();
+        assert_eq!(buster[0].buster_slot, CardBusterSlot::Hero);
+        assert!(buster[1..4].iter().all(|c| c.buster_slot == CardBusterSlot::Command));
+        assert_eq!(buster[4].rarity, CardRarity::Gold);
+        assert!(buster[9..].iter().all(|c| c.rarity == CardRarity::Bronze && c.set.id == SetId(2)));
+    }
+
+    #[test]
+    async fn simulate_counts_hall_of_fame_fallbacks() {
+        // GIVEN
+        let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+        let fallback = card_pool.snapshot(&fixture::rules(1.0)).unwrap();
+        let skip = card_pool.snapshot(&fixture::rules(1.0).with_hall_of_fame_policy(HallOfFamePolicy::Skip)).unwrap();
+        // WHEN
+        let fallback: CardCounts = simulate(&fallback, 1000, 1, 2);
+        let skip: CardCounts = simulate(&skip, 1000, 1, 2);
+        // THEN
+        // Три приказа, три серебряные и девять бронзовых основных карт всегда заменяются
+        assert!((15 * 1000..=17 * 1000).contains(&fallback.hall_of_fame_fallbacks));
+        assert_eq!(skip.hall_of_fame_fallbacks, 0);
+    }
+
+    #[test]
+    async fn snapshot_with_empty_pool_then_error() {
+        // GIVEN
+        let db_pull = FixtureBuilder::standard()
+            .without(fixture::KOV, CardBusterSlot::Command, CardRarity::Gold)
+            .build()
+            .await;
+        let card_pool = CardPool::load(&db_pull).await.unwrap();
+        // WHEN
+        let pool = card_pool.snapshot(&fixture::rules(0.0));
+        // THEN
+        assert!(pool.is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Статистические тесты генератора: критерий хи-квадрат на фиксированных зернах.
+//! Зерна фиксированы, поэтому тесты детерминированы, а уровень значимости 0.001
+//! оставляет запас для смены алгоритмов генерации
+
+use std::collections::HashMap;
+
+use rand::{SeedableRng, rngs::StdRng};
+use tokio::test;
+
+use crate::fixture::{self, FixtureBuilder};
+use crate::*;
+
+const SEED: u64 = 20260116;
+const BUSTERS: usize = 5000;
+
+/// Квантиль уровня 0.999 стандартного нормального распределения
+const Z_999: f64 = 3.090232;
+
+fn chi_square(observed: &[u64], probabilities: &[f64]) -> f64 {
+    let total = observed.iter().sum::<u64>() as f64;
+    observed.iter().zip(probabilities)
+        .filter(|(_, p)| **p > 0.0)
+        .map(|(&o, &p)| {
+            let expected = total * p;
+            (o as f64 - expected).powi(2) / expected
+        })
+        .sum()
+}
+
+/// Табличные критические значения хи-квадрат для уровня значимости 0.001
+const CHI_SQUARE_TABLE: [f64; 10] = [10.828, 13.816, 16.266, 18.467, 20.515, 22.458, 24.322, 26.124, 27.877, 29.588];
+
+/// Критическое значение хи-квадрат с уровнем значимости 0.001.
+/// Для больших степеней свободы используется приближение Уилсона-Хилферти
+fn chi_square_critical(degrees_of_freedom: usize) -> f64 {
+    if let Some(&value) = CHI_SQUARE_TABLE.get(degrees_of_freedom.wrapping_sub(1)) {
+        return value;
+    }
+    let k = degrees_of_freedom as f64;
+    let a = 2.0 / (9.0 * k);
+    k * (1.0 - a + Z_999 * a.sqrt()).powi(3)
+}
+
+/// Проверяет, что наблюдаемые частоты согласуются с вероятностями
+fn assert_fits(observed: &[u64], probabilities: &[f64]) {
+    assert_eq!(observed.len(), probabilities.len());
+    let nonzero = probabilities.iter().filter(|p| **p > 0.0).count();
+    for (o, p) in observed.iter().zip(probabilities) {
+        if *p == 0.0 {
+            assert_eq!(*o, 0, "observed {:?} for probabilities {:?}", observed, probabilities);
+        }
+    }
+    if nonzero < 2 {
+        return;
+    }
+    let statistic = chi_square(observed, probabilities);
+    let critical = chi_square_critical(nonzero - 1);
+    assert!(
+        statistic < critical,
+        "chi-square {:.2} >= {:.2}: observed {:?} for probabilities {:?}",
+        statistic, critical, observed, probabilities,
+    );
+}
+
+fn rarity_index(rarity: CardRarity) -> usize {
+    match rarity {
+        CardRarity::Bronze => 0,
+        CardRarity::Silver => 1,
+        CardRarity::Gold => 2,
+    }
+}
+
+async fn generate_busters(db_pull: sqlx::SqlitePool, rules: &BusterRules) -> Vec<[Card; BUSTER_SIZE]> {
+    let mut card_base = CardBase::with_seed(db_pull, SEED).await.unwrap();
+    let mut busters = Vec::with_capacity(BUSTERS);
+    for _ in 0..BUSTERS {
+        busters.push(card_base.generate_buster(rules).unwrap());
+    }
+    busters
+}
+
+/// Частоты редкостей в позиции бустера (бронза, серебро, золото)
+fn rarity_counts(busters: &[[Card; BUSTER_SIZE]], position: usize) -> [u64; 3] {
+    let mut counts = [0; 3];
+    busters.iter().for_each(|b| counts[rarity_index(b[position].rarity)] += 1);
+    counts
+}
+
+#[test]
+async fn chi_square_critical_matches_table() {
+    // Приближение для 11 и 20 степеней свободы отличается от табличных 31.264 и 45.315 меньше чем на 1%
+    assert!((chi_square_critical(11) - 31.264).abs() < 0.31);
+    assert!((chi_square_critical(20) - 45.315).abs() < 0.45);
+    assert_eq!(chi_square_critical(2), 13.816);
+}
+
+#[test]
+async fn assert_fits_rejects_skewed_counts() {
+    let result = std::panic::catch_unwind(|| assert_fits(&[600, 400], &[0.5, 0.5]));
+    assert!(result.is_err());
+}
+
+#[test]
+async fn distribution_generate_fits_values() {
+    // GIVEN
+    let probabilities = [0.2, 0.5, 0.3];
+    let distribution = Distribution::new(probabilities).unwrap();
+    let mut rng = StdRng::seed_from_u64(SEED);
+    // WHEN
+    let mut counts = [0; 3];
+    for _ in 0..20000 {
+        counts[distribution.generate(&mut rng)] += 1;
+    }
+    // THEN
+    assert_fits(&counts, &probabilities);
+}
+
+#[test]
+async fn distribution_generate_with_zero_values_never_returns_them() {
+    // GIVEN
+    let probabilities = [0.0, 0.25, 0.0, 0.75, 0.0];
+    let distribution = Distribution::new(probabilities).unwrap();
+    let mut rng = StdRng::seed_from_u64(SEED);
+    // WHEN
+    let mut counts = [0; 5];
+    for _ in 0..20000 {
+        counts[distribution.generate(&mut rng)] += 1;
+    }
+    // THEN
+    assert_fits(&counts, &probabilities);
+}
+
+#[test]
+async fn dynamic_distribution_from_weights_fits_weights() {
+    // GIVEN
+    let weights: Vec<f64> = (1..=30).map(|i| (i % 7) as f64).collect();
+    let distribution = DynamicDistribution::from_weights(&weights).unwrap();
+    let mut rng = StdRng::seed_from_u64(SEED);
+    // WHEN
+    let mut counts = vec![0; weights.len()];
+    for _ in 0..100000 {
+        counts[distribution.generate(&mut rng)] += 1;
+    }
+    // THEN
+    assert_fits(&counts, distribution.values());
+}
+
+#[test]
+async fn generate_buster_rarity_rates_fit_rules() {
+    // GIVEN
+    let rules = fixture::rules(0.0);
+    // WHEN
+    let busters = generate_busters(fixture::standard().await, &rules).await;
+    // THEN
+    // Герой серебро/золото
+    assert_fits(&rarity_counts(&busters, 0), &[0.0, 0.8, 0.2]);
+    // Приказ случайной редкости
+    assert_fits(&rarity_counts(&busters, 1), &[0.7, 0.2, 0.1]);
+    // Приказ бронза/серебро
+    assert_fits(&rarity_counts(&busters, 2), &[0.75, 0.25, 0.0]);
+    // Основная карта серебро/золото
+    assert_fits(&rarity_counts(&busters, 5), &[0.0, 0.7, 0.3]);
+}
+
+#[test]
+async fn generate_buster_chooses_uniformly_within_pool() {
+    // GIVEN
+    let rules = fixture::rules(0.0);
+    let db_pull = FixtureBuilder::standard()
+        .cards(fixture::KOV, CardBusterSlot::BasicCard, CardRarity::Bronze, 5)
+        .build()
+        .await;
+    // WHEN
+    let busters = generate_busters(db_pull, &rules).await;
+    // THEN
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    busters.iter()
+        .flat_map(|b| b[9..].iter())
+        .for_each(|c| *counts.entry(c.name.clone()).or_default() += 1);
+    let observed: Vec<u64> = counts.values().copied().collect();
+    assert_eq!(observed.len(), 7);
+    assert_fits(&observed, &[1.0 / 7.0; 7]);
+}
+
+#[test]
+async fn generate_buster_chooses_by_card_weights() {
+    // GIVEN
+    let rules = fixture::rules(0.0);
+    let builder = FixtureBuilder::standard()
+        .weighted_card(fixture::KOV, CardBusterSlot::BasicCard, CardRarity::Gold, 2.0);
+    let weighted = builder.last_card_name();
+    let db_pull = builder.build().await;
+    // WHEN
+    let busters = generate_busters(db_pull, &rules).await;
+    // THEN
+    // Пятый слот всегда золотая основная карта: две карты с весом 1 и одна с весом 2
+    let mut counts: HashMap<&str, u64> = HashMap::new();
+    busters.iter().for_each(|b| *counts.entry(b[4].name.as_str()).or_default() += 1);
+    let weighted = counts.remove(weighted.as_str()).unwrap_or(0);
+    let others: Vec<u64> = counts.into_values().collect();
+    assert_eq!(others.len(), 2);
+    assert_fits(&[others[0], others[1], weighted], &[0.25, 0.25, 0.5]);
+}
+
+#[test]
+async fn generate_buster_hall_of_fame_rate_fits_chanse() {
+    // GIVEN
+    let hall_of_fame_chanse = 0.3;
+    let rules = fixture::rules(hall_of_fame_chanse);
+    // WHEN
+    let busters = generate_busters(fixture::standard().await, &rules).await;
+    // THEN
+    // В Зале Славы фикстуры есть золотые основные карты, поэтому замена всегда удается
+    let hall_of_fame = busters.iter().filter(|b| b[4].set.is_substitution_pool).count() as u64;
+    assert_fits(&[BUSTERS as u64 - hall_of_fame, hall_of_fame], &[1.0 - hall_of_fame_chanse, hall_of_fame_chanse]);
+}
+
+#[test]
+async fn generate_buster_with_same_seed_then_same_busters() {
+    // GIVEN
+    let rules = fixture::rules(0.5);
+    let mut first = CardBase::with_seed(fixture::standard().await, SEED).await.unwrap();
+    let mut second = CardBase::with_seed(fixture::standard().await, SEED).await.unwrap();
+    // WHEN
+    let first = first.generate_buster(&rules).unwrap();
+    let second = second.generate_buster(&rules).unwrap();
+    // THEN
+    let names = |b: &[Card; BUSTER_SIZE]| b.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&first), names(&second));
+}
+
+/// Частоты героев в симуляции против аналитических `pull_rates`
+async fn assert_simulated_heroes_fit_pull_rates(rules: BusterRules) {
+    let card_pool = CardPool::load(&fixture::standard().await).await.unwrap();
+    let pool = card_pool.snapshot(&rules).unwrap();
+    let pull_rates = card_pool.pull_rates(&rules).unwrap();
+    let counts: simulation::CardCounts = simulation::simulate(&pool, BUSTERS * 4, SEED, 4);
+    // Герои выпадают только в первом слоте, поэтому их частоты образуют одно распределение
+    let heroes: Vec<(usize, f64)> = pool.cards().iter().enumerate()
+        .filter(|(_, c)| c.buster_slot == CardBusterSlot::Hero)
+        .map(|(i, c)| {
+            let rate = pull_rates.iter().find(|r| r.card.set.id == c.set.id && r.card.id_in_set == c.id_in_set).unwrap();
+            (i, rate.per_buster)
+        })
+        .collect();
+    let observed: Vec<u64> = heroes.iter().map(|(i, _)| counts.counts[*i]).collect();
+    let probabilities: Vec<f64> = heroes.iter().map(|(_, p)| *p).collect();
+    assert_fits(&observed, &probabilities);
+}
+
+#[test]
+async fn simulate_hero_rates_fit_pull_rates() {
+    assert_simulated_heroes_fit_pull_rates(fixture::rules(0.3)).await;
+}
+
+#[test]
+async fn simulate_hero_rates_with_reroll_fit_pull_rates() {
+    assert_simulated_heroes_fit_pull_rates(fixture::rules(0.3).with_hall_of_fame_policy(HallOfFamePolicy::RerollRarity)).await;
+}
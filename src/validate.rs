@@ -0,0 +1,274 @@
+use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::{Card, CardBusterSlot, CardRarity, Locale, Localize, SetInfo, SetRegistry};
+
+/// Пары (слот, редкость), которые может запросить `CardBase::generate_buster`.
+/// Если пул основного сета для одной из них пуст, бустер не сгенерируется
+const BUSTER_POOLS: [(CardBusterSlot, CardRarity); 8] = [
+    (CardBusterSlot::Hero, CardRarity::Silver),
+    (CardBusterSlot::Hero, CardRarity::Gold),
+    (CardBusterSlot::Command, CardRarity::Bronze),
+    (CardBusterSlot::Command, CardRarity::Silver),
+    (CardBusterSlot::Command, CardRarity::Gold),
+    (CardBusterSlot::BasicCard, CardRarity::Bronze),
+    (CardBusterSlot::BasicCard, CardRarity::Silver),
+    (CardBusterSlot::BasicCard, CardRarity::Gold),
+];
+
+/// Результат проверки импортированных карт одного сета
+#[derive(Debug, Clone, Serialize)]
+pub struct SetReport {
+    pub set: Arc<SetInfo>,
+    pub imported: usize,
+    /// Номера из диапазона `1..=card_count`, для которых нет карты
+    pub missing_ids: Vec<u32>,
+    /// Номера вне диапазона `1..=card_count`
+    pub unexpected_ids: Vec<u32>,
+    /// Номера, под которыми импортировано несколько карт, и имена этих карт
+    pub duplicate_ids: Vec<(u32, Vec<String>)>,
+    pub without_image: Vec<(u32, String)>,
+    /// Пулы, из-за которых `generate_buster` вернет `None`.
+    /// Для сетов-подмен не заполняется, там пустой пул означает замену на основной сет
+    pub empty_pools: Vec<(CardBusterSlot, CardRarity)>,
+    /// Карты с отрицательным или бесконечным весом, из-за них `CardPool::load` вернет ошибку
+    pub invalid_weights: Vec<(u32, String, f64)>,
+    /// Непустые пулы, где у всех карт нулевой вес: из них не выпадет ни одна карта
+    pub zero_weight_pools: Vec<(CardBusterSlot, CardRarity)>,
+}
+
+impl SetReport {
+    pub fn analyze(set: Arc<SetInfo>, cards: &[Card]) -> Self {
+        let mut by_id: BTreeMap<u32, Vec<&Card>> = BTreeMap::new();
+        cards.iter().for_each(|c| by_id.entry(c.id_in_set).or_default().push(c));
+
+        let missing_ids = (1..=set.card_count).filter(|id| !by_id.contains_key(id)).collect();
+        let unexpected_ids = by_id.keys().copied().filter(|id| !(1..=set.card_count).contains(id)).collect();
+        let duplicate_ids = by_id.iter()
+            .filter(|(_, cards)| cards.len() > 1)
+            .map(|(&id, cards)| (id, cards.iter().map(|c| c.name.clone()).collect()))
+            .collect();
+        let without_image = cards.iter()
+            .filter(|c| c.image_url.is_none())
+            .map(|c| (c.id_in_set, c.name.clone()))
+            .collect();
+        let empty_pools = if set.is_substitution_pool {
+            Vec::new()
+        } else {
+            BUSTER_POOLS.into_iter()
+                .filter(|&(slot, rarity)| !cards.iter().any(|c| c.buster_slot == slot && c.rarity == rarity))
+                .collect()
+        };
+
+        let invalid_weights = cards.iter()
+            .filter(|c| !c.weight.is_finite() || c.weight < 0.0)
+            .map(|c| (c.id_in_set, c.name.clone(), c.weight))
+            .collect();
+        let zero_weight_pools = CardBusterSlot::iter()
+            .flat_map(|slot| CardRarity::iter().map(move |rarity| (slot, rarity)))
+            .filter(|&(slot, rarity)| {
+                let mut pool = cards.iter().filter(|c| c.buster_slot == slot && c.rarity == rarity).peekable();
+                pool.peek().is_some() && pool.all(|c| c.weight == 0.0)
+            })
+            .collect();
+
+        SetReport {
+            set,
+            imported: cards.len(),
+            missing_ids,
+            unexpected_ids,
+            duplicate_ids,
+            without_image,
+            empty_pools,
+            invalid_weights,
+            zero_weight_pools,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.imported == self.set.card_count as usize
+            && self.missing_ids.is_empty()
+            && self.unexpected_ids.is_empty()
+            && self.duplicate_ids.is_empty()
+            && self.without_image.is_empty()
+            && self.empty_pools.is_empty()
+            && self.invalid_weights.is_empty()
+            && self.zero_weight_pools.is_empty()
+    }
+}
+
+impl Display for SetReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_localized(Locale::default(), f)
+    }
+}
+
+/// Отчет всегда на английском, от языка зависят только названия слотов и редкостей
+impl Localize for SetReport {
+    fn fmt_localized(&self, locale: Locale, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.is_ok() { "OK" } else { "PROBLEMS" };
+        writeln!(f, "{} ({}): {}", self.set.localized(locale), self.set.localized_name(locale), status)?;
+        writeln!(f, "  cards: {} of {}", self.imported, self.set.card_count)?;
+        if !self.missing_ids.is_empty() {
+            writeln!(f, "  missing id_in_set: {:?}", self.missing_ids)?;
+        }
+        if !self.unexpected_ids.is_empty() {
+            writeln!(f, "  id_in_set out of range: {:?}", self.unexpected_ids)?;
+        }
+        for (id, names) in self.duplicate_ids.iter() {
+            writeln!(f, "  duplicate id_in_set {}: {}", id, names.join(", "))?;
+        }
+        for (id, name) in self.without_image.iter() {
+            writeln!(f, "  no image: {} {}", id, name)?;
+        }
+        for (slot, rarity) in self.empty_pools.iter() {
+            writeln!(f, "  empty pool: {} {}", slot.localized(locale), rarity.localized(locale))?;
+        }
+        for (id, name, weight) in self.invalid_weights.iter() {
+            writeln!(f, "  invalid weight {}: {} {}", weight, id, name)?;
+        }
+        for (slot, rarity) in self.zero_weight_pools.iter() {
+            writeln!(f, "  zero weight pool: {} {}", slot.localized(locale), rarity.localized(locale))?;
+        }
+        Ok(())
+    }
+}
+
+/// Проверяет все сеты из БД
+pub async fn validate_sets(db_pull: &SqlitePool) -> Result<Vec<SetReport>, Box<dyn std::error::Error>> {
+    let sets = SetRegistry::load(db_pull).await?;
+    let mut reports = Vec::new();
+    for set in sets.iter() {
+        let mut cards = crate::load_set_cards(db_pull, set).await?;
+        cards.sort_by_key(|c| c.id_in_set);
+        reports.push(SetReport::analyze(set.clone(), &cards));
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SetId;
+    use crate::fixture::{self, FixtureBuilder};
+
+    fn set(card_count: u32, is_substitution_pool: bool) -> Arc<SetInfo> {
+        Arc::new(SetInfo {
+            id: SetId(1),
+            name: "Тестовый".to_string(),
+            short_name: "ТСТ".to_string(),
+            release_date: "2025-10-21".to_string(),
+            card_count,
+            is_substitution_pool,
+        })
+    }
+
+    fn card(set: &Arc<SetInfo>, id_in_set: u32, slot: CardBusterSlot, rarity: CardRarity) -> Card {
+        let image_url = Some(format!("https://response-world.ru/{}.webp", id_in_set));
+        Card::new(format!("Карта {}", id_in_set), id_in_set, rarity, slot, set.clone(), image_url, crate::DEFAULT_CARD_WEIGHT)
+    }
+
+    fn complete_set() -> (Arc<SetInfo>, Vec<Card>) {
+        let set = set(BUSTER_POOLS.len() as u32, false);
+        let cards = BUSTER_POOLS.iter().enumerate()
+            .map(|(i, &(slot, rarity))| card(&set, i as u32 + 1, slot, rarity))
+            .collect();
+        (set, cards)
+    }
+
+    #[test]
+    fn analyze_complete_set_then_ok() {
+        // GIVEN
+        let (set, cards) = complete_set();
+        // WHEN
+        let report = SetReport::analyze(set, &cards);
+        // THEN
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    #[test]
+    fn analyze_set_with_gap_and_duplicate() {
+        // GIVEN
+        let (set, mut cards) = complete_set();
+        cards[2].id_in_set = 2;
+        cards.push(card(&set, 12, CardBusterSlot::BasicCard, CardRarity::Bronze));
+        // WHEN
+        let report = SetReport::analyze(set, &cards);
+        // THEN
+        assert!(!report.is_ok());
+        assert_eq!(report.missing_ids, vec![3]);
+        assert_eq!(report.unexpected_ids, vec![12]);
+        assert_eq!(report.duplicate_ids, vec![(2, vec!["Карта 2".to_string(), "Карта 3".to_string()])]);
+    }
+
+    #[test]
+    fn analyze_set_with_card_without_image() {
+        // GIVEN
+        let (set, mut cards) = complete_set();
+        cards[0].image_url = None;
+        // WHEN
+        let report = SetReport::analyze(set, &cards);
+        // THEN
+        assert_eq!(report.without_image, vec![(1, "Карта 1".to_string())]);
+    }
+
+    #[test]
+    fn analyze_set_without_gold_heroes_then_empty_pool() {
+        // GIVEN
+        let (set, mut cards) = complete_set();
+        cards[1].rarity = CardRarity::Silver;
+        // WHEN
+        let report = SetReport::analyze(set, &cards);
+        // THEN
+        assert_eq!(report.empty_pools, vec![(CardBusterSlot::Hero, CardRarity::Gold)]);
+    }
+
+    #[test]
+    fn analyze_substitution_pool_without_commands_then_no_empty_pools() {
+        // GIVEN
+        let set = set(1, true);
+        let cards = vec![card(&set, 1, CardBusterSlot::Hero, CardRarity::Gold)];
+        // WHEN
+        let report = SetReport::analyze(set, &cards);
+        // THEN
+        assert!(report.empty_pools.is_empty());
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn analyze_set_with_zero_and_invalid_weights() {
+        // GIVEN
+        let (set, mut cards) = complete_set();
+        cards[1].weight = 0.0;
+        cards[2].weight = f64::INFINITY;
+        // WHEN
+        let report = SetReport::analyze(set, &cards);
+        // THEN
+        assert!(!report.is_ok());
+        assert_eq!(report.zero_weight_pools, vec![(CardBusterSlot::Hero, CardRarity::Gold)]);
+        assert_eq!(report.invalid_weights, vec![(3, "Карта 3".to_string(), f64::INFINITY)]);
+        assert!(report.empty_pools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn validate_sets_from_fixture() {
+        // GIVEN
+        let db_pull = FixtureBuilder::standard()
+            .without(fixture::KOV, CardBusterSlot::Command, CardRarity::Gold)
+            .card_without_image(fixture::KOV, CardBusterSlot::BasicCard, CardRarity::Bronze)
+            .build()
+            .await;
+        // WHEN
+        let reports = validate_sets(&db_pull).await.unwrap();
+        // THEN
+        let kov = reports.iter().find(|r| r.set.short_name == fixture::KOV).unwrap();
+        assert_eq!(kov.imported, 15);
+        assert_eq!(kov.without_image, vec![(15, "КОВ 15".to_string())]);
+        assert_eq!(kov.empty_pools, vec![(CardBusterSlot::Command, CardRarity::Gold)]);
+        let hall_of_fame = reports.iter().find(|r| r.set.short_name == fixture::HALL_OF_FAME).unwrap();
+        assert_eq!(hall_of_fame.imported, 3);
+        assert!(hall_of_fame.empty_pools.is_empty());
+    }
+}